@@ -0,0 +1,76 @@
+//! Touch ID unlock for encrypted workspaces
+//!
+//! On macOS the workspace password is kept in the data protection Keychain,
+//! readable only after biometric authentication. Other platforms (and Macs
+//! without Touch ID) return errors, and the frontend falls back to the password prompt.
+
+/// Keychain account holding the workspace password
+#[cfg(target_os = "macos")]
+const KEYCHAIN_ACCOUNT: &str = "workspace-password";
+
+/// `errSecItemNotFound`
+#[cfg(target_os = "macos")]
+const ERR_SEC_ITEM_NOT_FOUND: i32 = -25300;
+
+/// Keychain options for this app's item; `service` is the app identifier
+#[cfg(target_os = "macos")]
+fn keychain_options(service: &str) -> security_framework::passwords::PasswordOptions {
+    let mut options =
+        security_framework::passwords::PasswordOptions::new_generic_password(service, KEYCHAIN_ACCOUNT);
+    options.use_protected_keychain();
+    options
+}
+
+/// Store the workspace password, protected by the currently enrolled fingerprints
+#[cfg(target_os = "macos")]
+pub fn store(service: &str, password: &str) -> Result<(), String> {
+    use security_framework::passwords::{set_generic_password_options, AccessControlOptions};
+
+    // Replace rather than update, so the access control is always the current one
+    forget(service)?;
+
+    let mut options = keychain_options(service);
+    options.set_access_control_options(AccessControlOptions::BIOMETRY_CURRENT_SET);
+    set_generic_password_options(password.as_bytes(), options)
+        .map_err(|e| format!("Biometric unlock is unavailable: {}", e))
+}
+
+/// Read the stored password; shows the Touch ID prompt
+#[cfg(target_os = "macos")]
+pub fn unlock(service: &str) -> Result<String, String> {
+    let password = security_framework::passwords::generic_password(keychain_options(service))
+        .map_err(|e| match e.code() {
+            ERR_SEC_ITEM_NOT_FOUND => "No password stored for biometric unlock".to_string(),
+            _ => format!("Biometric unlock failed: {}", e),
+        })?;
+    String::from_utf8(password).map_err(|e| format!("Stored password is not valid UTF-8: {}", e))
+}
+
+/// Remove the stored password (no error if there is none)
+#[cfg(target_os = "macos")]
+pub fn forget(service: &str) -> Result<(), String> {
+    use security_framework::passwords::delete_generic_password_options;
+
+    match delete_generic_password_options(keychain_options(service)) {
+        Err(e) if e.code() != ERR_SEC_ITEM_NOT_FOUND => {
+            Err(format!("Failed to remove biometric password: {}", e))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn store(_service: &str, _password: &str) -> Result<(), String> {
+    Err("Biometric unlock is only available on macOS".to_string())
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn unlock(_service: &str) -> Result<String, String> {
+    Err("Biometric unlock is only available on macOS".to_string())
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn forget(_service: &str) -> Result<(), String> {
+    // Nothing can have been stored
+    Ok(())
+}
@@ -1,10 +1,15 @@
+use crate::biometric;
 use crate::crypto;
 use crate::env;
-use crate::pty::{CreatePtyOptions, PtyManager};
+use crate::platform;
+use crate::pty::{
+    self, CreatePtyOptions, CreateTmuxOptions, CreateWslOptions, DelayedWrite, PtyBroadcastGroup, PtyInfo,
+    PtyInstanceLimit, PtyManager,
+};
 use crate::workspace;
 use std::collections::HashMap;
 use std::sync::Arc;
-use tauri::State;
+use tauri::{Manager, State};
 
 /// Create a new PTY instance
 #[tauri::command]
@@ -15,6 +20,238 @@ pub async fn pty_create(
     pty_manager.create(options)
 }
 
+/// Create a PTY that is killed if the shell prints nothing within `timeout_ms`
+#[tauri::command]
+pub async fn pty_create_with_startup_timeout(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    options: CreatePtyOptions,
+    timeout_ms: u64,
+) -> Result<bool, String> {
+    pty_manager.create(CreatePtyOptions {
+        startup_timeout_ms: Some(timeout_ms),
+        ..options
+    })
+}
+
+/// Create a read-only PTY for monitoring (all writes are rejected)
+#[tauri::command]
+pub async fn pty_create_monitoring(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    options: CreatePtyOptions,
+) -> Result<bool, String> {
+    pty_manager.create(CreatePtyOptions {
+        read_only: true,
+        ..options
+    })
+}
+
+/// Start a new PTY with the same shell, environment and directory as an existing one
+#[tauri::command]
+pub async fn pty_fork(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    source_id: String,
+    new_id: String,
+) -> Result<bool, String> {
+    pty_manager.fork(source_id, new_id)
+}
+
+/// Toggle read-only mode for a PTY instance
+#[tauri::command]
+pub async fn pty_set_read_only(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+    read_only: bool,
+) -> Result<(), String> {
+    pty_manager.set_read_only(id, read_only)
+}
+
+/// Get a PTY's buffered raw output (sent as binary) to repaint after a frontend reload
+#[tauri::command]
+pub async fn pty_get_scrollback(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+    max_bytes: Option<usize>,
+) -> Result<tauri::ipc::Response, String> {
+    pty_manager
+        .get_scrollback(id, max_bytes)
+        .map(tauri::ipc::Response::new)
+}
+
+/// Get the Nth most recent scrollback line of a PTY (0 is the latest)
+#[tauri::command]
+pub async fn pty_get_history_line(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+    index: usize,
+) -> Result<Option<String>, String> {
+    pty_manager.get_history_line(id, index)
+}
+
+/// Get scrollback lines `start..end` of a PTY, most recent first
+#[tauri::command]
+pub async fn pty_get_history_range(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+    start: usize,
+    end: usize,
+) -> Result<Vec<String>, String> {
+    pty_manager.get_history_range(id, start, end)
+}
+
+/// Send SIGINT/SIGTERM/SIGHUP/SIGKILL to a PTY's foreground process
+#[tauri::command]
+pub async fn pty_signal(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+    signal: String,
+) -> Result<(), String> {
+    pty_manager.signal(id, signal)
+}
+
+/// Wait for a PTY's process to exit and return its exit code
+#[tauri::command]
+pub async fn pty_wait(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+) -> Result<Option<u32>, String> {
+    pty_manager.wait(id).await
+}
+
+/// List live terminals with their cwd, pid and title
+#[tauri::command]
+pub async fn pty_list(pty_manager: State<'_, Arc<PtyManager>>) -> Result<Vec<PtyInfo>, String> {
+    Ok(pty_manager.list())
+}
+
+/// Get the title a PTY last set via OSC 0/2
+#[tauri::command]
+pub async fn pty_get_title(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+) -> Result<Option<String>, String> {
+    pty_manager.get_title(id)
+}
+
+/// Get the OS process id of a PTY's shell
+#[tauri::command]
+pub async fn pty_get_pid(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+) -> Result<Option<u32>, String> {
+    Ok(pty_manager.get_pid(id))
+}
+
+/// Get the last command submitted to a PTY
+#[tauri::command]
+pub async fn pty_get_last_command(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+) -> Result<Option<String>, String> {
+    pty_manager.get_last_command(id)
+}
+
+/// Get the number of running PTYs and the instance limit
+#[tauri::command]
+pub async fn pty_get_instance_limit(
+    pty_manager: State<'_, Arc<PtyManager>>,
+) -> Result<PtyInstanceLimit, String> {
+    Ok(pty_manager.instance_limit())
+}
+
+/// Set the maximum number of concurrent PTYs
+#[tauri::command]
+pub async fn pty_set_max_instances(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    max_instances: usize,
+) -> Result<(), String> {
+    pty_manager.set_max_instances(max_instances)
+}
+
+/// Start appending a PTY's output to a log file, optionally as plain text
+#[tauri::command]
+pub async fn pty_start_logging(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+    path: String,
+    strip_ansi: bool,
+) -> Result<(), String> {
+    pty_manager.start_logging(id, path, strip_ansi)
+}
+
+/// Stop logging a PTY's output; false if no log was active
+#[tauri::command]
+pub async fn pty_stop_logging(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+) -> Result<bool, String> {
+    pty_manager.stop_logging(id)
+}
+
+/// Replay a PTY's scrollback after a frontend reload; false if it's gone
+#[tauri::command]
+pub async fn pty_reconnect(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+) -> Result<bool, String> {
+    pty_manager.reconnect(id)
+}
+
+/// Discard a PTY's in-memory scrollback without killing the session
+#[tauri::command]
+pub async fn pty_clear_scrollback(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+) -> Result<(), String> {
+    pty_manager.clear_scrollback(id)
+}
+
+/// Create a PTY with the directory's .env/.env.{profile}/.envrc merged into its environment
+#[tauri::command]
+pub async fn pty_create_with_env_file(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+    cwd: String,
+    env_profile: Option<String>,
+    expansion: Option<env::VarExpansion>,
+) -> Result<bool, String> {
+    let custom_env =
+        env::resolve_environment(&cwd, env_profile.as_deref(), expansion.unwrap_or_default())?;
+    pty_manager.create(CreatePtyOptions {
+        id,
+        cwd,
+        custom_env: Some(custom_env),
+        ..Default::default()
+    })
+}
+
+/// Create a PTY attached to a (possibly new) tmux session
+#[tauri::command]
+pub async fn pty_create_tmux(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    options: CreateTmuxOptions,
+) -> Result<bool, String> {
+    pty_manager.create_tmux(options)
+}
+
+/// Create a PTY running a shell in a WSL distribution (Windows only)
+#[tauri::command]
+pub async fn pty_create_wsl(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    options: CreateWslOptions,
+) -> Result<bool, String> {
+    pty_manager.create_wsl(options)
+}
+
+/// Stream a file's appended content into a terminal panel (like `tail -f`)
+#[tauri::command]
+pub async fn pty_tail_file(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+    file_path: String,
+) -> Result<(), String> {
+    pty_manager.tail_file(id, file_path)
+}
+
 /// Write data to a PTY instance
 #[tauri::command]
 pub async fn pty_write(
@@ -25,6 +262,77 @@ pub async fn pty_write(
     pty_manager.write(id, data)
 }
 
+/// Put a PTY in a broadcast group (None leaves it); writes go to every member
+#[tauri::command]
+pub async fn pty_set_broadcast_group(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+    group: Option<String>,
+) -> Result<(), String> {
+    pty_manager.set_broadcast_group(id, group)
+}
+
+/// List broadcast groups and their members
+#[tauri::command]
+pub async fn pty_list_broadcast_groups(
+    pty_manager: State<'_, Arc<PtyManager>>,
+) -> Result<Vec<PtyBroadcastGroup>, String> {
+    Ok(pty_manager.list_broadcast_groups())
+}
+
+/// Write raw bytes to a PTY
+#[tauri::command]
+pub async fn pty_write_bytes(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+    data: Vec<u8>,
+) -> Result<(), String> {
+    pty_manager.write_bytes(id, data)
+}
+
+/// Paste text into a PTY, bracketed unless `bracketed` is false
+#[tauri::command]
+pub async fn pty_paste(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+    data: String,
+    bracketed: Option<bool>,
+) -> Result<(), String> {
+    pty_manager.paste(id, data, bracketed.unwrap_or(true))
+}
+
+/// Write raw bytes to a PTY in fixed-size chunks
+#[tauri::command]
+pub async fn pty_write_chunked(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+    data: Vec<u8>,
+    chunk_size: u32,
+) -> Result<(), String> {
+    pty_manager.write_chunked(id, data, chunk_size as usize)
+}
+
+/// Write scripted input to a PTY with a delay before each chunk
+#[tauri::command]
+pub async fn pty_write_with_delay(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+    chars: Vec<DelayedWrite>,
+    cancel_token: String,
+) -> Result<(), String> {
+    let chars = chars.into_iter().map(|c| (c.delay_ms, c.text)).collect();
+    pty_manager.write_with_delay(id, chars, cancel_token)
+}
+
+/// Cancel a running delayed write sequence
+#[tauri::command]
+pub async fn pty_cancel_write_delay(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    cancel_token: String,
+) -> Result<bool, String> {
+    Ok(pty_manager.cancel_delayed_write(cancel_token))
+}
+
 /// Resize a PTY instance
 #[tauri::command]
 pub async fn pty_resize(
@@ -36,6 +344,28 @@ pub async fn pty_resize(
     pty_manager.resize(id, cols, rows)
 }
 
+/// Resize every PTY instance; returns how many were resized
+#[tauri::command]
+pub async fn pty_resize_all(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    cols: u16,
+    rows: u16,
+) -> Result<usize, String> {
+    pty_manager.resize_all(cols, rows)
+}
+
+/// Resize every PTY instance, including pixel dimensions
+#[tauri::command]
+pub async fn pty_resize_all_with_pixel(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    cols: u16,
+    rows: u16,
+    pixel_width: u16,
+    pixel_height: u16,
+) -> Result<usize, String> {
+    pty_manager.resize_all_with_pixel(cols, rows, pixel_width, pixel_height)
+}
+
 /// Kill a PTY instance
 #[tauri::command]
 pub async fn pty_kill(pty_manager: State<'_, Arc<PtyManager>>, id: String) -> Result<bool, String> {
@@ -61,6 +391,21 @@ pub async fn pty_get_cwd(
     pty_manager.get_cwd(id)
 }
 
+/// Get the version string of the shell running in a PTY
+#[tauri::command]
+pub async fn pty_get_shell_version(
+    pty_manager: State<'_, Arc<PtyManager>>,
+    id: String,
+) -> Result<String, String> {
+    pty_manager.get_shell_version(id)
+}
+
+/// Get the version string of a shell binary (before creating a PTY)
+#[tauri::command]
+pub async fn get_shell_version(shell_path: String) -> Result<String, String> {
+    pty::get_shell_version(&shell_path)
+}
+
 /// Open a folder selection dialog
 #[tauri::command]
 pub async fn dialog_select_folder(app_handle: tauri::AppHandle) -> Result<Option<String>, String> {
@@ -74,16 +419,309 @@ pub async fn dialog_select_folder(app_handle: tauri::AppHandle) -> Result<Option
     Ok(result.map(|path| path.to_string()))
 }
 
-/// Save workspace data
+/// Save workspace data to a profile (the default profile when omitted)
 #[tauri::command]
-pub async fn workspace_save(app_handle: tauri::AppHandle, data: String) -> Result<bool, String> {
-    workspace::save_workspace(&app_handle, data)
+pub async fn workspace_save(
+    app_handle: tauri::AppHandle,
+    data: String,
+    profile: Option<String>,
+) -> Result<bool, String> {
+    match profile {
+        Some(profile) => workspace::save_profile(&app_handle, &profile, data, None),
+        None => workspace::save_workspace(&app_handle, data),
+    }
 }
 
-/// Load workspace data
+/// Save workspace data with a description stored alongside it
 #[tauri::command]
-pub async fn workspace_load(app_handle: tauri::AppHandle) -> Result<Option<String>, String> {
-    workspace::load_workspace(&app_handle)
+pub async fn workspace_save_with_comment(
+    app_handle: tauri::AppHandle,
+    data: String,
+    comment: String,
+) -> Result<bool, String> {
+    workspace::save_workspace_with_comment(&app_handle, data, Some(comment))
+}
+
+/// Replace the comment of a saved workspace (empty clears it)
+#[tauri::command]
+pub async fn workspace_update_comment(
+    app_handle: tauri::AppHandle,
+    name: String,
+    comment: String,
+) -> Result<(), String> {
+    let comment = if comment.is_empty() { None } else { Some(comment) };
+    workspace::update_comment(&app_handle, &name, comment)
+}
+
+/// Load workspace data from a profile (the default profile when omitted)
+#[tauri::command]
+pub async fn workspace_load(
+    app_handle: tauri::AppHandle,
+    profile: Option<String>,
+) -> Result<Option<String>, String> {
+    match profile {
+        Some(profile) => workspace::load_profile(&app_handle, &profile),
+        None => workspace::load_workspace(&app_handle),
+    }
+}
+
+/// Load workspace data along with the schema version it was stored in
+#[tauri::command]
+pub async fn workspace_load_versioned(
+    app_handle: tauri::AppHandle,
+    profile: Option<String>,
+) -> Result<Option<workspace::VersionedWorkspace>, String> {
+    let profile = profile.unwrap_or_else(|| workspace::DEFAULT_WORKSPACE_NAME.to_string());
+    workspace::load_profile_versioned(&app_handle, &profile)
+}
+
+/// Export the workspace to a file, optionally encrypted with `export_password`
+#[tauri::command]
+pub async fn workspace_export(
+    app_handle: tauri::AppHandle,
+    path: String,
+    current_password: Option<String>,
+    export_password: Option<String>,
+) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        workspace::export_workspace(
+            &app_handle,
+            &path,
+            current_password.as_deref(),
+            export_password.as_deref(),
+        )
+    })
+    .await
+    .map_err(|e| format!("Failed to export workspace: {}", e))?
+}
+
+/// Import a workspace file, replacing the current workspace
+#[tauri::command]
+pub async fn workspace_import(
+    app_handle: tauri::AppHandle,
+    path: String,
+    password: Option<String>,
+) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        workspace::import_workspace(&app_handle, &path, password.as_deref())
+    })
+    .await
+    .map_err(|e| format!("Failed to import workspace: {}", e))?
+}
+
+/// List saved workspace profiles
+#[tauri::command]
+pub async fn workspace_list_profiles(app_handle: tauri::AppHandle) -> Result<Vec<String>, String> {
+    workspace::list_profiles(&app_handle)
+}
+
+/// Save workspace data encrypted with a password
+#[tauri::command]
+pub async fn workspace_save_encrypted(
+    app_handle: tauri::AppHandle,
+    data: String,
+    password: String,
+) -> Result<bool, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        workspace::save_workspace_encrypted(&app_handle, data, &password)
+    })
+    .await
+    .map_err(|e| format!("Failed to save encrypted workspace: {}", e))?
+}
+
+/// Load workspace data, decrypting it with the password if it was saved encrypted
+#[tauri::command]
+pub async fn workspace_load_encrypted(
+    app_handle: tauri::AppHandle,
+    password: String,
+) -> Result<Option<String>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        workspace::load_workspace_encrypted(&app_handle, &password)
+    })
+    .await
+    .map_err(|e| format!("Failed to load encrypted workspace: {}", e))?
+}
+
+/// Save workspace data uncompressed for debugging (`enabled: false` switches back to gzip)
+#[tauri::command]
+pub async fn workspace_save_plaintext(
+    app_handle: tauri::AppHandle,
+    data: String,
+    enabled: bool,
+) -> Result<bool, String> {
+    if enabled {
+        workspace::save_workspace_plaintext(&app_handle, data)
+    } else {
+        let saved = workspace::save_workspace(&app_handle, data)?;
+        workspace::remove_plaintext_workspace(&app_handle)?;
+        Ok(saved)
+    }
+}
+
+/// Load a history snapshot's JSON for preview without restoring it (default profile when omitted)
+#[tauri::command]
+pub async fn workspace_get_history_snapshot(
+    app_handle: tauri::AppHandle,
+    filename: String,
+    profile: Option<String>,
+) -> Result<String, String> {
+    let profile = profile.unwrap_or_else(|| workspace::DEFAULT_WORKSPACE_NAME.to_string());
+    workspace::get_history_snapshot(&app_handle, &profile, filename)
+}
+
+/// Re-encrypt all encrypted values in the workspace with a new password
+#[tauri::command]
+pub async fn workspace_rotate_encryption_key(
+    app_handle: tauri::AppHandle,
+    old_password: String,
+    new_password: String,
+) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        workspace::rotate_encryption_key(&app_handle, &old_password, &new_password)
+    })
+    .await
+    .map_err(|e| format!("Failed to rotate encryption key: {}", e))?
+}
+
+/// Restore a history snapshot as the profile's current workspace (default profile when omitted)
+#[tauri::command]
+pub async fn workspace_restore_history(
+    app_handle: tauri::AppHandle,
+    filename: String,
+    profile: Option<String>,
+) -> Result<(), String> {
+    let profile = profile.unwrap_or_else(|| workspace::DEFAULT_WORKSPACE_NAME.to_string());
+    workspace::restore_history(&app_handle, &profile, filename)
+}
+
+/// Get the workspace save settings (history retention, compression level)
+#[tauri::command]
+pub async fn workspace_get_settings(app_handle: tauri::AppHandle) -> Result<workspace::WorkspaceSettings, String> {
+    Ok(workspace::load_settings(&app_handle))
+}
+
+/// Update the workspace save settings
+#[tauri::command]
+pub async fn workspace_set_settings(
+    app_handle: tauri::AppHandle,
+    settings: workspace::WorkspaceSettings,
+) -> Result<(), String> {
+    workspace::save_settings(&app_handle, &settings)
+}
+
+/// List a profile's history snapshots, newest first (default profile when omitted)
+#[tauri::command]
+pub async fn workspace_list_history(
+    app_handle: tauri::AppHandle,
+    profile: Option<String>,
+) -> Result<Vec<workspace::HistoryEntry>, String> {
+    let profile = profile.unwrap_or_else(|| workspace::DEFAULT_WORKSPACE_NAME.to_string());
+    workspace::list_history(&app_handle, &profile)
+}
+
+/// Recompress the workspace file at maximum compression
+#[tauri::command]
+pub async fn workspace_optimize(app_handle: tauri::AppHandle) -> Result<workspace::OptimizeResult, String> {
+    workspace::optimize_workspace(&app_handle)
+}
+
+/// Copy a workspace (default if `src_name` is omitted) under a new name
+#[tauri::command]
+pub async fn workspace_clone(
+    app_handle: tauri::AppHandle,
+    src_name: Option<String>,
+    dest_name: String,
+) -> Result<(), String> {
+    workspace::clone_workspace(&app_handle, src_name, dest_name)
+}
+
+/// List the most recently modified workspaces, newest first
+#[tauri::command]
+pub async fn workspace_list_recent(
+    app_handle: tauri::AppHandle,
+    limit: u32,
+) -> Result<Vec<workspace::WorkspaceEntry>, String> {
+    workspace::list_recent(&app_handle, limit as usize)
+}
+
+/// Mark a workspace as recently used without changing its content
+#[tauri::command]
+pub async fn workspace_touch(app_handle: tauri::AppHandle, name: String) -> Result<(), String> {
+    workspace::touch_workspace(&app_handle, &name)
+}
+
+/// Map every terminal saved in the workspace to its live PTY cwd (None if not running)
+#[tauri::command]
+pub async fn workspace_get_pty_cwd_map(
+    app_handle: tauri::AppHandle,
+    pty_manager: State<'_, Arc<PtyManager>>,
+) -> Result<HashMap<String, Option<String>>, String> {
+    let data = match workspace::load_workspace(&app_handle)? {
+        Some(data) => data,
+        None => return Ok(HashMap::new()),
+    };
+
+    let mut map = HashMap::new();
+    for id in workspace::terminal_ids(&data)? {
+        let cwd = pty_manager.get_cwd(id.clone())?;
+        map.insert(id, cwd);
+    }
+
+    Ok(map)
+}
+
+/// Remove duplicate terminal entries from a workspace JSON
+#[tauri::command]
+pub async fn workspace_deduplicate_tabs(json: String) -> Result<String, String> {
+    workspace::deduplicate_tabs(&json)
+}
+
+/// Count duplicate terminal entries in a workspace JSON
+#[tauri::command]
+pub async fn workspace_count_duplicate_tabs(json: String) -> Result<usize, String> {
+    workspace::count_duplicate_tabs(&json)
+}
+
+/// Minify workspace JSON without saving it
+#[tauri::command]
+pub async fn workspace_compact_json(json: String) -> Result<String, String> {
+    workspace::compact_json(&json)
+}
+
+/// Pretty-print workspace JSON without saving it
+#[tauri::command]
+pub async fn workspace_prettify_json(json: String) -> Result<String, String> {
+    workspace::prettify_json(&json)
+}
+
+/// Export the terminal list of a workspace JSON as CSV
+#[tauri::command]
+pub async fn workspace_export_csv(data: String) -> Result<String, String> {
+    workspace::export_sessions_csv(&data)
+}
+
+/// Export the terminal list of a workspace JSON as TSV
+#[tauri::command]
+pub async fn workspace_export_tsv(data: String) -> Result<String, String> {
+    workspace::export_sessions_tsv(&data)
+}
+
+/// Start periodically copying the workspace file to an external directory
+#[tauri::command]
+pub async fn workspace_start_auto_backup(
+    app_handle: tauri::AppHandle,
+    dest_dir: String,
+    interval_secs: u64,
+) -> Result<(), String> {
+    workspace::start_backup_task(&app_handle, dest_dir, interval_secs)
+}
+
+/// Stop the auto-backup task; returns false if none was running
+#[tauri::command]
+pub async fn workspace_stop_auto_backup(
+    auto_backup: State<'_, workspace::AutoBackup>,
+) -> Result<bool, String> {
+    Ok(auto_backup.stop())
 }
 
 /// Get the config file path
@@ -92,6 +730,26 @@ pub async fn get_config_path(app_handle: tauri::AppHandle) -> Result<String, Str
     workspace::get_config_path(&app_handle).map(|p| p.to_string_lossy().to_string())
 }
 
+/// Get available and total disk space for the filesystem containing a path
+#[tauri::command]
+pub async fn get_disk_space(path: String) -> Result<platform::DiskSpace, String> {
+    platform::get_disk_space_bytes(&path)
+}
+
+/// Check if the app is running inside WSL
+#[tauri::command]
+pub async fn is_wsl() -> Result<bool, String> {
+    Ok(platform::is_wsl())
+}
+
+/// List installed Homebrew formulas and casks (macOS only)
+#[tauri::command]
+pub async fn get_homebrew_packages() -> Result<platform::HomebrewPackages, String> {
+    tauri::async_runtime::spawn_blocking(platform::get_homebrew_packages)
+        .await
+        .map_err(|e| format!("Homebrew task failed: {}", e))?
+}
+
 // ============================================================================
 // Encryption Commands
 // ============================================================================
@@ -102,8 +760,16 @@ pub async fn crypto_encrypt(
     plaintext: String,
     password: String,
     hint: Option<String>,
+    kdf: Option<crypto::KdfParams>,
+    algorithm: Option<crypto::CipherAlgorithm>,
 ) -> Result<String, String> {
-    let envelope = crypto::encrypt(&plaintext, &password, hint)?;
+    let envelope = crypto::encrypt_with_params(
+        &plaintext,
+        &password,
+        hint,
+        kdf.unwrap_or_default(),
+        algorithm.unwrap_or_default(),
+    )?;
     crypto::envelope_to_string(&envelope)
 }
 
@@ -114,6 +780,48 @@ pub async fn crypto_decrypt(encrypted_data: String, password: String) -> Result<
     crypto::decrypt(&envelope, &password)
 }
 
+/// Change the password of encrypted data without writing plaintext anywhere
+#[tauri::command]
+pub async fn crypto_reencrypt(
+    encrypted_data: String,
+    old_password: String,
+    new_password: String,
+    new_hint: Option<String>,
+) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let envelope = crypto::string_to_envelope(&encrypted_data)?;
+        let rotated = crypto::reencrypt(&envelope, &old_password, &new_password, new_hint)?;
+        crypto::envelope_to_string(&rotated)
+    })
+    .await
+    .map_err(|e| format!("Failed to re-encrypt: {}", e))?
+}
+
+/// Encrypt a file to `out_path`, streaming it in chunks
+#[tauri::command]
+pub async fn crypto_encrypt_file(
+    in_path: String,
+    out_path: String,
+    password: String,
+    hint: Option<String>,
+) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        crypto::encrypt_file(std::path::Path::new(&in_path), std::path::Path::new(&out_path), &password, hint)
+    })
+    .await
+    .map_err(|e| format!("Failed to encrypt file: {}", e))?
+}
+
+/// Decrypt a file written by `crypto_encrypt_file` to `out_path`
+#[tauri::command]
+pub async fn crypto_decrypt_file(in_path: String, out_path: String, password: String) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        crypto::decrypt_file(std::path::Path::new(&in_path), std::path::Path::new(&out_path), &password)
+    })
+    .await
+    .map_err(|e| format!("Failed to decrypt file: {}", e))?
+}
+
 /// Get password hint from encrypted data
 #[tauri::command]
 pub async fn crypto_get_hint(encrypted_data: String) -> Result<Option<String>, String> {
@@ -121,20 +829,110 @@ pub async fn crypto_get_hint(encrypted_data: String) -> Result<Option<String>, S
     Ok(envelope.hint)
 }
 
+/// Derive a deterministic key for a file path (Base64, 32 bytes)
+#[tauri::command]
+pub async fn crypto_derive_file_key(password: String, file_path: String) -> Result<String, String> {
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+    let key = crypto::derive_file_key(&password, &file_path)?;
+    Ok(BASE64.encode(&key[..]))
+}
+
+/// Get how many bytes encryption adds to a plaintext of the given length
+#[tauri::command]
+pub async fn crypto_envelope_overhead(plaintext_len: u32) -> Result<u32, String> {
+    Ok(crypto::envelope_overhead_bytes(plaintext_len as usize) as u32)
+}
+
+/// Benchmark Argon2 and return parameters taking about `target_ms` to derive a key
+#[tauri::command]
+pub async fn crypto_calibrate_kdf(target_ms: u64) -> Result<crypto::KdfParams, String> {
+    if target_ms == 0 {
+        return Err("Target time must be greater than 0".to_string());
+    }
+    tauri::async_runtime::spawn_blocking(move || {
+        crypto::calibrate_kdf(std::time::Duration::from_millis(target_ms))
+    })
+    .await
+    .map_err(|e| format!("Failed to calibrate KDF: {}", e))?
+}
+
+/// Remember the workspace password for Touch ID unlock (macOS only)
+#[tauri::command]
+pub async fn crypto_store_biometric(app_handle: tauri::AppHandle, password: String) -> Result<(), String> {
+    let service = app_handle.config().identifier.clone();
+    tauri::async_runtime::spawn_blocking(move || biometric::store(&service, &password))
+        .await
+        .map_err(|e| format!("Biometric task failed: {}", e))?
+}
+
+/// Get the remembered workspace password after Touch ID authentication
+#[tauri::command]
+pub async fn crypto_unlock_biometric(app_handle: tauri::AppHandle) -> Result<String, String> {
+    let service = app_handle.config().identifier.clone();
+    tauri::async_runtime::spawn_blocking(move || biometric::unlock(&service))
+        .await
+        .map_err(|e| format!("Biometric task failed: {}", e))?
+}
+
+/// Forget the password remembered for Touch ID unlock
+#[tauri::command]
+pub async fn crypto_forget_biometric(app_handle: tauri::AppHandle) -> Result<(), String> {
+    let service = app_handle.config().identifier.clone();
+    tauri::async_runtime::spawn_blocking(move || biometric::forget(&service))
+        .await
+        .map_err(|e| format!("Biometric task failed: {}", e))?
+}
+
+/// Estimate password strength (advisory only, never blocks encryption)
+#[tauri::command]
+pub async fn crypto_estimate_strength(password: String) -> Result<crypto::PasswordStrength, String> {
+    Ok(crypto::estimate_strength(&password))
+}
+
+/// Generate a memorable random passphrase (default separator: '-')
+#[tauri::command]
+pub async fn crypto_generate_passphrase(
+    word_count: u8,
+    separator: Option<char>,
+) -> Result<crypto::Passphrase, String> {
+    if !(3..=20).contains(&word_count) {
+        return Err("Word count must be between 3 and 20".to_string());
+    }
+    Ok(crypto::generate_passphrase(word_count, separator.unwrap_or('-')))
+}
+
 // ============================================================================
 // Environment Variable Commands
 // ============================================================================
 
-/// Read .env file from a directory
+/// Read .env file from a directory; Strict expansion fails on undefined references
 #[tauri::command]
-pub async fn env_read_dotenv(dir_path: String) -> Result<HashMap<String, String>, String> {
-    let result = env::read_env_file(&dir_path);
+pub async fn env_read_dotenv(
+    dir_path: String,
+    expansion: Option<env::VarExpansion>,
+) -> Result<HashMap<String, String>, String> {
+    let expansion = expansion.unwrap_or_default();
+    let result = env::read_env_file_with(&dir_path, expansion);
     if !result.errors.is_empty() {
+        if expansion == env::VarExpansion::Strict {
+            return Err(result.errors.join("; "));
+        }
         eprintln!("Errors reading .env: {:?}", result.errors);
     }
     Ok(result.env_vars)
 }
 
+/// Read .env, .env.local, .env.{mode} and .env.{mode}.local merged in dotenv precedence
+#[tauri::command]
+pub async fn env_read_layered(
+    dir_path: String,
+    mode: Option<String>,
+    expansion: Option<env::VarExpansion>,
+) -> Result<env::EnvParseResult, String> {
+    Ok(env::read_env_layered(&dir_path, mode, expansion.unwrap_or_default()))
+}
+
 /// Read .envrc file from a directory (direnv format)
 #[tauri::command]
 pub async fn env_read_envrc(dir_path: String) -> Result<HashMap<String, String>, String> {
@@ -145,6 +943,144 @@ pub async fn env_read_envrc(dir_path: String) -> Result<HashMap<String, String>,
     Ok(result.env_vars)
 }
 
+/// Read an .ini file from a directory, optionally limited to one section
+#[tauri::command]
+pub async fn env_read_ini(
+    dir_path: String,
+    filename: String,
+    section: Option<String>,
+) -> Result<env::EnvParseResult, String> {
+    Ok(env::read_ini_file(&dir_path, &filename, section.as_deref()))
+}
+
+/// Read custom fields from a Bitwarden item as environment variables
+#[tauri::command]
+pub async fn env_read_bitwarden(item_id: String) -> Result<env::EnvParseResult, String> {
+    tauri::async_runtime::spawn_blocking(move || env::read_bitwarden_item(&item_id))
+        .await
+        .map_err(|e| format!("Bitwarden task failed: {}", e))
+}
+
+/// Read secrets for a chamber service (AWS SSM Parameter Store)
+#[tauri::command]
+pub async fn env_read_chamber(
+    service: String,
+    env_name: String,
+) -> Result<env::EnvParseResult, String> {
+    tauri::async_runtime::spawn_blocking(move || env::read_chamber_service(&service, &env_name))
+        .await
+        .map_err(|e| format!("chamber task failed: {}", e))
+}
+
+/// Check if the chamber CLI is installed
+#[tauri::command]
+pub async fn env_has_chamber_cli() -> Result<bool, String> {
+    Ok(env::has_chamber_cli())
+}
+
+/// Read parameters under a path from AWS SSM Parameter Store
+#[tauri::command]
+pub async fn env_read_ssm_path(path_prefix: String) -> Result<env::EnvParseResult, String> {
+    tauri::async_runtime::spawn_blocking(move || env::read_ssm_path(&path_prefix))
+        .await
+        .map_err(|e| format!("SSM task failed: {}", e))
+}
+
+/// Read secrets from Azure Key Vault via the az CLI
+#[tauri::command]
+pub async fn env_read_azure_keyvault(
+    vault_url: String,
+    secret_names: Vec<String>,
+) -> Result<env::EnvParseResult, String> {
+    tauri::async_runtime::spawn_blocking(move || env::read_azure_keyvault(&vault_url, &secret_names))
+        .await
+        .map_err(|e| format!("Azure Key Vault task failed: {}", e))
+}
+
+/// Read secrets from the pass password manager as `[env_key, entry_path]` pairs
+#[tauri::command]
+pub async fn env_read_pass(entries: Vec<[String; 2]>) -> Result<env::EnvParseResult, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let entries: Vec<(&str, &str)> = entries
+            .iter()
+            .map(|[key, path]| (key.as_str(), path.as_str()))
+            .collect();
+        env::read_pass_env(&entries)
+    })
+    .await
+    .map_err(|e| format!("pass task failed: {}", e))
+}
+
+/// Read secrets for a Doppler project/config
+#[tauri::command]
+pub async fn env_read_doppler(project: String, config: String) -> Result<env::EnvParseResult, String> {
+    tauri::async_runtime::spawn_blocking(move || env::read_doppler_secrets(&project, &config))
+        .await
+        .map_err(|e| format!("Doppler task failed: {}", e))
+}
+
+/// Read site environment variables from Netlify
+#[tauri::command]
+pub async fn env_read_netlify(site_id: Option<String>) -> Result<env::EnvParseResult, String> {
+    tauri::async_runtime::spawn_blocking(move || env::read_netlify_env(site_id.as_deref()))
+        .await
+        .map_err(|e| format!("Netlify task failed: {}", e))
+}
+
+/// Read a Kubernetes Secret manifest as environment variables
+#[tauri::command]
+pub async fn env_read_k8s_secret(file_path: String) -> Result<env::EnvParseResult, String> {
+    Ok(env::read_k8s_secret(&file_path))
+}
+
+/// Read a KV v2 secret from HashiCorp Vault
+#[tauri::command]
+pub async fn env_read_vault_kv(mount: String, path: String) -> Result<env::EnvParseResult, String> {
+    // reqwest's blocking client must not run on the async runtime thread
+    tauri::async_runtime::spawn_blocking(move || env::read_vault_kv(&mount, &path))
+        .await
+        .map_err(|e| format!("Vault task failed: {}", e))
+}
+
+/// Read .env from a directory with `~`, variable references and relative paths expanded
+#[tauri::command]
+pub async fn env_expand_paths(
+    dir_path: String,
+    keys_to_expand: Option<Vec<String>>,
+) -> Result<HashMap<String, String>, String> {
+    let mut vars = env::read_env_file(&dir_path).env_vars;
+    env::expand_paths(
+        &mut vars,
+        keys_to_expand.as_deref(),
+        Some(std::path::Path::new(&dir_path)),
+    );
+    Ok(vars)
+}
+
+/// Write environment variables to a directory's .envrc
+#[tauri::command]
+pub async fn env_write_envrc(
+    dir_path: String,
+    vars: HashMap<String, String>,
+) -> Result<(), String> {
+    env::write_envrc_file(&dir_path, &vars)
+}
+
+/// Merge environment maps in order with a conflict strategy (default: last wins)
+#[tauri::command]
+pub async fn env_merge(
+    sources: Vec<HashMap<String, String>>,
+    strategy: Option<env::MergeStrategy>,
+) -> Result<HashMap<String, String>, String> {
+    env::merge(&sources, strategy.unwrap_or_default())
+}
+
+/// Read Procfile process definitions from a directory
+#[tauri::command]
+pub async fn env_read_procfile(dir_path: String) -> Result<HashMap<String, String>, String> {
+    env::parse_procfile(&dir_path)
+}
+
 /// Check if .env file exists in directory
 #[tauri::command]
 pub async fn env_has_dotenv(dir_path: String) -> Result<bool, String> {
@@ -1,19 +1,23 @@
 //! Workspace encryption module
 //!
-//! Provides AES-256-GCM encryption with Argon2id key derivation.
-//! On macOS, supports Touch ID authentication via Keychain.
+//! Provides AES-256-GCM (or ChaCha20-Poly1305) encryption with Argon2id key derivation.
+//! On macOS, the password can be kept in the Keychain for Touch ID unlock (see `biometric`).
 
 use aes_gcm::{
-    aead::{Aead, KeyInit},
+    aead::{Aead, KeyInit, Payload},
     Aes256Gcm, Nonce,
 };
-use argon2::{
-    password_hash::{rand_core::OsRng, SaltString},
-    Argon2, PasswordHasher,
-};
+use argon2::{password_hash::rand_core::OsRng, Algorithm, Argon2, Params, Version};
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::ChaCha20Poly1305;
 use rand::RngCore;
+use crate::platform;
 use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
 
 /// Encrypted data envelope containing all info needed for decryption
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,93 +30,599 @@ pub struct EncryptedEnvelope {
     pub salt: String,
     /// Optional password hint
     pub hint: Option<String>,
-    /// Version for future compatibility
+    /// Version for future compatibility (3+ authenticates the metadata fields)
     pub version: u8,
+    /// AEAD cipher (AES-256-GCM for envelopes that predate the field)
+    #[serde(default)]
+    pub algorithm: CipherAlgorithm,
+    /// Argon2 memory cost in KiB (version 2+)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub m_cost: Option<u32>,
+    /// Argon2 iterations (version 2+)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub t_cost: Option<u32>,
+    /// Argon2 parallelism (version 2+)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p_cost: Option<u32>,
 }
 
-/// Derive a 256-bit key from password using Argon2id
-fn derive_key(password: &str, salt: &[u8]) -> Result<[u8; 32], String> {
-    let argon2 = Argon2::default();
+impl EncryptedEnvelope {
+    /// Argon2 parameters this envelope was encrypted with
+    pub fn kdf_params(&self) -> Result<KdfParams, String> {
+        match self.version {
+            // Version 1 predates stored parameters and always used the then-default cost
+            1 => Ok(KdfParams::LEGACY),
+            2 | 3 => match (self.m_cost, self.t_cost, self.p_cost) {
+                (Some(m_cost), Some(t_cost), Some(p_cost)) => Ok(KdfParams { m_cost, t_cost, p_cost }),
+                _ => Err("Envelope is missing KDF parameters".to_string()),
+            },
+            v => Err(format!("Unsupported envelope version {}", v)),
+        }
+    }
 
-    // Create salt string from bytes
-    let salt_string = SaltString::encode_b64(salt).map_err(|e| format!("Salt error: {}", e))?;
+    /// Canonical serialization of the non-secret fields, authenticated as AEAD associated data
+    /// so editing any of them fails decryption. Empty before version 3.
+    fn associated_data(&self) -> Result<Vec<u8>, String> {
+        if self.version < 3 {
+            return Ok(Vec::new());
+        }
+        let fields = (
+            self.version,
+            self.algorithm,
+            &self.salt,
+            &self.nonce,
+            &self.hint,
+            self.m_cost,
+            self.t_cost,
+            self.p_cost,
+        );
+        serde_json::to_vec(&fields).map_err(|e| format!("Serialize error: {}", e))
+    }
+}
 
-    // Hash password
-    let hash = argon2
-        .hash_password(password.as_bytes(), &salt_string)
-        .map_err(|e| format!("Hash error: {}", e))?;
+/// Envelope version written by `encrypt`
+const ENVELOPE_VERSION: u8 = 3;
 
-    // Extract 32 bytes from hash output
-    let hash_bytes = hash.hash.ok_or("No hash output")?;
-    let bytes = hash_bytes.as_bytes();
+/// AEAD cipher used for the ciphertext
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CipherAlgorithm {
+    #[default]
+    #[serde(rename = "aes-256-gcm")]
+    Aes256Gcm,
+    /// Faster than AES in software, for CPUs without AES instructions
+    #[serde(rename = "chacha20-poly1305")]
+    ChaCha20Poly1305,
+}
 
-    let mut key = [0u8; 32];
-    key.copy_from_slice(&bytes[..32.min(bytes.len())]);
+impl CipherAlgorithm {
+    fn encrypt(
+        self,
+        key: &[u8; 32],
+        nonce: &[u8; 12],
+        plaintext: &[u8],
+        aad: &[u8],
+    ) -> Result<Vec<u8>, String> {
+        let nonce = Nonce::from_slice(nonce);
+        let payload = Payload { msg: plaintext, aad };
+        let result = match self {
+            Self::Aes256Gcm => Aes256Gcm::new_from_slice(key)
+                .map_err(|e| format!("Cipher error: {}", e))?
+                .encrypt(nonce, payload),
+            Self::ChaCha20Poly1305 => ChaCha20Poly1305::new_from_slice(key)
+                .map_err(|e| format!("Cipher error: {}", e))?
+                .encrypt(nonce, payload),
+        };
+        result.map_err(|e| format!("Encryption error: {}", e))
+    }
 
-    // Pad if needed (shouldn't happen with Argon2)
-    if bytes.len() < 32 {
-        return Err("Hash output too short".to_string());
+    fn decrypt(
+        self,
+        key: &[u8; 32],
+        nonce: &[u8; 12],
+        ciphertext: &[u8],
+        aad: &[u8],
+    ) -> Result<Vec<u8>, String> {
+        let nonce = Nonce::from_slice(nonce);
+        let payload = Payload { msg: ciphertext, aad };
+        let result = match self {
+            Self::Aes256Gcm => Aes256Gcm::new_from_slice(key)
+                .map_err(|e| format!("Cipher error: {}", e))?
+                .decrypt(nonce, payload),
+            Self::ChaCha20Poly1305 => ChaCha20Poly1305::new_from_slice(key)
+                .map_err(|e| format!("Cipher error: {}", e))?
+                .decrypt(nonce, payload),
+        };
+        result.map_err(|_| DECRYPTION_FAILED_ERROR.to_string())
     }
+}
+
+/// `format` value identifying an encrypted file
+const FILE_ENVELOPE_FORMAT: &str = "moonterm-encrypted-file";
+
+/// Plaintext bytes per chunk of an encrypted file
+const FILE_CHUNK_SIZE: u32 = 64 * 1024;
+
+/// Largest chunk size accepted when decrypting, so a crafted header can't force a huge buffer
+const MAX_FILE_CHUNK_SIZE: u32 = 16 * 1024 * 1024;
+
+/// Longest header line accepted when decrypting
+const MAX_FILE_HEADER_LEN: u64 = 4096;
+
+/// Header of an encrypted file.
+///
+/// The file is this header as a single line of JSON, followed by the ciphertext in
+/// chunks of `chunk_size` plaintext bytes plus a 16-byte tag each. The final chunk is
+/// always shorter than `chunk_size` (possibly empty), so truncation is detected even
+/// at a chunk boundary. Chunk `i` is encrypted with the nonce
+/// `nonce_prefix (7 bytes) || i (u32 big-endian) || last-chunk flag (1 byte)`, and the
+/// header line (without its newline) is every chunk's associated data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FileEnvelopeHeader {
+    format: String,
+    version: u8,
+    algorithm: CipherAlgorithm,
+    /// Base64-encoded salt for Argon2
+    salt: String,
+    /// Base64-encoded 7-byte nonce prefix
+    nonce_prefix: String,
+    chunk_size: u32,
+    hint: Option<String>,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+/// Advisory password strength estimate, for rendering a meter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasswordStrength {
+    /// 0 (too guessable) to 4 (very unguessable)
+    pub score: u8,
+    /// Human-readable time to crack offline against a slow hash, e.g. "3 hours"
+    pub crack_time: String,
+}
+
+/// Argon2id cost parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// Memory cost in KiB
+    pub m_cost: u32,
+    /// Number of iterations
+    pub t_cost: u32,
+    /// Degree of parallelism
+    pub p_cost: u32,
+}
+
+impl KdfParams {
+    /// argon2 0.5 defaults, used for version-1 envelopes and file keys
+    pub const LEGACY: KdfParams = KdfParams { m_cost: 19 * 1024, t_cost: 2, p_cost: 1 };
+
+    /// Upper bounds, so a crafted envelope can't make decryption exhaust memory or hang
+    const MAX_M_COST: u32 = 1024 * 1024;
+    const MAX_T_COST: u32 = 64;
+    const MAX_P_COST: u32 = 16;
+
+    fn to_argon2(self) -> Result<Argon2<'static>, String> {
+        if self.m_cost > Self::MAX_M_COST || self.t_cost > Self::MAX_T_COST || self.p_cost > Self::MAX_P_COST {
+            return Err(format!(
+                "KDF parameters too large (max m_cost {}, t_cost {}, p_cost {})",
+                Self::MAX_M_COST,
+                Self::MAX_T_COST,
+                Self::MAX_P_COST
+            ));
+        }
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, None)
+            .map_err(|e| format!("Invalid KDF parameters: {}", e))?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self::LEGACY
+    }
+}
+
+/// Prefix of decrypt errors for structurally invalid envelopes, as opposed to a wrong password
+pub const CORRUPTED_ENVELOPE_ERROR: &str = "CorruptedEnvelope";
+
+/// Decrypt error for an AEAD tag mismatch: the password is wrong or the ciphertext was altered
+pub const DECRYPTION_FAILED_ERROR: &str = "Decryption failed - wrong password or corrupted data";
+
+fn corrupted(reason: impl std::fmt::Display) -> String {
+    format!("{}: {}", CORRUPTED_ENVELOPE_ERROR, reason)
+}
+
+/// Embedded wordlist for passphrase generation (one lowercase word per line)
+const WORDLIST: &str = include_str!("wordlist.txt");
+
+/// Generated passphrase with its estimated strength
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Passphrase {
+    pub passphrase: String,
+    /// Entropy in bits, assuming the attacker knows the wordlist and word count
+    pub entropy_bits: f64,
+}
+
+/// Most memory calibration will try, in KiB
+const CALIBRATION_MAX_M_COST: u32 = 256 * 1024;
+
+/// Total time calibration may spend benchmarking
+const CALIBRATION_BUDGET: Duration = Duration::from_secs(10);
+
+/// Find Argon2 parameters whose key derivation takes about `target` on this machine.
+/// Memory is doubled first, then iterations are added; `KdfParams::LEGACY` is the floor.
+pub fn calibrate_kdf(target: Duration) -> Result<KdfParams, String> {
+    let started = Instant::now();
+    let salt = [0u8; 16];
+    let mut params = KdfParams::LEGACY;
+    let mut best = (params, Duration::MAX);
+
+    loop {
+        let run_started = Instant::now();
+        derive_key("calibration", &salt, params)?;
+        let elapsed = run_started.elapsed();
+
+        let distance = if elapsed > target { elapsed - target } else { target - elapsed };
+        if distance < best.1 {
+            best = (params, distance);
+        }
+
+        // Cost only grows from here, so past the target every further step is further away
+        if elapsed >= target || started.elapsed() + elapsed * 2 > CALIBRATION_BUDGET {
+            break;
+        }
+        if params.m_cost < CALIBRATION_MAX_M_COST {
+            params.m_cost = (params.m_cost * 2).min(CALIBRATION_MAX_M_COST);
+        } else if params.t_cost < KdfParams::MAX_T_COST {
+            params.t_cost += 1;
+        } else {
+            break;
+        }
+    }
+
+    Ok(best.0)
+}
+
+/// Derive a 256-bit key from password using Argon2id
+/// The key is scrubbed from memory when dropped.
+fn derive_key(password: &str, salt: &[u8], params: KdfParams) -> Result<Zeroizing<[u8; 32]>, String> {
+    let argon2 = params.to_argon2()?;
+
+    // Hash straight into the key buffer so no other copy of the output exists
+    let mut key = Zeroizing::new([0u8; 32]);
+    argon2
+        .hash_password_into(password.as_bytes(), salt, &mut key[..])
+        .map_err(|e| format!("Hash error: {}", e))?;
 
     Ok(key)
 }
 
+/// Derive a deterministic key for a file from a master password.
+///
+/// The Argon2id salt is the first 16 bytes of SHA-256(file_path), so the same
+/// password + path always yields the same key. Moving or renaming the file
+/// changes the path and therefore the key.
+pub fn derive_file_key(master_password: &str, file_path: &str) -> Result<Zeroizing<[u8; 32]>, String> {
+    use sha2::{Digest, Sha256};
+
+    let digest = Sha256::digest(file_path.as_bytes());
+    // Fixed parameters: changing them would change every file's key
+    derive_key(master_password, &digest[..16], KdfParams::LEGACY)
+}
+
 /// Encrypt plaintext with password
 pub fn encrypt(plaintext: &str, password: &str, hint: Option<String>) -> Result<EncryptedEnvelope, String> {
+    encrypt_with_params(plaintext, password, hint, KdfParams::default(), CipherAlgorithm::default())
+}
+
+/// Encrypt plaintext with password using explicit Argon2 parameters and cipher
+pub fn encrypt_with_params(
+    plaintext: &str,
+    password: &str,
+    hint: Option<String>,
+    params: KdfParams,
+    algorithm: CipherAlgorithm,
+) -> Result<EncryptedEnvelope, String> {
     // Generate random salt (16 bytes)
     let mut salt = [0u8; 16];
     OsRng.fill_bytes(&mut salt);
 
     // Derive key from password
-    let key = derive_key(password, &salt)?;
+    let key = derive_key(password, &salt, params)?;
 
-    // Generate random nonce (12 bytes for AES-GCM)
+    // Generate random nonce (12 bytes for both ciphers)
     let mut nonce_bytes = [0u8; 12];
     OsRng.fill_bytes(&mut nonce_bytes);
-    let nonce = Nonce::from_slice(&nonce_bytes);
-
-    // Create cipher and encrypt
-    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|e| format!("Cipher error: {}", e))?;
-    let ciphertext = cipher
-        .encrypt(nonce, plaintext.as_bytes())
-        .map_err(|e| format!("Encryption error: {}", e))?;
-
-    Ok(EncryptedEnvelope {
-        ciphertext: BASE64.encode(&ciphertext),
-        nonce: BASE64.encode(&nonce_bytes),
-        salt: BASE64.encode(&salt),
+
+    let mut envelope = EncryptedEnvelope {
+        ciphertext: String::new(),
+        nonce: BASE64.encode(nonce_bytes),
+        salt: BASE64.encode(salt),
         hint,
-        version: 1,
-    })
+        version: ENVELOPE_VERSION,
+        algorithm,
+        m_cost: Some(params.m_cost),
+        t_cost: Some(params.t_cost),
+        p_cost: Some(params.p_cost),
+    };
+    let aad = envelope.associated_data()?;
+    let ciphertext = algorithm.encrypt(&key, &nonce_bytes, plaintext.as_bytes(), &aad)?;
+    envelope.ciphertext = BASE64.encode(ciphertext);
+
+    Ok(envelope)
 }
 
-/// Decrypt ciphertext with password
+/// Decrypt ciphertext with password.
+/// Structural problems fail with `CORRUPTED_ENVELOPE_ERROR` before any key is derived;
+/// only an authentication tag mismatch yields `DECRYPTION_FAILED_ERROR`.
 pub fn decrypt(envelope: &EncryptedEnvelope, password: &str) -> Result<String, String> {
-    // Decode base64 components
+    // Decode and check base64 components
     let ciphertext = BASE64
         .decode(&envelope.ciphertext)
-        .map_err(|e| format!("Ciphertext decode error: {}", e))?;
-    let nonce_bytes = BASE64
+        .map_err(|e| corrupted(format!("ciphertext is not valid base64: {}", e)))?;
+    if ciphertext.len() < 16 {
+        return Err(corrupted("ciphertext is shorter than the 16-byte authentication tag"));
+    }
+    let nonce_bytes: [u8; 12] = BASE64
         .decode(&envelope.nonce)
-        .map_err(|e| format!("Nonce decode error: {}", e))?;
+        .map_err(|e| corrupted(format!("nonce is not valid base64: {}", e)))?
+        .try_into()
+        .map_err(|nonce: Vec<u8>| corrupted(format!("nonce must be 12 bytes, got {}", nonce.len())))?;
     let salt = BASE64
         .decode(&envelope.salt)
-        .map_err(|e| format!("Salt decode error: {}", e))?;
-
-    // Derive key from password
-    let key = derive_key(password, &salt)?;
+        .map_err(|e| corrupted(format!("salt is not valid base64: {}", e)))?;
+    if salt.is_empty() {
+        return Err(corrupted("salt is empty"));
+    }
+    let params = envelope.kdf_params().map_err(corrupted)?;
 
-    // Create cipher and decrypt
-    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|e| format!("Cipher error: {}", e))?;
-    let nonce = Nonce::from_slice(&nonce_bytes);
+    // Derive key from password, with the parameters it was encrypted with
+    let key = derive_key(password, &salt, params)?;
 
-    let plaintext = cipher
-        .decrypt(nonce, ciphertext.as_ref())
-        .map_err(|_| "Decryption failed - wrong password or corrupted data".to_string())?;
+    let aad = envelope.associated_data()?;
+    let plaintext = envelope.algorithm.decrypt(&key, &nonce_bytes, &ciphertext, &aad)?;
 
     String::from_utf8(plaintext).map_err(|e| format!("UTF-8 decode error: {}", e))
 }
 
+/// Decrypt with `old_password` and encrypt again with `new_password`, entirely in memory.
+/// Keeps the KDF parameters and cipher; salt and nonce are fresh.
+pub fn reencrypt(
+    envelope: &EncryptedEnvelope,
+    old_password: &str,
+    new_password: &str,
+    new_hint: Option<String>,
+) -> Result<EncryptedEnvelope, String> {
+    let plaintext = Zeroizing::new(decrypt(envelope, old_password)?);
+    encrypt_with_params(&plaintext, new_password, new_hint, envelope.kdf_params()?, envelope.algorithm)
+}
+
+/// Nonce for chunk `counter` of an encrypted file
+fn chunk_nonce(prefix: &[u8; 7], counter: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..7].copy_from_slice(prefix);
+    nonce[7..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+/// Read until `buf` is full or the reader is exhausted, returning the bytes read
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Encrypt `reader` to `writer` in the chunked file format (see `FileEnvelopeHeader`)
+fn encrypt_stream(
+    reader: &mut impl Read,
+    writer: &mut impl Write,
+    password: &str,
+    hint: Option<String>,
+) -> Result<(), String> {
+    let params = KdfParams::default();
+    let algorithm = CipherAlgorithm::default();
+
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let mut prefix = [0u8; 7];
+    OsRng.fill_bytes(&mut prefix);
+
+    let header = FileEnvelopeHeader {
+        format: FILE_ENVELOPE_FORMAT.to_string(),
+        version: 1,
+        algorithm,
+        salt: BASE64.encode(salt),
+        nonce_prefix: BASE64.encode(prefix),
+        chunk_size: FILE_CHUNK_SIZE,
+        hint,
+        m_cost: params.m_cost,
+        t_cost: params.t_cost,
+        p_cost: params.p_cost,
+    };
+    // JSON escapes newlines, so the header is always a single line
+    let header_line = serde_json::to_vec(&header).map_err(|e| format!("Serialize error: {}", e))?;
+    let key = derive_key(password, &salt, params)?;
+
+    let write_error = |e: std::io::Error| format!("Failed to write encrypted file: {}", e);
+    writer.write_all(&header_line).map_err(write_error)?;
+    writer.write_all(b"\n").map_err(write_error)?;
+
+    let mut buf = Zeroizing::new(vec![0u8; FILE_CHUNK_SIZE as usize]);
+    let mut counter: u32 = 0;
+    loop {
+        let len = read_full(reader, &mut buf).map_err(|e| format!("Failed to read input file: {}", e))?;
+        let last = len < buf.len();
+        let chunk = algorithm.encrypt(&key, &chunk_nonce(&prefix, counter, last), &buf[..len], &header_line)?;
+        writer.write_all(&chunk).map_err(write_error)?;
+        if last {
+            break;
+        }
+        counter = counter
+            .checked_add(1)
+            .ok_or_else(|| "File is too large to encrypt".to_string())?;
+    }
+
+    writer.flush().map_err(write_error)
+}
+
+/// Decrypt a chunked encrypted file from `reader` to `writer`
+fn decrypt_stream(reader: &mut impl BufRead, writer: &mut impl Write, password: &str) -> Result<(), String> {
+    let mut header_line = Vec::new();
+    reader
+        .by_ref()
+        .take(MAX_FILE_HEADER_LEN)
+        .read_until(b'\n', &mut header_line)
+        .map_err(|e| format!("Failed to read encrypted file: {}", e))?;
+    if header_line.pop() != Some(b'\n') {
+        return Err(corrupted("missing file header"));
+    }
+
+    let header: FileEnvelopeHeader =
+        serde_json::from_slice(&header_line).map_err(|e| corrupted(format!("invalid file header: {}", e)))?;
+    if header.format != FILE_ENVELOPE_FORMAT || header.version != 1 {
+        return Err(corrupted("not a supported encrypted file"));
+    }
+    if header.chunk_size == 0 || header.chunk_size > MAX_FILE_CHUNK_SIZE {
+        return Err(corrupted(format!("invalid chunk size {}", header.chunk_size)));
+    }
+    let prefix: [u8; 7] = BASE64
+        .decode(&header.nonce_prefix)
+        .map_err(|e| corrupted(format!("nonce prefix is not valid base64: {}", e)))?
+        .try_into()
+        .map_err(|prefix: Vec<u8>| corrupted(format!("nonce prefix must be 7 bytes, got {}", prefix.len())))?;
+    let salt = BASE64
+        .decode(&header.salt)
+        .map_err(|e| corrupted(format!("salt is not valid base64: {}", e)))?;
+    if salt.is_empty() {
+        return Err(corrupted("salt is empty"));
+    }
+
+    let params = KdfParams {
+        m_cost: header.m_cost,
+        t_cost: header.t_cost,
+        p_cost: header.p_cost,
+    };
+    let key = derive_key(password, &salt, params)?;
+
+    let write_error = |e: std::io::Error| format!("Failed to write decrypted file: {}", e);
+    let mut buf = vec![0u8; header.chunk_size as usize + 16];
+    let mut counter: u32 = 0;
+    loop {
+        let len = read_full(reader, &mut buf).map_err(|e| format!("Failed to read encrypted file: {}", e))?;
+        if len < 16 {
+            return Err(corrupted("file is truncated"));
+        }
+        let last = len < buf.len();
+        let nonce = chunk_nonce(&prefix, counter, last);
+        let plaintext = Zeroizing::new(header.algorithm.decrypt(&key, &nonce, &buf[..len], &header_line)?);
+        writer.write_all(&plaintext).map_err(write_error)?;
+        if last {
+            break;
+        }
+        counter = counter
+            .checked_add(1)
+            .ok_or_else(|| corrupted("too many chunks"))?;
+    }
+
+    writer.flush().map_err(write_error)
+}
+
+/// Encrypt a file to `out_path`, streaming it in chunks rather than loading it whole
+pub fn encrypt_file(
+    in_path: &Path,
+    out_path: &Path,
+    password: &str,
+    hint: Option<String>,
+) -> Result<(), String> {
+    let input = File::open(in_path).map_err(|e| format!("Failed to open {}: {}", in_path.display(), e))?;
+    platform::write_file_atomic_with(out_path, |file| {
+        encrypt_stream(&mut BufReader::new(input), &mut BufWriter::new(file), password, hint)
+    })
+}
+
+/// Decrypt a file written by `encrypt_file`. Nothing is written to `out_path`
+/// unless the whole file decrypts and authenticates.
+pub fn decrypt_file(in_path: &Path, out_path: &Path, password: &str) -> Result<(), String> {
+    let input = File::open(in_path).map_err(|e| format!("Failed to open {}: {}", in_path.display(), e))?;
+    platform::write_file_atomic_with(out_path, |file| {
+        decrypt_stream(&mut BufReader::new(input), &mut BufWriter::new(file), password)
+    })
+}
+
+/// Generate a random passphrase of `word_count` words joined by `separator`
+pub fn generate_passphrase(word_count: u8, separator: char) -> Passphrase {
+    use rand::Rng;
+
+    let words: Vec<&str> = WORDLIST.lines().filter(|w| !w.is_empty()).collect();
+    let mut rng = rand::rngs::OsRng;
+
+    let passphrase = (0..word_count)
+        .map(|_| words[rng.gen_range(0..words.len())])
+        .collect::<Vec<_>>()
+        .join(&separator.to_string());
+
+    Passphrase {
+        passphrase,
+        entropy_bits: word_count as f64 * (words.len() as f64).log2(),
+    }
+}
+
+/// Estimate how guessable a password is using zxcvbn
+pub fn estimate_strength(password: &str) -> PasswordStrength {
+    match zxcvbn::zxcvbn(password, &[]) {
+        Ok(entropy) => PasswordStrength {
+            score: entropy.score(),
+            // Argon2 makes offline guessing slow, so rate against a slow hash
+            crack_time: entropy.crack_times().offline_slow_hashing_1e4_per_second().to_string(),
+        },
+        // zxcvbn rejects empty passwords
+        Err(_) => PasswordStrength {
+            score: 0,
+            crack_time: "instant".to_string(),
+        },
+    }
+}
+
+/// Length of padded base64 output for `len` input bytes
+fn base64_len(len: usize) -> usize {
+    len.div_ceil(3) * 4
+}
+
+/// Serialized envelope size (without hint) for a plaintext of `plaintext_len` bytes.
+/// Ciphertext is plaintext + 16-byte GCM tag; salt and nonce are fixed size.
+pub fn envelope_size_bytes(plaintext_len: usize) -> usize {
+    // Measure the JSON framing with placeholder fields of the right lengths
+    let params = KdfParams::default();
+    let placeholder = EncryptedEnvelope {
+        ciphertext: "A".repeat(base64_len(plaintext_len + 16)),
+        nonce: "A".repeat(base64_len(12)),
+        salt: "A".repeat(base64_len(16)),
+        hint: None,
+        version: ENVELOPE_VERSION,
+        algorithm: CipherAlgorithm::default(),
+        m_cost: Some(params.m_cost),
+        t_cost: Some(params.t_cost),
+        p_cost: Some(params.p_cost),
+    };
+
+    serde_json::to_string(&placeholder)
+        .map(|json| json.len())
+        .unwrap_or(0)
+}
+
+/// Bytes added by encryption on top of the plaintext itself
+pub fn envelope_overhead_bytes(plaintext_len: usize) -> usize {
+    envelope_size_bytes(plaintext_len).saturating_sub(plaintext_len)
+}
+
 /// Serialize envelope to JSON string
 pub fn envelope_to_string(envelope: &EncryptedEnvelope) -> Result<String, String> {
     serde_json::to_string(envelope).map_err(|e| format!("Serialize error: {}", e))
@@ -127,6 +637,20 @@ pub fn string_to_envelope(data: &str) -> Result<EncryptedEnvelope, String> {
 mod tests {
     use super::*;
 
+    /// Envelope encrypted without associated data, as versions before 3 were
+    fn unauthenticated_envelope(plaintext: &str, password: &str, version: u8) -> EncryptedEnvelope {
+        let mut envelope =
+            encrypt_with_params(plaintext, password, None, KdfParams::LEGACY, CipherAlgorithm::default())
+                .unwrap();
+        envelope.version = version;
+
+        let key = derive_key(password, &BASE64.decode(&envelope.salt).unwrap(), KdfParams::LEGACY).unwrap();
+        let nonce: [u8; 12] = BASE64.decode(&envelope.nonce).unwrap().try_into().unwrap();
+        let ciphertext = envelope.algorithm.encrypt(&key, &nonce, plaintext.as_bytes(), &[]).unwrap();
+        envelope.ciphertext = BASE64.encode(ciphertext);
+        envelope
+    }
+
     #[test]
     fn test_encrypt_decrypt() {
         let plaintext = "Hello, World! 你好世界";
@@ -147,6 +671,251 @@ mod tests {
         let envelope = encrypt(plaintext, password, None).unwrap();
         let result = decrypt(&envelope, wrong_password);
 
-        assert!(result.is_err());
+        assert_eq!(result.unwrap_err(), DECRYPTION_FAILED_ERROR);
+    }
+
+    #[test]
+    fn test_corrupted_envelope_errors() {
+        let envelope = encrypt("Secret data", "password", None).unwrap();
+        let corrupt = |edit: fn(&mut EncryptedEnvelope)| {
+            let mut envelope = envelope.clone();
+            edit(&mut envelope);
+            decrypt(&envelope, "password").unwrap_err()
+        };
+
+        let errors = [
+            corrupt(|e| e.ciphertext = "not base64!".to_string()),
+            corrupt(|e| e.ciphertext = BASE64.encode([0u8; 8])),
+            corrupt(|e| e.nonce = BASE64.encode([0u8; 8])),
+            corrupt(|e| e.salt = String::new()),
+            corrupt(|e| e.m_cost = None),
+        ];
+        for error in errors {
+            assert!(error.starts_with(CORRUPTED_ENVELOPE_ERROR), "{}", error);
+        }
+
+        // A flipped ciphertext byte is only detectable by the tag check
+        let mut tampered = envelope.clone();
+        let mut bytes = BASE64.decode(&tampered.ciphertext).unwrap();
+        bytes[0] ^= 1;
+        tampered.ciphertext = BASE64.encode(bytes);
+        assert_eq!(decrypt(&tampered, "password").unwrap_err(), DECRYPTION_FAILED_ERROR);
+    }
+
+    #[test]
+    fn test_generate_passphrase() {
+        let result = generate_passphrase(6, '-');
+        let words: Vec<&str> = result.passphrase.split('-').collect();
+
+        assert_eq!(words.len(), 6);
+        assert!(words.iter().all(|w| WORDLIST.lines().any(|l| l == *w)));
+        assert!(result.entropy_bits > 60.0);
+    }
+
+    #[test]
+    fn test_envelope_size_matches_encrypt() {
+        for len in [0, 1, 2, 3, 100, 1000] {
+            let plaintext = "x".repeat(len);
+            let envelope = encrypt(&plaintext, "password", None).unwrap();
+            let actual = envelope_to_string(&envelope).unwrap().len();
+
+            assert_eq!(envelope_size_bytes(len), actual, "plaintext length {}", len);
+        }
+        assert_eq!(envelope_overhead_bytes(3), envelope_size_bytes(3) - 3);
+    }
+
+    #[test]
+    fn test_derive_file_key() {
+        let key = derive_file_key("password", "/home/user/notes.txt").unwrap();
+
+        assert_eq!(key, derive_file_key("password", "/home/user/notes.txt").unwrap());
+        assert_ne!(key, derive_file_key("password", "/home/user/other.txt").unwrap());
+        assert_ne!(key, derive_file_key("other", "/home/user/notes.txt").unwrap());
+    }
+
+    #[test]
+    fn test_custom_kdf_params_roundtrip() {
+        let params = KdfParams { m_cost: 8 * 1024, t_cost: 3, p_cost: 2 };
+        let envelope =
+            encrypt_with_params("tuned", "password", None, params, CipherAlgorithm::default()).unwrap();
+
+        // Parameters travel with the envelope, so decrypt doesn't need them passed in
+        let envelope = string_to_envelope(&envelope_to_string(&envelope).unwrap()).unwrap();
+        assert_eq!(envelope.version, ENVELOPE_VERSION);
+        assert_eq!(envelope.kdf_params().unwrap(), params);
+        assert_eq!(decrypt(&envelope, "password").unwrap(), "tuned");
+
+        let too_big = KdfParams { m_cost: u32::MAX, ..params };
+        assert!(
+            encrypt_with_params("x", "password", None, too_big, CipherAlgorithm::default()).is_err()
+        );
+    }
+
+    #[test]
+    fn test_v1_envelope_uses_legacy_params() {
+        // A version-1 envelope has no KDF fields
+        let mut envelope = unauthenticated_envelope("old", "password", 1);
+        envelope.m_cost = None;
+        envelope.t_cost = None;
+        envelope.p_cost = None;
+
+        let json = envelope_to_string(&envelope).unwrap();
+        assert!(!json.contains("m_cost"));
+        let envelope = string_to_envelope(&json).unwrap();
+        assert_eq!(envelope.kdf_params().unwrap(), KdfParams::LEGACY);
+        assert_eq!(decrypt(&envelope, "password").unwrap(), "old");
+    }
+
+    #[test]
+    fn test_calibrate_kdf_floor() {
+        // A target faster than the legacy cost can't go below it
+        assert_eq!(calibrate_kdf(Duration::from_millis(1)).unwrap(), KdfParams::LEGACY);
+    }
+
+    #[test]
+    fn test_cipher_algorithms_roundtrip() {
+        for algorithm in [CipherAlgorithm::Aes256Gcm, CipherAlgorithm::ChaCha20Poly1305] {
+            let envelope =
+                encrypt_with_params("secret", "password", None, KdfParams::default(), algorithm).unwrap();
+            let envelope = string_to_envelope(&envelope_to_string(&envelope).unwrap()).unwrap();
+
+            assert_eq!(envelope.algorithm, algorithm);
+            assert_eq!(decrypt(&envelope, "password").unwrap(), "secret");
+            assert!(decrypt(&envelope, "wrong").is_err());
+        }
+    }
+
+    #[test]
+    fn test_envelope_without_algorithm_is_aes() {
+        let envelope = encrypt("secret", "password", None).unwrap();
+        let mut json: serde_json::Value = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(json["algorithm"], "aes-256-gcm");
+        json.as_object_mut().unwrap().remove("algorithm");
+
+        let envelope: EncryptedEnvelope = serde_json::from_value(json).unwrap();
+        assert_eq!(envelope.algorithm, CipherAlgorithm::Aes256Gcm);
+        assert_eq!(decrypt(&envelope, "password").unwrap(), "secret");
+    }
+
+    #[test]
+    fn test_reencrypt() {
+        let params = KdfParams { m_cost: 8 * 1024, t_cost: 1, p_cost: 1 };
+        let original =
+            encrypt_with_params("secret", "old", None, params, CipherAlgorithm::ChaCha20Poly1305).unwrap();
+
+        assert!(reencrypt(&original, "wrong", "new", None).is_err());
+
+        let rotated = reencrypt(&original, "old", "new", Some("hint".to_string())).unwrap();
+        assert_eq!(decrypt(&rotated, "new").unwrap(), "secret");
+        assert!(decrypt(&rotated, "old").is_err());
+        assert_ne!(rotated.salt, original.salt);
+        assert_ne!(rotated.nonce, original.nonce);
+        assert_eq!(rotated.hint.as_deref(), Some("hint"));
+        assert_eq!(rotated.kdf_params().unwrap(), params);
+        assert_eq!(rotated.algorithm, CipherAlgorithm::ChaCha20Poly1305);
+    }
+
+    #[test]
+    fn test_metadata_is_authenticated() {
+        let envelope = encrypt("secret", "password", Some("real hint".to_string())).unwrap();
+
+        let mut tampered = envelope.clone();
+        tampered.hint = Some("fake hint".to_string());
+        assert_eq!(decrypt(&tampered, "password").unwrap_err(), DECRYPTION_FAILED_ERROR);
+
+        let mut tampered = envelope.clone();
+        tampered.version = 2;
+        assert_eq!(decrypt(&tampered, "password").unwrap_err(), DECRYPTION_FAILED_ERROR);
+
+        // Older versions carry no associated data and still decrypt
+        let mut old = unauthenticated_envelope("secret", "password", 2);
+        old.hint = Some("edited".to_string());
+        assert_eq!(decrypt(&old, "password").unwrap(), "secret");
+    }
+
+    #[test]
+    fn test_derive_key_matches_password_hasher() {
+        use argon2::{password_hash::SaltString, PasswordHasher};
+
+        // Keys must stay identical to the PasswordHasher output earlier releases derived
+        let salt = [7u8; 16];
+        let argon2 = KdfParams::LEGACY.to_argon2().unwrap();
+        let hash = argon2
+            .hash_password(b"password", &SaltString::encode_b64(&salt).unwrap())
+            .unwrap();
+
+        let key = derive_key("password", &salt, KdfParams::LEGACY).unwrap();
+        assert_eq!(hash.hash.unwrap().as_bytes(), &key[..]);
+    }
+
+    #[test]
+    fn test_estimate_strength() {
+        assert_eq!(estimate_strength("").score, 0);
+        assert_eq!(estimate_strength("password").score, 0);
+
+        let strong = estimate_strength("correct-horse-battery-staple-quartz-lantern");
+        assert_eq!(strong.score, 4);
+        assert!(!strong.crack_time.is_empty());
+    }
+
+    #[test]
+    fn test_file_stream_roundtrip() {
+        let chunk = FILE_CHUNK_SIZE as usize;
+        for len in [0, 1, chunk - 1, chunk, 2 * chunk + 5] {
+            let plaintext: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+            let mut encrypted = Vec::new();
+            encrypt_stream(&mut plaintext.as_slice(), &mut encrypted, "password", None).unwrap();
+
+            let mut decrypted = Vec::new();
+            decrypt_stream(&mut encrypted.as_slice(), &mut decrypted, "password").unwrap();
+            assert_eq!(decrypted, plaintext, "length {}", len);
+
+            let mut sink = Vec::new();
+            let error = decrypt_stream(&mut encrypted.as_slice(), &mut sink, "wrong").unwrap_err();
+            assert_eq!(error, DECRYPTION_FAILED_ERROR);
+        }
+    }
+
+    #[test]
+    fn test_file_stream_detects_tampering() {
+        let chunk = FILE_CHUNK_SIZE as usize;
+        let plaintext = vec![7u8; 2 * chunk];
+        let mut encrypted = Vec::new();
+        encrypt_stream(&mut plaintext.as_slice(), &mut encrypted, "password", None).unwrap();
+        let body_start = encrypted.iter().position(|&b| b == b'\n').unwrap() + 1;
+
+        // Dropping the final (empty) chunk leaves a file that ends on a chunk boundary
+        let truncated = &encrypted[..encrypted.len() - 16];
+        assert!(decrypt_stream(&mut &truncated[..], &mut Vec::new(), "password").is_err());
+
+        // Swapping the first two chunks breaks their nonces
+        let mut swapped = encrypted.clone();
+        let sealed = chunk + 16;
+        let (first, rest) = swapped[body_start..].split_at_mut(sealed);
+        first.swap_with_slice(&mut rest[..sealed]);
+        assert!(decrypt_stream(&mut swapped.as_slice(), &mut Vec::new(), "password").is_err());
+
+        // The header is authenticated too
+        let header = String::from_utf8_lossy(&encrypted[..body_start]);
+        let mut tampered = header.replace("\"hint\":null", "\"hint\":\"x\"").into_bytes();
+        tampered.extend_from_slice(&encrypted[body_start..]);
+        assert!(decrypt_stream(&mut tampered.as_slice(), &mut Vec::new(), "password").is_err());
+    }
+
+    #[test]
+    fn test_decrypt_file_leaves_no_output_on_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let plain = dir.path().join("secret.txt");
+        let sealed = dir.path().join("secret.txt.enc");
+        let opened = dir.path().join("opened.txt");
+        std::fs::write(&plain, "top secret").unwrap();
+
+        encrypt_file(&plain, &sealed, "password", Some("hint".to_string())).unwrap();
+        assert!(decrypt_file(&sealed, &opened, "wrong").is_err());
+        assert!(!opened.exists());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+
+        decrypt_file(&sealed, &opened, "password").unwrap();
+        assert_eq!(std::fs::read_to_string(&opened).unwrap(), "top secret");
     }
 }
@@ -1,25 +1,66 @@
+use crate::platform::{self, run_cli};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+/// How long to wait for an external secrets CLI before giving up
+const SECRET_CLI_TIMEOUT: Duration = Duration::from_secs(15);
 
 /// Result of parsing environment files
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct EnvParseResult {
     pub env_vars: HashMap<String, String>,
     pub source: String,
     pub errors: Vec<String>,
+    /// Values are secrets and should be masked in the UI
+    #[serde(default)]
+    pub redacted: bool,
+    /// Files that contributed, in precedence order (layered reads only)
+    #[serde(default)]
+    pub files: Vec<String>,
+}
+
+/// How `parse_env_file_with` treats `${VAR}` and `$VAR` references in values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VarExpansion {
+    /// Store values exactly as written (e.g. secrets that may contain `$`)
+    Literal,
+    /// Expand references; undefined ones become empty, like dotenv
+    #[default]
+    Lenient,
+    /// Expand references; an undefined one is an error
+    Strict,
 }
 
 /// Parse a .env file and return key-value pairs
 /// Supports:
 /// - KEY=value
 /// - KEY="quoted value"
-/// - KEY='single quoted'
+/// - KEY='single quoted' (not expanded)
+/// - ${VAR} and $VAR references to earlier keys or the process environment (\$ for a literal $)
 /// - # comments
 /// - Empty lines (ignored)
 pub fn parse_env_file(content: &str) -> HashMap<String, String> {
-    let mut result = HashMap::new();
+    // Lenient expansion never fails
+    parse_env_file_with(content, VarExpansion::Lenient).unwrap_or_default()
+}
+
+/// Parse a .env file, expanding variable references according to `expansion`
+pub fn parse_env_file_with(content: &str, expansion: VarExpansion) -> Result<HashMap<String, String>, String> {
+    parse_env_onto(content, expansion, HashMap::new())
+}
+
+/// Parse `content` on top of `base`: its keys override base keys, and its references can see them
+fn parse_env_onto(
+    content: &str,
+    expansion: VarExpansion,
+    base: HashMap<String, String>,
+) -> Result<HashMap<String, String>, String> {
+    let mut result = base;
 
     for line in content.lines() {
         let line = line.trim();
@@ -33,12 +74,14 @@ pub fn parse_env_file(content: &str) -> HashMap<String, String> {
         if let Some(eq_pos) = line.find('=') {
             let key = line[..eq_pos].trim().to_string();
             let mut value = line[eq_pos + 1..].trim().to_string();
+            let mut single_quoted = false;
 
             // Handle quoted values
             if (value.starts_with('"') && value.ends_with('"'))
                 || (value.starts_with('\'') && value.ends_with('\''))
             {
                 if value.len() >= 2 {
+                    single_quoted = value.starts_with('\'');
                     value = value[1..value.len() - 1].to_string();
                 }
             }
@@ -51,17 +94,89 @@ pub fn parse_env_file(content: &str) -> HashMap<String, String> {
                 value = value.replace("\\t", "\t");
             }
 
+            if expansion != VarExpansion::Literal && !single_quoted {
+                value = expand_vars(&value, &result, expansion == VarExpansion::Strict)
+                    .map_err(|e| format!("{}: {}", key, e))?;
+            }
+
             if !key.is_empty() {
                 result.insert(key, value);
             }
         }
     }
 
-    result
+    Ok(result)
+}
+
+/// Expand `${VAR}` and `$VAR` against `defined`, falling back to the process environment.
+/// `\$` yields a literal `$`. Undefined references are empty unless `strict`.
+fn expand_vars(value: &str, defined: &HashMap<String, String>, strict: bool) -> Result<String, String> {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' && chars.peek() == Some(&'$') {
+            result.push('$');
+            chars.next();
+            continue;
+        }
+        if c != '$' {
+            result.push(c);
+            continue;
+        }
+
+        let name = if chars.peek() == Some(&'{') {
+            let rest: String = chars.clone().skip(1).collect();
+            match rest.find('}') {
+                Some(end) if is_valid_env_key(&rest[..end]) => {
+                    // Skip the braces and the name
+                    for _ in 0..end + 2 {
+                        chars.next();
+                    }
+                    rest[..end].to_string()
+                }
+                // Unterminated or malformed: keep the text as written
+                _ => {
+                    result.push('$');
+                    continue;
+                }
+            }
+        } else {
+            let mut name = String::new();
+            while let Some(&c) = chars.peek() {
+                let valid = c == '_' || c.is_ascii_alphabetic() || (!name.is_empty() && c.is_ascii_digit());
+                if !valid {
+                    break;
+                }
+                name.push(c);
+                chars.next();
+            }
+            if name.is_empty() {
+                // A lone `$` (or `$5`) is literal
+                result.push('$');
+                continue;
+            }
+            name
+        };
+
+        match defined.get(&name).cloned().or_else(|| std::env::var(&name).ok()) {
+            Some(expanded) => result.push_str(&expanded),
+            None if strict => return Err(format!("undefined variable ${{{}}}", name)),
+            None => {}
+        }
+    }
+
+    Ok(result)
 }
 
 /// Read and parse .env file from a directory
 pub fn read_env_file(dir_path: &str) -> EnvParseResult {
+    read_env_file_with(dir_path, VarExpansion::Lenient)
+}
+
+/// Read .env from a directory, expanding references according to `expansion`.
+/// A Strict expansion failure is reported in `errors`.
+pub fn read_env_file_with(dir_path: &str, expansion: VarExpansion) -> EnvParseResult {
     let env_path = Path::new(dir_path).join(".env");
 
     if !env_path.exists() {
@@ -69,19 +184,28 @@ pub fn read_env_file(dir_path: &str) -> EnvParseResult {
             env_vars: HashMap::new(),
             source: ".env".to_string(),
             errors: vec![],
+            ..Default::default()
         };
     }
 
     match fs::read_to_string(&env_path) {
-        Ok(content) => EnvParseResult {
-            env_vars: parse_env_file(&content),
-            source: ".env".to_string(),
-            errors: vec![],
-        },
+        Ok(content) => {
+            let (env_vars, errors) = match parse_env_file_with(&content, expansion) {
+                Ok(env_vars) => (env_vars, vec![]),
+                Err(e) => (HashMap::new(), vec![format!(".env: {}", e)]),
+            };
+            EnvParseResult {
+                env_vars,
+                source: ".env".to_string(),
+                errors,
+                ..Default::default()
+            }
+        }
         Err(e) => EnvParseResult {
             env_vars: HashMap::new(),
             source: ".env".to_string(),
             errors: vec![format!("Failed to read .env: {}", e)],
+            ..Default::default()
         },
     }
 }
@@ -97,6 +221,7 @@ pub fn read_envrc_file(dir_path: &str) -> EnvParseResult {
             env_vars: HashMap::new(),
             source: ".envrc".to_string(),
             errors: vec![],
+            ..Default::default()
         };
     }
 
@@ -119,13 +244,11 @@ pub fn read_envrc_file(dir_path: &str) -> EnvParseResult {
                         let key = rest[..eq_pos].trim().to_string();
                         let mut value = rest[eq_pos + 1..].trim().to_string();
 
-                        // Handle quoted values
-                        if (value.starts_with('"') && value.ends_with('"'))
-                            || (value.starts_with('\'') && value.ends_with('\''))
-                        {
-                            if value.len() >= 2 {
-                                value = value[1..value.len() - 1].to_string();
-                            }
+                        // Handle quoted values (double quotes may contain shell escapes)
+                        if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+                            value = unescape_double_quoted(&value[1..value.len() - 1]);
+                        } else if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+                            value = value[1..value.len() - 1].to_string();
                         }
 
                         if !key.is_empty() {
@@ -142,16 +265,245 @@ pub fn read_envrc_file(dir_path: &str) -> EnvParseResult {
                 env_vars: result,
                 source: ".envrc".to_string(),
                 errors,
+                ..Default::default()
             }
         }
         Err(e) => EnvParseResult {
             env_vars: HashMap::new(),
             source: ".envrc".to_string(),
             errors: vec![format!("Failed to read .envrc: {}", e)],
+            ..Default::default()
         },
     }
 }
 
+/// Read and parse `.env.{profile}` from a directory (e.g. `.env.development`)
+pub fn read_env_profile_file(dir_path: &str, profile: &str) -> EnvParseResult {
+    read_env_profile_file_with(dir_path, profile, VarExpansion::Lenient)
+}
+
+/// `read_env_profile_file` with the given reference expansion
+fn read_env_profile_file_with(dir_path: &str, profile: &str, expansion: VarExpansion) -> EnvParseResult {
+    let source = format!(".env.{}", profile);
+    let mut result = EnvParseResult {
+        env_vars: HashMap::new(),
+        source: source.clone(),
+        errors: vec![],
+        ..Default::default()
+    };
+
+    if !is_valid_env_profile(profile) {
+        result.errors.push(format!("Invalid env profile name: {}", profile));
+        return result;
+    }
+
+    let path = Path::new(dir_path).join(&source);
+    if path.exists() {
+        match fs::read_to_string(&path) {
+            Ok(content) => match parse_env_file_with(&content, expansion) {
+                Ok(env_vars) => result.env_vars = env_vars,
+                Err(e) => result.errors.push(format!("{}: {}", source, e)),
+            },
+            Err(e) => result.errors.push(format!("Failed to read {}: {}", source, e)),
+        }
+    }
+
+    result
+}
+
+/// Check that a profile/mode name can't escape the directory it's joined to
+fn is_valid_env_profile(profile: &str) -> bool {
+    !profile.is_empty() && !profile.contains(['/', '\\']) && !profile.contains("..")
+}
+
+/// Read `.env`, `.env.local`, `.env.{mode}` and `.env.{mode}.local` from a directory,
+/// merged in that order so later files override earlier ones (dotenv precedence).
+/// Later files can reference keys from earlier ones.
+pub fn read_env_layered(dir_path: &str, mode: Option<String>, expansion: VarExpansion) -> EnvParseResult {
+    let mut result = EnvParseResult {
+        env_vars: HashMap::new(),
+        source: "layered".to_string(),
+        errors: vec![],
+        ..Default::default()
+    };
+
+    let mut names = vec![".env".to_string(), ".env.local".to_string()];
+    match mode.filter(|mode| !mode.is_empty()) {
+        Some(mode) if is_valid_env_profile(&mode) => {
+            names.push(format!(".env.{}", mode));
+            names.push(format!(".env.{}.local", mode));
+        }
+        Some(mode) => result.errors.push(format!("Invalid env mode name: {}", mode)),
+        None => {}
+    }
+
+    for name in names {
+        let path = Path::new(dir_path).join(&name);
+        if !path.exists() {
+            continue;
+        }
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                let base = std::mem::take(&mut result.env_vars);
+                match parse_env_onto(&content, expansion, base.clone()) {
+                    Ok(env_vars) => {
+                        result.env_vars = env_vars;
+                        result.files.push(name);
+                    }
+                    Err(e) => {
+                        // Keep what the earlier files defined
+                        result.env_vars = base;
+                        result.errors.push(format!("{}: {}", name, e));
+                    }
+                }
+            }
+            Err(e) => result.errors.push(format!("Failed to read {}: {}", name, e)),
+        }
+    }
+
+    result
+}
+
+/// How `merge` resolves a key present in more than one source
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MergeStrategy {
+    /// Later sources override earlier ones
+    #[default]
+    LastWins,
+    /// The first source to define a key keeps it
+    FirstWins,
+    /// Fail if a key has different values in different sources
+    ErrorOnConflict,
+    /// Reserved for frontend conflict resolution; currently merges like LastWins
+    Prompt,
+}
+
+/// Merge environment maps in order using the given conflict strategy
+pub fn merge(
+    sources: &[HashMap<String, String>],
+    strategy: MergeStrategy,
+) -> Result<HashMap<String, String>, String> {
+    let mut merged: HashMap<String, String> = HashMap::new();
+
+    for source in sources {
+        for (key, value) in source {
+            match strategy {
+                MergeStrategy::LastWins | MergeStrategy::Prompt => {
+                    merged.insert(key.clone(), value.clone());
+                }
+                MergeStrategy::FirstWins => {
+                    merged.entry(key.clone()).or_insert_with(|| value.clone());
+                }
+                MergeStrategy::ErrorOnConflict => match merged.get(key) {
+                    Some(existing) if existing != value => {
+                        return Err(format!("Conflicting values for environment variable {}", key));
+                    }
+                    Some(_) => {}
+                    None => {
+                        merged.insert(key.clone(), value.clone());
+                    }
+                },
+            }
+        }
+    }
+
+    Ok(merged)
+}
+
+/// Resolve the environment for a directory: `.env`, then `.env.{profile}`,
+/// then `.envrc` exports, with later files overriding earlier ones.
+/// With Strict expansion any error fails the whole resolution; otherwise errors are logged.
+pub fn resolve_environment(
+    dir_path: &str,
+    profile: Option<&str>,
+    expansion: VarExpansion,
+) -> Result<HashMap<String, String>, String> {
+    let mut results = vec![read_env_file_with(dir_path, expansion)];
+    if let Some(profile) = profile {
+        results.push(read_env_profile_file_with(dir_path, profile, expansion));
+    }
+    results.push(read_envrc_file(dir_path));
+
+    let mut sources = Vec::with_capacity(results.len());
+    for result in results {
+        if !result.errors.is_empty() {
+            if expansion == VarExpansion::Strict {
+                return Err(result.errors.join("; "));
+            }
+            eprintln!("Errors reading {}: {:?}", result.source, result.errors);
+        }
+        sources.push(result.env_vars);
+    }
+
+    merge(&sources, MergeStrategy::LastWins)
+}
+
+/// Undo shell escapes inside a double-quoted value (\\, \", \$, \`)
+fn unescape_double_quoted(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(&next) = chars.peek() {
+                if matches!(next, '\\' | '"' | '$' | '`') {
+                    result.push(next);
+                    chars.next();
+                    continue;
+                }
+            }
+        }
+        result.push(c);
+    }
+
+    result
+}
+
+/// Escape a value for use inside double quotes in a shell script
+fn escape_double_quoted(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | '"' | '$' | '`') {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// Check if a key is a valid shell variable name
+fn is_valid_env_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Serialize variables as `.envrc` content (`export KEY="value"` lines, sorted by key)
+pub fn format_envrc(vars: &HashMap<String, String>) -> Result<String, String> {
+    let mut keys: Vec<&String> = vars.keys().collect();
+    keys.sort();
+
+    let mut content = String::from("# Generated by moonterm\n");
+    for key in keys {
+        let value = &vars[key];
+        if !is_valid_env_key(key) {
+            return Err(format!("Invalid environment variable name: {}", key));
+        }
+        if value.contains('\n') || value.contains('\r') {
+            return Err(format!("Multi-line values are not supported in .envrc: {}", key));
+        }
+        content.push_str(&format!("export {}=\"{}\"\n", key, escape_double_quoted(value)));
+    }
+
+    Ok(content)
+}
+
+/// Write variables to `{dir_path}/.envrc` (atomically, via a temp file)
+pub fn write_envrc_file(dir_path: &str, vars: &HashMap<String, String>) -> Result<(), String> {
+    let content = format_envrc(vars)?;
+    platform::write_file_atomic(&Path::new(dir_path).join(".envrc"), content.as_bytes())
+}
+
 /// Check if .env file exists in directory
 pub fn has_env_file(dir_path: &str) -> bool {
     Path::new(dir_path).join(".env").exists()
@@ -162,6 +514,687 @@ pub fn has_envrc_file(dir_path: &str) -> bool {
     Path::new(dir_path).join(".envrc").exists()
 }
 
+/// Key fragments that mark a variable as holding a path
+const PATH_KEY_MARKERS: &[&str] = &["PATH", "DIR", "FILE", "HOME"];
+
+/// Expand `~`, `$VAR`/`${VAR}` and relative paths in path-like values, in place.
+/// Only keys in `keys_to_expand` are touched, or by default keys containing
+/// PATH/DIR/FILE/HOME. Variables resolve against the map first, then the process
+/// environment; unknown ones are left as-is. Values starting with `./` or `../`
+/// are made absolute against `base_dir` when given.
+pub fn expand_paths(
+    vars: &mut HashMap<String, String>,
+    keys_to_expand: Option<&[String]>,
+    base_dir: Option<&Path>,
+) {
+    let snapshot = vars.clone();
+    let lookup = |name: &str| -> Option<String> {
+        snapshot.get(name).cloned().or_else(|| std::env::var(name).ok())
+    };
+
+    for (key, value) in vars.iter_mut() {
+        let should_expand = match keys_to_expand {
+            Some(keys) => keys.iter().any(|k| k == key),
+            None => {
+                let upper = key.to_uppercase();
+                PATH_KEY_MARKERS.iter().any(|marker| upper.contains(marker))
+            }
+        };
+        if !should_expand {
+            continue;
+        }
+
+        let expanded = shellexpand::tilde(value.as_str()).to_string();
+        let mut expanded = shellexpand::env_with_context_no_errors(&expanded, &lookup).to_string();
+
+        if let Some(base) = base_dir {
+            if let Some(relative) = expanded.strip_prefix("./") {
+                expanded = base.join(relative).to_string_lossy().to_string();
+            } else if expanded.starts_with("../") {
+                expanded = base.join(&expanded).to_string_lossy().to_string();
+            }
+        }
+
+        *value = expanded;
+    }
+}
+
+/// Parse Procfile content (`name: command` per line) into a map
+/// Only the first colon separates the name, so commands may contain colons
+pub fn parse_procfile_content(content: &str) -> HashMap<String, String> {
+    let mut result = HashMap::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+
+        // Skip empty lines and comments
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(colon_pos) = line.find(':') {
+            let name = line[..colon_pos].trim();
+            let command = line[colon_pos + 1..].trim();
+
+            if !name.is_empty() && !command.is_empty() {
+                result.insert(name.to_string(), command.to_string());
+            }
+        }
+    }
+
+    result
+}
+
+/// Read and parse the Procfile (Heroku-style process definitions) in a directory
+pub fn parse_procfile(dir_path: &str) -> Result<HashMap<String, String>, String> {
+    let procfile_path = Path::new(dir_path).join("Procfile");
+    let content =
+        fs::read_to_string(&procfile_path).map_err(|e| format!("Failed to read Procfile: {}", e))?;
+    Ok(parse_procfile_content(&content))
+}
+
+/// Parse `[section]` headers and `key = value` (or `key: value`) pairs from an .ini file.
+/// With `section`, only that section's keys are returned; otherwise all keys are
+/// returned as `section.key` (keys before the first header are unprefixed).
+pub fn parse_ini_section(content: &str, section: Option<&str>) -> EnvParseResult {
+    let mut env_vars = HashMap::new();
+    let mut errors = Vec::new();
+    let mut current: Option<String> = None;
+
+    for (line_no, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = Some(name.trim().to_string());
+            continue;
+        }
+
+        let Some(sep) = line.find(['=', ':']) else {
+            errors.push(format!("Line {}: expected key = value", line_no + 1));
+            continue;
+        };
+        let key = line[..sep].trim();
+        let mut value = line[sep + 1..].trim();
+        if value.len() >= 2
+            && ((value.starts_with('"') && value.ends_with('"'))
+                || (value.starts_with('\'') && value.ends_with('\'')))
+        {
+            value = &value[1..value.len() - 1];
+        }
+        if key.is_empty() {
+            continue;
+        }
+
+        match (section, current.as_deref()) {
+            (Some(wanted), Some(current)) if wanted == current => {
+                env_vars.insert(key.to_string(), value.to_string());
+            }
+            (Some(_), _) => {}
+            (None, Some(current)) => {
+                env_vars.insert(format!("{}.{}", current, key), value.to_string());
+            }
+            (None, None) => {
+                env_vars.insert(key.to_string(), value.to_string());
+            }
+        }
+    }
+
+    EnvParseResult {
+        env_vars,
+        source: "ini".to_string(),
+        errors,
+        ..Default::default()
+    }
+}
+
+/// Read an .ini file from a directory, optionally limited to one section
+pub fn read_ini_file(dir_path: &str, filename: &str, section: Option<&str>) -> EnvParseResult {
+    if filename.is_empty() || filename.contains(['/', '\\']) || filename == ".." {
+        return EnvParseResult {
+            env_vars: HashMap::new(),
+            source: filename.to_string(),
+            errors: vec![format!("Invalid file name: {}", filename)],
+            ..Default::default()
+        };
+    }
+
+    match fs::read_to_string(Path::new(dir_path).join(filename)) {
+        Ok(content) => EnvParseResult {
+            source: filename.to_string(),
+            ..parse_ini_section(&content, section)
+        },
+        Err(e) => EnvParseResult {
+            env_vars: HashMap::new(),
+            source: filename.to_string(),
+            errors: vec![format!("Failed to read {}: {}", filename, e)],
+            ..Default::default()
+        },
+    }
+}
+
+/// Extract values from the first `kind: Secret` document of a Kubernetes manifest.
+/// `data` values are base64-decoded; `stringData` values are used as-is and win on conflict.
+pub fn parse_k8s_secret(content: &str) -> Result<HashMap<String, String>, String> {
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+    for document in serde_yaml::Deserializer::from_str(content) {
+        let doc = serde_yaml::Value::deserialize(document)
+            .map_err(|e| format!("Failed to parse YAML: {}", e))?;
+
+        if doc.get("kind").and_then(|k| k.as_str()) != Some("Secret") {
+            continue;
+        }
+
+        let mut result = HashMap::new();
+
+        if let Some(data) = doc.get("data").and_then(|d| d.as_mapping()) {
+            for (key, value) in data {
+                let (Some(key), Some(value)) = (key.as_str(), value.as_str()) else {
+                    continue;
+                };
+                let decoded = BASE64
+                    .decode(value.trim())
+                    .map_err(|e| format!("Invalid base64 in data.{}: {}", key, e))?;
+                let decoded = String::from_utf8(decoded)
+                    .map_err(|_| format!("data.{} is not valid UTF-8", key))?;
+                result.insert(key.to_string(), decoded);
+            }
+        }
+
+        if let Some(string_data) = doc.get("stringData").and_then(|d| d.as_mapping()) {
+            for (key, value) in string_data {
+                if let (Some(key), Some(value)) = (key.as_str(), value.as_str()) {
+                    result.insert(key.to_string(), value.to_string());
+                }
+            }
+        }
+
+        return Ok(result);
+    }
+
+    Err("No Secret document found".to_string())
+}
+
+/// Read a Kubernetes Secret manifest (YAML) as environment variables
+pub fn read_k8s_secret(file_path: &str) -> EnvParseResult {
+    let result = fs::read_to_string(file_path)
+        .map_err(|e| format!("Failed to read {}: {}", file_path, e))
+        .and_then(|content| parse_k8s_secret(&content));
+
+    secret_result("kubernetes", result)
+}
+
+/// Vault server used when VAULT_ADDR is not set (local agent listener)
+const DEFAULT_VAULT_ADDR: &str = "http://127.0.0.1:8200";
+/// Token sink written by Vault Agent's auto-auth
+const VAULT_AGENT_TOKEN_PATH: &str = "/run/vault-agent/token";
+
+/// Extract `data.data` from a Vault KV v2 read response
+pub fn parse_vault_kv_response(json: &str) -> Result<HashMap<String, String>, String> {
+    let response: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse Vault response: {}", e))?;
+
+    let data = response
+        .get("data")
+        .and_then(|d| d.get("data"))
+        .and_then(|d| d.as_object())
+        .ok_or_else(|| "Vault response has no data.data object".to_string())?;
+
+    Ok(data
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                serde_json::Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            (key.clone(), value)
+        })
+        .collect())
+}
+
+/// Get a Vault token from VAULT_TOKEN or the agent's token sink
+fn vault_token() -> Result<String, String> {
+    if let Ok(token) = std::env::var("VAULT_TOKEN") {
+        if !token.is_empty() {
+            return Ok(token);
+        }
+    }
+
+    fs::read_to_string(VAULT_AGENT_TOKEN_PATH)
+        .map(|token| token.trim().to_string())
+        .map_err(|_| "No Vault token: set VAULT_TOKEN or run Vault Agent with a token sink".to_string())
+}
+
+/// Fetch a KV v2 secret from Vault (blocking, 5s timeout)
+fn fetch_vault_kv(mount: &str, path: &str) -> Result<HashMap<String, String>, String> {
+    let token = vault_token()?;
+    let addr = std::env::var("VAULT_ADDR").unwrap_or_else(|_| DEFAULT_VAULT_ADDR.to_string());
+    let url = format!(
+        "{}/v1/{}/data/{}",
+        addr.trim_end_matches('/'),
+        mount.trim_matches('/'),
+        path.trim_matches('/')
+    );
+
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(5))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let response = client
+        .get(&url)
+        .header("X-Vault-Token", token)
+        .send()
+        .map_err(|e| format!("Vault request failed: {}", e))?;
+
+    let status = response.status();
+    let body = response
+        .text()
+        .map_err(|e| format!("Failed to read Vault response: {}", e))?;
+    if !status.is_success() {
+        return Err(format!("Vault returned {}: {}", status, body.trim()));
+    }
+
+    parse_vault_kv_response(&body)
+}
+
+/// Read a KV v2 secret from Vault
+pub fn read_vault_kv(mount: &str, path: &str) -> EnvParseResult {
+    secret_result("vault", fetch_vault_kv(mount, path))
+}
+
+/// Parse one page of `aws ssm get-parameters-by-path` output.
+/// Returns the parameters keyed by name (without `path_prefix`) and the next page token.
+pub fn parse_ssm_parameters(
+    json: &str,
+    path_prefix: &str,
+) -> Result<(HashMap<String, String>, Option<String>), String> {
+    let response: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse SSM response: {}", e))?;
+
+    let mut result = HashMap::new();
+    if let Some(parameters) = response.get("Parameters").and_then(|p| p.as_array()) {
+        for parameter in parameters {
+            let name = parameter.get("Name").and_then(|n| n.as_str()).unwrap_or("");
+            let key = name
+                .strip_prefix(path_prefix)
+                .unwrap_or(name)
+                .trim_start_matches('/');
+            if key.is_empty() {
+                continue;
+            }
+            let value = parameter.get("Value").and_then(|v| v.as_str()).unwrap_or("");
+            result.insert(key.to_string(), value.to_string());
+        }
+    }
+
+    let next_token = response
+        .get("NextToken")
+        .and_then(|t| t.as_str())
+        .map(|t| t.to_string());
+
+    Ok((result, next_token))
+}
+
+/// Fetch all parameters under a path from AWS SSM via the `aws` CLI
+fn fetch_ssm_path(path_prefix: &str) -> Result<HashMap<String, String>, String> {
+    let mut result = HashMap::new();
+    let mut next_token: Option<String> = None;
+
+    loop {
+        let mut args = vec![
+            "ssm",
+            "get-parameters-by-path",
+            "--path",
+            path_prefix,
+            "--with-decryption",
+            "--output",
+            "json",
+        ];
+        if let Some(ref token) = next_token {
+            args.push("--starting-token");
+            args.push(token);
+        }
+
+        let output = run_cli("aws", &args, Duration::from_secs(30))?;
+        let (page, token) = parse_ssm_parameters(&output, path_prefix)?;
+        result.extend(page);
+
+        match token {
+            Some(token) => next_token = Some(token),
+            None => break,
+        }
+    }
+
+    Ok(result)
+}
+
+/// Read parameters under a path from AWS SSM Parameter Store
+pub fn read_ssm_path(path_prefix: &str) -> EnvParseResult {
+    secret_result("aws-ssm", fetch_ssm_path(path_prefix))
+}
+
+/// How many `az` processes to run at once when fetching Key Vault secrets
+const AZURE_KEYVAULT_BATCH_SIZE: usize = 10;
+
+/// Extract the vault name from `https://{name}.vault.azure.net` (or a bare name)
+pub fn azure_vault_name(vault_url: &str) -> Result<String, String> {
+    let host = vault_url
+        .trim()
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .split('/')
+        .next()
+        .unwrap_or("");
+    let name = host.split('.').next().unwrap_or("");
+
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(format!("Invalid Key Vault URL: {}", vault_url));
+    }
+    Ok(name.to_string())
+}
+
+/// Key Vault secret names only allow dashes, so map `db-password` to `DB_PASSWORD`
+pub fn azure_secret_env_key(secret_name: &str) -> String {
+    secret_name.replace('-', "_").to_ascii_uppercase()
+}
+
+/// Fetch a single secret value via `az keyvault secret show`
+fn fetch_azure_secret(vault_name: &str, secret_name: &str) -> Result<String, String> {
+    run_cli(
+        "az",
+        &[
+            "keyvault", "secret", "show", "--vault-name", vault_name, "--name", secret_name,
+            "--query", "value", "--output", "tsv",
+        ],
+        SECRET_CLI_TIMEOUT,
+    )
+    .map(|output| output.trim_end_matches(['\r', '\n']).to_string())
+    .map_err(|e| {
+        if e.contains("az login") || e.contains("AADSTS") {
+            format!("{}: not logged in to Azure - run `az login` first", secret_name)
+        } else {
+            format!("{}: {}", secret_name, e)
+        }
+    })
+}
+
+/// Read secrets from Azure Key Vault via the `az` CLI.
+/// Failures are reported per secret; the others are still returned.
+pub fn read_azure_keyvault(vault_url: &str, secret_names: &[String]) -> EnvParseResult {
+    let vault_name = match azure_vault_name(vault_url) {
+        Ok(name) => name,
+        Err(e) => return secret_result("azure-keyvault", Err(e)),
+    };
+
+    let mut env_vars = HashMap::new();
+    let mut errors = Vec::new();
+
+    for batch in secret_names.chunks(AZURE_KEYVAULT_BATCH_SIZE) {
+        let vault_name = vault_name.as_str();
+        let results: Vec<(&String, Result<String, String>)> = thread::scope(|scope| {
+            let handles: Vec<_> = batch
+                .iter()
+                .map(|name| (name, scope.spawn(move || fetch_azure_secret(vault_name, name))))
+                .collect();
+            handles
+                .into_iter()
+                .map(|(name, handle)| {
+                    let result = handle
+                        .join()
+                        .unwrap_or_else(|_| Err(format!("{}: fetch thread panicked", name)));
+                    (name, result)
+                })
+                .collect()
+        });
+
+        for (name, result) in results {
+            match result {
+                Ok(value) => {
+                    env_vars.insert(azure_secret_env_key(name), value);
+                }
+                Err(e) => errors.push(e),
+            }
+        }
+    }
+
+    EnvParseResult {
+        env_vars,
+        source: "azure-keyvault".to_string(),
+        errors,
+        redacted: true,
+        ..Default::default()
+    }
+}
+
+/// Read the first line (the password, by convention) of a `pass` entry
+pub fn read_pass_entry(entry_path: &str) -> Result<String, String> {
+    if entry_path.is_empty() || entry_path.starts_with('-') {
+        return Err(format!("Invalid pass entry: {}", entry_path));
+    }
+
+    let output = run_cli("pass", &["show", entry_path], SECRET_CLI_TIMEOUT).map_err(|e| {
+        if e.contains("gpg-agent") || e.contains("S.gpg-agent") || e.contains("decryption failed") {
+            format!(
+                "{}: GPG could not decrypt the entry - make sure gpg-agent is running and unlocked",
+                entry_path
+            )
+        } else {
+            format!("{}: {}", entry_path, e)
+        }
+    })?;
+
+    Ok(output.lines().next().unwrap_or("").to_string())
+}
+
+/// Read `(env_key, pass_entry_path)` pairs from `pass`.
+/// Failures are reported per entry; the others are still returned.
+pub fn read_pass_env(entries: &[(&str, &str)]) -> EnvParseResult {
+    let mut env_vars = HashMap::new();
+    let mut errors = Vec::new();
+
+    for (key, entry_path) in entries {
+        if !is_valid_env_key(key) {
+            errors.push(format!("Invalid environment variable name: {}", key));
+            continue;
+        }
+        match read_pass_entry(entry_path) {
+            Ok(value) => {
+                env_vars.insert(key.to_string(), value);
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+
+    EnvParseResult {
+        env_vars,
+        source: "pass".to_string(),
+        errors,
+        redacted: true,
+        ..Default::default()
+    }
+}
+
+/// Turn a failed `doppler` invocation into a user-facing error
+fn doppler_error(project: &str, config: &str, error: String) -> String {
+    let lower = error.to_lowercase();
+    if lower.contains("not found in path") {
+        "Doppler CLI is not installed".to_string()
+    } else if lower.contains("doppler login") || lower.contains("unauthorized") || lower.contains("invalid auth token") {
+        "Not authenticated with Doppler - run `doppler login` first".to_string()
+    } else if lower.contains("could not find") || lower.contains("invalid project") || lower.contains("invalid config") {
+        format!("Doppler project '{}' or config '{}' not found", project, config)
+    } else {
+        error
+    }
+}
+
+/// Download secrets for a Doppler project/config via the `doppler` CLI
+pub fn read_doppler_secrets(project: &str, config: &str) -> EnvParseResult {
+    let result = run_cli(
+        "doppler",
+        &[
+            "secrets", "download", "--no-file", "--project", project, "--config", config,
+            "--format", "env-no-quotes",
+        ],
+        SECRET_CLI_TIMEOUT,
+    )
+    .and_then(|output| parse_env_file_with(&output, VarExpansion::Literal))
+    .map_err(|e| doppler_error(project, config, e));
+
+    secret_result("doppler", result)
+}
+
+/// Parse `netlify env:list --json` output. Accepts the API shape
+/// (`[{ "key": ..., "values": [{ "value": ... }] }]`, first value wins) and the
+/// flat `{ "KEY": "value" }` object printed by newer CLI versions.
+pub fn parse_netlify_env(json: &str) -> Result<HashMap<String, String>, String> {
+    let response: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse Netlify response: {}", e))?;
+
+    let mut result = HashMap::new();
+    match response {
+        serde_json::Value::Array(items) => {
+            for item in items {
+                let Some(key) = item.get("key").and_then(|k| k.as_str()) else {
+                    continue;
+                };
+                let value = item
+                    .get("values")
+                    .and_then(|v| v.as_array())
+                    .and_then(|values| values.first())
+                    .and_then(|v| v.get("value"))
+                    .and_then(|v| v.as_str());
+                if let Some(value) = value {
+                    result.insert(key.to_string(), value.to_string());
+                }
+            }
+        }
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                if let Some(value) = value.as_str() {
+                    result.insert(key, value.to_string());
+                }
+            }
+        }
+        _ => return Err("Unexpected Netlify response format".to_string()),
+    }
+
+    Ok(result)
+}
+
+/// Read site environment variables via the `netlify` CLI
+pub fn read_netlify_env(site_id: Option<&str>) -> EnvParseResult {
+    let mut args = vec!["env:list", "--json"];
+    if let Some(site_id) = site_id.filter(|s| !s.is_empty()) {
+        args.push("--site");
+        args.push(site_id);
+    }
+
+    let result = run_cli("netlify", &args, Duration::from_secs(20))
+        .map_err(|e| {
+            let lower = e.to_lowercase();
+            if lower.contains("not found in path") {
+                "Netlify CLI is not installed".to_string()
+            } else if lower.contains("netlify login") || lower.contains("not logged in") {
+                "Not logged in to Netlify - run `netlify login` first".to_string()
+            } else {
+                e
+            }
+        })
+        .and_then(|output| parse_netlify_env(&output));
+
+    secret_result("netlify", result)
+}
+
+/// Build a redacted result for a secrets source
+fn secret_result(source: &str, result: Result<HashMap<String, String>, String>) -> EnvParseResult {
+    match result {
+        Ok(env_vars) => EnvParseResult {
+            env_vars,
+            source: source.to_string(),
+            errors: vec![],
+            redacted: true,
+            ..Default::default()
+        },
+        Err(e) => EnvParseResult {
+            env_vars: HashMap::new(),
+            source: source.to_string(),
+            errors: vec![e],
+            redacted: true,
+            ..Default::default()
+        },
+    }
+}
+
+/// Extract text (type 0) and hidden (type 1) custom fields from `bw get item` JSON
+pub fn parse_bitwarden_fields(json: &str) -> Result<HashMap<String, String>, String> {
+    let item: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse Bitwarden item: {}", e))?;
+
+    let mut result = HashMap::new();
+    let fields = match item.get("fields").and_then(|f| f.as_array()) {
+        Some(fields) => fields,
+        None => return Ok(result),
+    };
+
+    for field in fields {
+        let field_type = field.get("type").and_then(|t| t.as_u64());
+        if !matches!(field_type, Some(0) | Some(1)) {
+            continue;
+        }
+
+        let name = field.get("name").and_then(|n| n.as_str()).unwrap_or("").trim();
+        if name.is_empty() {
+            continue;
+        }
+
+        let value = field.get("value").and_then(|v| v.as_str()).unwrap_or("");
+        result.insert(name.to_string(), value.to_string());
+    }
+
+    Ok(result)
+}
+
+/// Read custom fields of a Bitwarden item via the `bw` CLI
+/// Requires an unlocked vault (BW_SESSION set in the environment)
+pub fn read_bitwarden_item(item_id: &str) -> EnvParseResult {
+    let result = if std::env::var("BW_SESSION").map(|s| s.is_empty()).unwrap_or(true) {
+        Err("BW_SESSION is not set - run `bw unlock` first".to_string())
+    } else {
+        run_cli("bw", &["get", "item", item_id], SECRET_CLI_TIMEOUT)
+            .and_then(|output| parse_bitwarden_fields(&output))
+    };
+
+    secret_result("bitwarden", result)
+}
+
+/// Check if the `chamber` CLI is available
+pub fn has_chamber_cli() -> bool {
+    platform::find_in_path("chamber").is_some()
+}
+
+/// Read secrets for a chamber service from AWS SSM Parameter Store.
+/// A non-empty `env` selects the `{env}/{service}` service path.
+pub fn read_chamber_service(service: &str, env: &str) -> EnvParseResult {
+    let service_path = if env.is_empty() {
+        service.to_string()
+    } else {
+        format!("{}/{}", env, service)
+    };
+
+    let result = run_cli(
+        "chamber",
+        &["export", &service_path, "--format", "dotenv"],
+        SECRET_CLI_TIMEOUT,
+    )
+    .and_then(|output| parse_env_file_with(&output, VarExpansion::Literal));
+
+    secret_result("chamber", result)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -182,4 +1215,340 @@ WITH_SPACES = spaced value
         assert_eq!(result.get("KEY3"), Some(&"single quoted".to_string()));
         assert_eq!(result.get("EMPTY"), Some(&"".to_string()));
     }
+
+    #[test]
+    fn test_parse_bitwarden_fields() {
+        let json = r#"{
+            "id": "abc",
+            "name": "My API",
+            "fields": [
+                {"name": "API_URL", "value": "https://example.com", "type": 0},
+                {"name": "API_KEY", "value": "s3cret", "type": 1},
+                {"name": "ENABLED", "value": "true", "type": 2}
+            ]
+        }"#;
+        let result = parse_bitwarden_fields(json).unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result.get("API_URL"), Some(&"https://example.com".to_string()));
+        assert_eq!(result.get("API_KEY"), Some(&"s3cret".to_string()));
+        assert!(parse_bitwarden_fields(r#"{"id": "abc"}"#).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_procfile() {
+        let content = r#"
+# Processes
+web: bundle exec rails server -b 0.0.0.0:$PORT
+worker:bundle exec sidekiq
+clock: node -e "console.log('a:b')"
+release:
+: orphan command
+"#;
+        let result = parse_procfile_content(content);
+        assert_eq!(result.len(), 3);
+        assert_eq!(
+            result.get("web"),
+            Some(&"bundle exec rails server -b 0.0.0.0:$PORT".to_string())
+        );
+        assert_eq!(result.get("worker"), Some(&"bundle exec sidekiq".to_string()));
+        assert_eq!(
+            result.get("clock"),
+            Some(&"node -e \"console.log('a:b')\"".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_vault_kv_response() {
+        let json = r#"{
+            "data": {
+                "data": {"DB_PASSWORD": "hunter2", "PORT": 5432},
+                "metadata": {"version": 3}
+            }
+        }"#;
+        let result = parse_vault_kv_response(json).unwrap();
+        assert_eq!(result.get("DB_PASSWORD"), Some(&"hunter2".to_string()));
+        assert_eq!(result.get("PORT"), Some(&"5432".to_string()));
+        assert!(parse_vault_kv_response(r#"{"errors": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_k8s_secret() {
+        let content = r#"
+apiVersion: v1
+kind: ConfigMap
+metadata:
+  name: settings
+data:
+  IGNORED: "value"
+---
+apiVersion: v1
+kind: Secret
+metadata:
+  name: db
+type: Opaque
+data:
+  DB_USER: YWRtaW4=
+  DB_PASSWORD: aHVudGVyMg==
+stringData:
+  DB_HOST: localhost
+"#;
+        let result = parse_k8s_secret(content).unwrap();
+        assert_eq!(result.len(), 3);
+        assert_eq!(result.get("DB_USER"), Some(&"admin".to_string()));
+        assert_eq!(result.get("DB_PASSWORD"), Some(&"hunter2".to_string()));
+        assert_eq!(result.get("DB_HOST"), Some(&"localhost".to_string()));
+        assert!(parse_k8s_secret("kind: ConfigMap\n").is_err());
+    }
+
+    #[test]
+    fn test_expand_paths() {
+        let mut vars = HashMap::new();
+        vars.insert("BASE_DIR".to_string(), "/srv/app".to_string());
+        vars.insert("DATA_DIR".to_string(), "${BASE_DIR}/data".to_string());
+        vars.insert("LOG_FILE".to_string(), "./logs/app.log".to_string());
+        vars.insert("GREETING".to_string(), "$BASE_DIR".to_string());
+        vars.insert("CACHE_DIR".to_string(), "$MOONTERM_UNSET_VAR/cache".to_string());
+
+        expand_paths(&mut vars, None, Some(Path::new("/project")));
+        assert_eq!(vars["DATA_DIR"], "/srv/app/data");
+        assert_eq!(vars["LOG_FILE"], "/project/logs/app.log");
+        assert_eq!(vars["GREETING"], "$BASE_DIR");
+        assert_eq!(vars["CACHE_DIR"], "$MOONTERM_UNSET_VAR/cache");
+
+        let keys = vec!["GREETING".to_string()];
+        expand_paths(&mut vars, Some(&keys), None);
+        assert_eq!(vars["GREETING"], "/srv/app");
+    }
+
+    #[test]
+    fn test_resolve_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".env"), "A=base\nB=base\nC=base\n").unwrap();
+        fs::write(dir.path().join(".env.dev"), "B=dev\n").unwrap();
+        fs::write(dir.path().join(".envrc"), "export C=envrc\n").unwrap();
+        let dir_path = dir.path().to_string_lossy().to_string();
+
+        let env = resolve_environment(&dir_path, Some("dev"), VarExpansion::Lenient).unwrap();
+        assert_eq!(env["A"], "base");
+        assert_eq!(env["B"], "dev");
+        assert_eq!(env["C"], "envrc");
+
+        let env = resolve_environment(&dir_path, None, VarExpansion::Lenient).unwrap();
+        assert_eq!(env["B"], "base");
+
+        // Strict expansion turns an undefined reference into an error
+        fs::write(dir.path().join(".env.dev"), "B=${MOONTERM_TEST_UNDEFINED}\n").unwrap();
+        assert!(resolve_environment(&dir_path, Some("dev"), VarExpansion::Lenient).is_ok());
+        let err = resolve_environment(&dir_path, Some("dev"), VarExpansion::Strict).unwrap_err();
+        assert!(err.contains("MOONTERM_TEST_UNDEFINED"), "{}", err);
+    }
+
+    #[test]
+    fn test_write_envrc_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir_path = dir.path().to_string_lossy().to_string();
+
+        let mut vars = HashMap::new();
+        vars.insert("PLAIN".to_string(), "value".to_string());
+        vars.insert("QUOTED".to_string(), r#"say "hi""#.to_string());
+        vars.insert("BACKSLASH".to_string(), r"C:\tools\bin".to_string());
+        vars.insert("DOLLAR".to_string(), "$HOME and `cmd`".to_string());
+        vars.insert("EMPTY".to_string(), "".to_string());
+
+        write_envrc_file(&dir_path, &vars).unwrap();
+        let content = fs::read_to_string(dir.path().join(".envrc")).unwrap();
+        assert!(content.starts_with("# Generated by moonterm\n"));
+        assert!(content.contains(r#"export QUOTED="say \"hi\"""#));
+
+        let result = read_envrc_file(&dir_path);
+        assert!(result.errors.is_empty());
+        assert_eq!(result.env_vars, vars);
+
+        let mut invalid = HashMap::new();
+        invalid.insert("BAD KEY".to_string(), "x".to_string());
+        assert!(write_envrc_file(&dir_path, &invalid).is_err());
+    }
+
+    #[test]
+    fn test_merge_strategies() {
+        let map = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+        };
+        let conflicting = vec![map(&[("A", "1"), ("B", "1")]), map(&[("B", "2"), ("C", "2")])];
+        let compatible = vec![map(&[("A", "1"), ("B", "1")]), map(&[("B", "1"), ("C", "2")])];
+
+        let merged = merge(&conflicting, MergeStrategy::LastWins).unwrap();
+        assert_eq!(merged, map(&[("A", "1"), ("B", "2"), ("C", "2")]));
+
+        let merged = merge(&conflicting, MergeStrategy::FirstWins).unwrap();
+        assert_eq!(merged, map(&[("A", "1"), ("B", "1"), ("C", "2")]));
+
+        assert!(merge(&conflicting, MergeStrategy::ErrorOnConflict).is_err());
+        let merged = merge(&compatible, MergeStrategy::ErrorOnConflict).unwrap();
+        assert_eq!(merged, map(&[("A", "1"), ("B", "1"), ("C", "2")]));
+
+        for strategy in [MergeStrategy::LastWins, MergeStrategy::FirstWins] {
+            assert_eq!(merge(&compatible, strategy).unwrap(), map(&[("A", "1"), ("B", "1"), ("C", "2")]));
+        }
+    }
+
+    #[test]
+    fn test_parse_ssm_parameters() {
+        let json = r#"{
+            "Parameters": [
+                {"Name": "/myapp/prod/DB_HOST", "Type": "String", "Value": "db.internal"},
+                {"Name": "/myapp/prod/DB_PASSWORD", "Type": "SecureString", "Value": "hunter2"}
+            ],
+            "NextToken": "abc123"
+        }"#;
+        let (result, token) = parse_ssm_parameters(json, "/myapp/prod").unwrap();
+        assert_eq!(result.get("DB_HOST"), Some(&"db.internal".to_string()));
+        assert_eq!(result.get("DB_PASSWORD"), Some(&"hunter2".to_string()));
+        assert_eq!(token, Some("abc123".to_string()));
+
+        let (result, token) = parse_ssm_parameters(r#"{"Parameters": []}"#, "/x").unwrap();
+        assert!(result.is_empty());
+        assert!(token.is_none());
+    }
+
+    #[test]
+    fn test_azure_vault_name() {
+        assert_eq!(azure_vault_name("https://my-vault.vault.azure.net/").unwrap(), "my-vault");
+        assert_eq!(azure_vault_name("my-vault").unwrap(), "my-vault");
+        assert!(azure_vault_name("https://").is_err());
+        assert!(azure_vault_name("bad;name").is_err());
+        assert_eq!(azure_secret_env_key("db-password"), "DB_PASSWORD");
+    }
+
+    #[test]
+    fn test_doppler_error() {
+        assert_eq!(
+            doppler_error("app", "dev", "doppler not found in PATH".to_string()),
+            "Doppler CLI is not installed"
+        );
+        assert!(doppler_error("app", "dev", "doppler failed: Unauthorized".to_string())
+            .contains("doppler login"));
+        assert_eq!(
+            doppler_error("app", "dev", "doppler failed: Doppler Error: Could not find requested config".to_string()),
+            "Doppler project 'app' or config 'dev' not found"
+        );
+        assert_eq!(doppler_error("app", "dev", "boom".to_string()), "boom");
+    }
+
+    #[test]
+    fn test_parse_ini_all_sections() {
+        let content = r#"
+; global settings
+debug = true
+
+[database]
+host = localhost
+port: 5432
+
+[cache]
+host = "redis.local"
+# comment
+"#;
+        let result = parse_ini_section(content, None);
+        assert!(result.errors.is_empty());
+        assert_eq!(result.env_vars.get("debug"), Some(&"true".to_string()));
+        assert_eq!(result.env_vars.get("database.host"), Some(&"localhost".to_string()));
+        assert_eq!(result.env_vars.get("database.port"), Some(&"5432".to_string()));
+        assert_eq!(result.env_vars.get("cache.host"), Some(&"redis.local".to_string()));
+        assert_eq!(result.env_vars.len(), 4);
+    }
+
+    #[test]
+    fn test_parse_ini_single_section() {
+        let content = "[database]\nhost = db\n[cache]\nhost = redis\nbroken line\n";
+        let result = parse_ini_section(content, Some("cache"));
+        assert_eq!(result.env_vars.len(), 1);
+        assert_eq!(result.env_vars.get("host"), Some(&"redis".to_string()));
+        assert_eq!(result.errors, vec!["Line 5: expected key = value".to_string()]);
+
+        assert!(parse_ini_section(content, Some("missing")).env_vars.is_empty());
+    }
+
+    #[test]
+    fn test_parse_netlify_env() {
+        let json = r#"[
+            {"key": "API_URL", "values": [{"value": "https://api.dev", "context": "dev"}, {"value": "https://api"}]},
+            {"key": "EMPTY", "values": []}
+        ]"#;
+        let result = parse_netlify_env(json).unwrap();
+        assert_eq!(result.get("API_URL"), Some(&"https://api.dev".to_string()));
+        assert!(!result.contains_key("EMPTY"));
+
+        let result = parse_netlify_env(r#"{"TOKEN": "abc"}"#).unwrap();
+        assert_eq!(result.get("TOKEN"), Some(&"abc".to_string()));
+
+        assert!(parse_netlify_env("42").is_err());
+    }
+
+    #[test]
+    fn test_parse_env_file_expansion() {
+        std::env::set_var("MOONTERM_TEST_EXPAND_HOME", "/home/moon");
+        let content = r#"
+BASE=$MOONTERM_TEST_EXPAND_HOME/app
+BIN=${BASE}/bin
+PATHS="${BIN}:$BASE/lib"
+PRICE=costs \$5 or $5
+LITERAL='${BASE}'
+MISSING=a${MOONTERM_TEST_UNDEFINED}b
+UNTERMINATED=${BASE
+"#;
+        let result = parse_env_file(content);
+        assert_eq!(result["BASE"], "/home/moon/app");
+        // Nested: BIN was already expanded when PATHS referenced it
+        assert_eq!(result["BIN"], "/home/moon/app/bin");
+        assert_eq!(result["PATHS"], "/home/moon/app/bin:/home/moon/app/lib");
+        assert_eq!(result["PRICE"], "costs $5 or $5");
+        assert_eq!(result["LITERAL"], "${BASE}");
+        assert_eq!(result["MISSING"], "ab");
+        assert_eq!(result["UNTERMINATED"], "${BASE");
+
+        let strict = parse_env_file_with(content, VarExpansion::Strict).unwrap_err();
+        assert!(strict.contains("MOONTERM_TEST_UNDEFINED"), "{}", strict);
+
+        let literal = parse_env_file_with(content, VarExpansion::Literal).unwrap();
+        assert_eq!(literal["BIN"], "${BASE}/bin");
+    }
+
+    #[test]
+    fn test_read_env_layered() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, content: &str| std::fs::write(dir.path().join(name), content).unwrap();
+        write(".env", "A=env\nB=env\nC=env\nD=env\n");
+        write(".env.local", "B=local\n");
+        write(".env.test", "C=test\nFROM_BASE=${A}-${B}\n");
+        write(".env.test.local", "D=test-local\n");
+        write(".env.other", "A=other\n");
+        let dir_path = dir.path().to_str().unwrap();
+
+        let result = read_env_layered(dir_path, Some("test".to_string()), VarExpansion::Lenient);
+        assert_eq!(result.files, [".env", ".env.local", ".env.test", ".env.test.local"]);
+        assert!(result.errors.is_empty());
+        assert_eq!(result.env_vars["A"], "env");
+        assert_eq!(result.env_vars["B"], "local");
+        assert_eq!(result.env_vars["C"], "test");
+        assert_eq!(result.env_vars["D"], "test-local");
+        assert_eq!(result.env_vars["FROM_BASE"], "env-local");
+
+        // Without a mode only .env and .env.local apply
+        let result = read_env_layered(dir_path, None, VarExpansion::Lenient);
+        assert_eq!(result.files, [".env", ".env.local"]);
+        assert_eq!(result.env_vars["C"], "env");
+
+        let result = read_env_layered(dir_path, Some("../x".to_string()), VarExpansion::Lenient);
+        assert_eq!(result.files, [".env", ".env.local"]);
+        assert_eq!(result.errors.len(), 1);
+
+        // Strict: the file with the undefined reference is reported and skipped
+        write(".env.test.local", "D=${MOONTERM_TEST_UNDEFINED}\n");
+        let result = read_env_layered(dir_path, Some("test".to_string()), VarExpansion::Strict);
+        assert_eq!(result.files, [".env", ".env.local", ".env.test"]);
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].starts_with(".env.test.local"), "{:?}", result.errors);
+        assert_eq!(result.env_vars["D"], "env");
+    }
 }
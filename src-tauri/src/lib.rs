@@ -1,6 +1,8 @@
+mod biometric;
 mod commands;
 mod crypto;
 mod env;
+mod platform;
 mod pty;
 mod workspace;
 
@@ -19,6 +21,7 @@ pub fn run() {
             let app_handle = app.handle().clone();
             let pty_manager = Arc::new(PtyManager::new(app_handle.clone()));
             app.manage(pty_manager);
+            app.manage(workspace::AutoBackup::default());
 
             // Create app menu (Moonterm) with About and Quit
             let about = PredefinedMenuItem::about(app, Some("About Moonterm"), Some(AboutMetadata {
@@ -80,28 +83,128 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             // PTY commands
             commands::pty_create,
+            commands::pty_create_with_env_file,
+            commands::pty_create_monitoring,
+            commands::pty_create_with_startup_timeout,
+            commands::pty_set_read_only,
+            commands::pty_get_instance_limit,
+            commands::pty_set_max_instances,
+            commands::pty_reconnect,
+            commands::pty_start_logging,
+            commands::pty_stop_logging,
+            commands::pty_clear_scrollback,
+            commands::pty_get_scrollback,
+            commands::pty_get_history_line,
+            commands::pty_get_history_range,
+            commands::pty_get_last_command,
+            commands::pty_create_tmux,
+            commands::pty_create_wsl,
+            commands::pty_tail_file,
             commands::pty_write,
+            commands::pty_write_bytes,
+            commands::pty_set_broadcast_group,
+            commands::pty_list_broadcast_groups,
+            commands::pty_write_chunked,
+            commands::pty_paste,
+            commands::pty_write_with_delay,
+            commands::pty_cancel_write_delay,
             commands::pty_resize,
+            commands::pty_resize_all,
+            commands::pty_resize_all_with_pixel,
             commands::pty_kill,
+            commands::pty_signal,
+            commands::pty_wait,
             commands::pty_restart,
+            commands::pty_fork,
             commands::pty_get_cwd,
+            commands::pty_list,
+            commands::pty_get_pid,
+            commands::pty_get_title,
+            commands::pty_get_shell_version,
+            commands::get_shell_version,
             // Dialog commands
             commands::dialog_select_folder,
             // Workspace commands
             commands::workspace_save,
+            commands::workspace_save_with_comment,
+            commands::workspace_update_comment,
             commands::workspace_load,
+            commands::workspace_load_versioned,
+            commands::workspace_list_profiles,
+            commands::workspace_export,
+            commands::workspace_import,
+            commands::workspace_save_encrypted,
+            commands::workspace_load_encrypted,
+            commands::workspace_save_plaintext,
+            commands::workspace_get_history_snapshot,
+            commands::workspace_get_settings,
+            commands::workspace_set_settings,
+            commands::workspace_list_history,
+            commands::workspace_restore_history,
+            commands::workspace_rotate_encryption_key,
+            commands::workspace_optimize,
+            commands::workspace_clone,
+            commands::workspace_list_recent,
+            commands::workspace_touch,
+            commands::workspace_get_pty_cwd_map,
+            commands::workspace_deduplicate_tabs,
+            commands::workspace_count_duplicate_tabs,
+            commands::workspace_compact_json,
+            commands::workspace_prettify_json,
+            commands::workspace_start_auto_backup,
+            commands::workspace_stop_auto_backup,
+            commands::workspace_export_csv,
+            commands::workspace_export_tsv,
             commands::get_config_path,
+            // Platform commands
+            commands::get_disk_space,
+            commands::is_wsl,
+            commands::get_homebrew_packages,
             // Crypto commands (password-only)
             commands::crypto_encrypt,
             commands::crypto_decrypt,
+            commands::crypto_reencrypt,
+            commands::crypto_encrypt_file,
+            commands::crypto_decrypt_file,
             commands::crypto_get_hint,
+            commands::crypto_generate_passphrase,
+            commands::crypto_estimate_strength,
+            commands::crypto_envelope_overhead,
+            commands::crypto_derive_file_key,
+            commands::crypto_calibrate_kdf,
+            commands::crypto_store_biometric,
+            commands::crypto_unlock_biometric,
+            commands::crypto_forget_biometric,
             // Environment variable commands
             commands::env_read_dotenv,
+            commands::env_read_layered,
             commands::env_read_envrc,
+            commands::env_write_envrc,
+            commands::env_read_bitwarden,
+            commands::env_read_chamber,
+            commands::env_has_chamber_cli,
+            commands::env_read_procfile,
+            commands::env_read_ini,
+            commands::env_expand_paths,
+            commands::env_merge,
+            commands::env_read_vault_kv,
+            commands::env_read_k8s_secret,
+            commands::env_read_ssm_path,
+            commands::env_read_azure_keyvault,
+            commands::env_read_pass,
+            commands::env_read_doppler,
+            commands::env_read_netlify,
             commands::env_has_dotenv,
             commands::env_has_envrc,
             commands::env_get_files_info,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                // Don't leave shells (and whatever they started) orphaned
+                let count = app.state::<Arc<PtyManager>>().kill_all();
+                println!("Cleaned up {} PTY instance(s) on exit", count);
+            }
+        });
 }
@@ -0,0 +1,273 @@
+//! Platform-specific helpers
+//!
+//! Thin wrappers around OS APIs that have no portable std equivalent.
+
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Free and total space of the filesystem containing a path
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DiskSpace {
+    /// Bytes available to the current (unprivileged) user
+    pub available_bytes: u64,
+    pub total_bytes: u64,
+}
+
+/// Check if we're running inside Windows Subsystem for Linux
+#[cfg(target_os = "linux")]
+pub fn is_wsl() -> bool {
+    if std::env::var_os("WSL_DISTRO_NAME").is_some() {
+        return true;
+    }
+
+    // WSL kernels identify themselves as e.g. "...-microsoft-standard-WSL2"
+    std::fs::read_to_string("/proc/version")
+        .map(|version| {
+            let version = version.to_lowercase();
+            version.contains("microsoft") || version.contains("wsl")
+        })
+        .unwrap_or(false)
+}
+
+/// Check if we're running inside Windows Subsystem for Linux
+#[cfg(not(target_os = "linux"))]
+pub fn is_wsl() -> bool {
+    false
+}
+
+/// Get disk space for the filesystem containing `path` (statvfs)
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)]
+pub fn get_disk_space_bytes(path: &str) -> Result<DiskSpace, String> {
+    use std::ffi::CString;
+
+    let c_path = CString::new(path).map_err(|e| format!("Invalid path: {}", e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+
+    // SAFETY: c_path is a valid NUL-terminated string and stat is a valid out pointer
+    let ret = unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) };
+    if ret != 0 {
+        return Err(format!(
+            "Failed to get disk space for {}: {}",
+            path,
+            std::io::Error::last_os_error()
+        ));
+    }
+
+    // Field widths differ between platforms (u32 on macOS, u64 on Linux)
+    let fragment_size = stat.f_frsize as u64;
+    Ok(DiskSpace {
+        available_bytes: stat.f_bavail as u64 * fragment_size,
+        total_bytes: stat.f_blocks as u64 * fragment_size,
+    })
+}
+
+/// Get disk space for the volume containing `path` (GetDiskFreeSpaceExW)
+#[cfg(windows)]
+pub fn get_disk_space_bytes(path: &str) -> Result<DiskSpace, String> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = std::ffi::OsStr::new(path)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    let mut available: u64 = 0;
+    let mut total: u64 = 0;
+    let mut total_free: u64 = 0;
+
+    // SAFETY: wide is NUL-terminated and all out pointers are valid
+    let ok = unsafe { GetDiskFreeSpaceExW(wide.as_ptr(), &mut available, &mut total, &mut total_free) };
+    if ok == 0 {
+        return Err(format!(
+            "Failed to get disk space for {}: {}",
+            path,
+            std::io::Error::last_os_error()
+        ));
+    }
+
+    Ok(DiskSpace {
+        available_bytes: available,
+        total_bytes: total,
+    })
+}
+
+/// Look up an executable in PATH (also tries `.exe` on Windows)
+pub fn find_in_path(program: &str) -> Option<PathBuf> {
+    let path_var = std::env::var_os("PATH")?;
+    std::env::split_paths(&path_var).find_map(|dir| {
+        let candidate = dir.join(program);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        #[cfg(windows)]
+        {
+            let exe = dir.join(format!("{}.exe", program));
+            if exe.is_file() {
+                return Some(exe);
+            }
+        }
+        None
+    })
+}
+
+/// Write a file atomically: write a temp file in the same directory, then rename it
+/// over the target. On failure the previous file is left untouched.
+pub fn write_file_atomic(path: &Path, contents: &[u8]) -> Result<(), String> {
+    write_file_atomic_with(path, |file| {
+        use std::io::Write;
+
+        file.write_all(contents)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    })
+}
+
+/// Like `write_file_atomic`, but `write` streams the contents into the temp file.
+/// If it fails, the temp file is removed and the target is left untouched.
+pub fn write_file_atomic_with(
+    path: &Path,
+    write: impl FnOnce(&mut std::fs::File) -> Result<(), String>,
+) -> Result<(), String> {
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| format!("Invalid file path: {}", path.display()))?;
+    // Unique per write, so overlapping writes to the same target never share a temp file
+    static TMP_COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let tmp_path = dir.join(format!(
+        ".{}.tmp-{}-{}",
+        file_name,
+        std::process::id(),
+        TMP_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+    ));
+    let io_error = |e: std::io::Error| format!("Failed to write {}: {}", path.display(), e);
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&tmp_path)
+        .map_err(io_error)?;
+    let result = write(&mut file).and_then(|()| {
+        file.sync_all().map_err(io_error)?;
+        drop(file);
+        std::fs::rename(&tmp_path, path).map_err(io_error)
+    });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Run an external CLI and return its stdout, killing it after `timeout`
+pub fn run_cli(program: &str, args: &[&str], timeout: Duration) -> Result<String, String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                format!("{} not found in PATH", program)
+            } else {
+                format!("Failed to run {}: {}", program, e)
+            }
+        })?;
+
+    // Drain pipes on separate threads so a chatty child can't block on a full pipe
+    let mut stdout = child.stdout.take().ok_or("Failed to take stdout")?;
+    let mut stderr = child.stderr.take().ok_or("Failed to take stderr")?;
+    let stdout_thread = thread::spawn(move || {
+        let mut buf = String::new();
+        let _ = stdout.read_to_string(&mut buf);
+        buf
+    });
+    let stderr_thread = thread::spawn(move || {
+        let mut buf = String::new();
+        let _ = stderr.read_to_string(&mut buf);
+        buf
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("{} timed out after {}s", program, timeout.as_secs()));
+            }
+            Ok(None) => thread::sleep(Duration::from_millis(50)),
+            Err(e) => return Err(format!("Failed to wait for {}: {}", program, e)),
+        }
+    };
+
+    let stdout = stdout_thread.join().unwrap_or_default();
+    let stderr = stderr_thread.join().unwrap_or_default();
+
+    if !status.success() {
+        return Err(format!("{} failed: {}", program, stderr.trim()));
+    }
+
+    Ok(stdout)
+}
+
+/// Installed Homebrew formulas and casks
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HomebrewPackages {
+    pub formulas: Vec<String>,
+    pub casks: Vec<String>,
+}
+
+/// Locate `brew`; GUI apps on macOS often don't inherit the shell's PATH
+#[cfg(target_os = "macos")]
+fn find_brew() -> Option<PathBuf> {
+    find_in_path("brew").or_else(|| {
+        ["/opt/homebrew/bin/brew", "/usr/local/bin/brew"]
+            .iter()
+            .map(PathBuf::from)
+            .find(|p| p.is_file())
+    })
+}
+
+/// List installed Homebrew formulas and casks (cached after the first success)
+#[cfg(target_os = "macos")]
+pub fn get_homebrew_packages() -> Result<HomebrewPackages, String> {
+    static PACKAGES: std::sync::OnceLock<HomebrewPackages> = std::sync::OnceLock::new();
+    if let Some(packages) = PACKAGES.get() {
+        return Ok(packages.clone());
+    }
+
+    let brew = find_brew().ok_or_else(|| "Homebrew is not installed".to_string())?;
+    let brew = brew.to_string_lossy();
+    let timeout = Duration::from_secs(10);
+    let list = |kind: &str| -> Result<Vec<String>, String> {
+        let output = run_cli(&brew, &["list", kind, "-1"], timeout)?;
+        Ok(output
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(String::from)
+            .collect())
+    };
+
+    let packages = HomebrewPackages {
+        formulas: list("--formula")?,
+        casks: list("--cask")?,
+    };
+    Ok(PACKAGES.get_or_init(|| packages).clone())
+}
+
+/// Homebrew listing is only supported on macOS
+#[cfg(not(target_os = "macos"))]
+pub fn get_homebrew_packages() -> Result<HomebrewPackages, String> {
+    Err("Homebrew packages are only available on macOS".to_string())
+}
@@ -1,9 +1,13 @@
+use crate::platform;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use parking_lot::Mutex;
 use portable_pty::{native_pty_system, CommandBuilder, MasterPty, PtySize};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::io::{BufReader, Read, Write};
+use std::ops::ControlFlow;
 use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
 use tauri::{AppHandle, Emitter};
@@ -58,6 +62,24 @@ fn find_utf8_boundary(bytes: &[u8]) -> usize {
     end
 }
 
+/// Append `data` to `pending` and decode up to the last complete UTF-8 sequence.
+/// Trailing incomplete bytes stay in `pending` and are completed by the next read.
+fn decode_utf8_chunk(pending: &mut Vec<u8>, data: &[u8]) -> String {
+    pending.extend_from_slice(data);
+    let valid_len = find_utf8_boundary(pending);
+    let text = String::from_utf8_lossy(&pending[..valid_len]).to_string();
+    pending.drain(..valid_len);
+    text
+}
+
+/// Decode whatever `decode_utf8_chunk` held back once the stream has ended;
+/// an incomplete sequence becomes U+FFFD rather than being dropped
+fn flush_utf8_pending(pending: &mut Vec<u8>) -> String {
+    let text = String::from_utf8_lossy(pending).to_string();
+    pending.clear();
+    text
+}
+
 /// Find the last complete ANSI escape sequence boundary.
 /// ANSI sequences start with ESC (0x1B) and can be:
 /// - CSI: ESC [ ... <final byte 0x40-0x7E>
@@ -155,537 +177,3600 @@ fn find_ansi_boundary(bytes: &[u8]) -> usize {
     len
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CreatePtyOptions {
     pub id: String,
     pub cwd: String,
     /// Custom environment variables to merge (workspace + .env + .envrc)
     #[serde(default)]
     pub custom_env: Option<HashMap<String, String>>,
+    /// Some(true) forces a login shell, Some(false) an interactive non-login
+    /// shell, None keeps the platform default
+    #[serde(default)]
+    pub login_shell: Option<bool>,
+    /// Reject all writes (log viewers, monitoring dashboards)
+    #[serde(default)]
+    pub read_only: bool,
+    /// Kill the session if the shell prints nothing within this many ms
+    #[serde(default)]
+    pub startup_timeout_ms: Option<u64>,
+    /// Maximum scrollback lines kept in memory (default 10,000)
+    #[serde(default)]
+    pub scrollback_lines: Option<usize>,
+    /// Maximum raw scrollback bytes kept for `get_scrollback` (default 1 MiB)
+    #[serde(default)]
+    pub scrollback_bytes: Option<usize>,
+    /// Program to run instead of the platform default shell (path or name on PATH)
+    #[serde(default)]
+    pub shell: Option<String>,
+    /// Arguments for the shell (defaults to the platform default shell's arguments)
+    #[serde(default)]
+    pub args: Option<Vec<String>>,
+    /// Variables applied last, over everything else; `ENV_UNSET` removes a variable
+    #[serde(default)]
+    pub env: Option<HashMap<String, String>>,
+    /// Emit exact bytes as base64 via `pty:output-bytes` instead of lossy `pty:output` text
+    #[serde(default)]
+    pub binary: bool,
+    /// Initial size; the frontend's first resize() still wins
+    #[serde(default)]
+    pub cols: Option<u16>,
+    #[serde(default)]
+    pub rows: Option<u16>,
+    /// How long to coalesce output before emitting (default 8ms, 0 disables)
+    #[serde(default)]
+    pub output_flush_ms: Option<u64>,
+    /// Emit early once this many bytes are buffered (default 64 KiB)
+    #[serde(default)]
+    pub output_max_batch_bytes: Option<usize>,
+    /// Quiet period before a resize is applied (default 50ms, 0 applies immediately)
+    #[serde(default)]
+    pub resize_debounce_ms: Option<u64>,
+    /// Reader buffer size in bytes, clamped to 1 KiB..=1 MiB
+    #[serde(default)]
+    pub read_buffer_size: Option<usize>,
+    /// Cap on input bytes written per interval; unset disables throttling
+    #[serde(default)]
+    pub input_rate_limit_bytes: Option<usize>,
+    /// Throttle interval for `input_rate_limit_bytes` (default 10ms)
+    #[serde(default)]
+    pub input_rate_interval_ms: Option<u64>,
+    /// Kill the session after this long without input or output
+    #[serde(default)]
+    pub idle_timeout_secs: Option<u64>,
+    /// Let programs set the clipboard via OSC 52 (off by default)
+    #[serde(default)]
+    pub allow_clipboard_write: bool,
+    /// tmux session this terminal is attached to (set by `create_tmux`)
+    #[serde(skip)]
+    pub tmux_session: Option<String>,
 }
 
-struct PtyInstance {
-    writer: Arc<Mutex<Box<dyn Write + Send>>>,
-    cwd: String,
-    uses_pty: bool,
-    // Keep these alive to prevent PTY from closing
-    #[allow(dead_code)]
-    master: Option<Arc<Mutex<Box<dyn MasterPty + Send>>>>,
-    #[allow(dead_code)]
-    child_handle: Option<Arc<Mutex<Child>>>,
-}
+/// Bracketed-paste markers (DECSET 2004)
+const PASTE_START: &str = "\x1b[200~";
+const PASTE_END: &str = "\x1b[201~";
 
-pub struct PtyManager {
-    instances: Arc<Mutex<HashMap<String, PtyInstance>>>,
-    app_handle: AppHandle,
+/// Build the bytes for a paste. ESC bytes inside the data are dropped, so no
+/// end marker (however it's nested) can break out of the bracket and run commands.
+fn paste_payload(data: &str, bracketed: bool) -> Vec<u8> {
+    if !bracketed {
+        return data.as_bytes().to_vec();
+    }
+    let body: String = data.chars().filter(|&c| c != '\x1b').collect();
+    format!("{}{}{}", PASTE_START, body, PASTE_END).into_bytes()
 }
 
-impl PtyManager {
-    pub fn new(app_handle: AppHandle) -> Self {
-        Self {
-            instances: Arc::new(Mutex::new(HashMap::new())),
-            app_handle,
-        }
-    }
+/// Default quiet period for collapsing rapid resizes
+const DEFAULT_RESIZE_DEBOUNCE_MS: u64 = 50;
 
-    /// Detect the default shell for the current platform
-    fn get_default_shell() -> (String, Vec<String>) {
-        #[cfg(target_os = "windows")]
-        {
-            (
-                "powershell.exe".to_string(),
-                vec![
-                    "-ExecutionPolicy".to_string(),
-                    "Bypass".to_string(),
-                    "-NoLogo".to_string(),
-                ],
-            )
-        }
+/// Bounds for `CreatePtyOptions::read_buffer_size`
+const MIN_READ_BUFFER_SIZE: usize = 1024;
+const MAX_READ_BUFFER_SIZE: usize = 1024 * 1024;
 
-        #[cfg(target_os = "macos")]
-        {
-            let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/zsh".to_string());
-            // Use -l (login) to load .zprofile/.zshrc for proper PATH setup
-            // This ensures mise, homebrew, nvm, etc. paths are loaded
-            (shell, vec!["-l".to_string()])
-        }
+/// Allocate a reader buffer of the requested size, or `default` when unset
+fn read_buffer(requested: Option<usize>, default: usize) -> Vec<u8> {
+    let size = requested
+        .unwrap_or(default)
+        .clamp(MIN_READ_BUFFER_SIZE, MAX_READ_BUFFER_SIZE);
+    vec![0u8; size]
+}
 
-        #[cfg(target_os = "linux")]
-        {
-            let shell = std::env::var("SHELL")
-                .or_else(|_| {
-                    if std::path::Path::new("/bin/bash").exists() {
-                        Ok("/bin/bash".to_string())
-                    } else {
-                        Ok("/bin/sh".to_string())
-                    }
-                })
-                .unwrap();
-            (shell, vec![])
+/// Default output coalescing window
+const DEFAULT_OUTPUT_FLUSH_MS: u64 = 8;
+
+/// Default output batch size that forces an early emit
+const DEFAULT_OUTPUT_MAX_BATCH_BYTES: usize = 64 * 1024;
+
+/// Wait for the next chunk of output, then keep collecting chunks until `flush`
+/// has passed since the first one or `max_bytes` are buffered.
+/// With `idle` set, an empty batch is returned if nothing arrives within it.
+/// Returns None once the reader has hung up and nothing is left.
+fn next_output_batch(
+    chunks: &std::sync::mpsc::Receiver<Vec<u8>>,
+    flush: std::time::Duration,
+    max_bytes: usize,
+    idle: Option<std::time::Duration>,
+) -> Option<Vec<u8>> {
+    let mut batch = match idle {
+        Some(wait) => match chunks.recv_timeout(wait) {
+            Ok(chunk) => chunk,
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => return Some(Vec::new()),
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => return None,
+        },
+        None => chunks.recv().ok()?,
+    };
+    let deadline = std::time::Instant::now() + flush;
+
+    while batch.len() < max_bytes {
+        let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        match chunks.recv_timeout(remaining) {
+            Ok(chunk) => batch.extend_from_slice(&chunk),
+            // Timed out or the reader hung up: emit what we have
+            Err(_) => break,
         }
     }
 
-    /// Create UTF-8 environment variables with proper terminal settings
-    fn create_utf8_env() -> HashMap<String, String> {
-        let mut env_vars: HashMap<String, String> = std::env::vars().collect();
-
-        // CRITICAL: Set TERM for proper terminal emulation
-        // Without this, zsh plugins (syntax-highlighting, autosuggestions, powerlevel10k)
-        // don't know how to use cursor movement sequences and output raw characters
-        env_vars.insert("TERM".to_string(), "xterm-256color".to_string());
-        env_vars.insert("COLORTERM".to_string(), "truecolor".to_string());
-
-        // Terminal program identification - helps Claude Code and other TUI apps
-        // detect they're running in a proper terminal with full capabilities
-        env_vars.insert("TERM_PROGRAM".to_string(), "moonterm".to_string());
-        env_vars.insert("TERM_PROGRAM_VERSION".to_string(), env!("CARGO_PKG_VERSION").to_string());
-
-        // Force color output for CLI tools that check for TTY
-        env_vars.insert("FORCE_COLOR".to_string(), "1".to_string());
-
-        // Explicitly set CI to empty string to prevent tools from entering CI mode
-        // Claude Code and many CLI tools check this to decide output format
-        env_vars.insert("CI".to_string(), "".to_string());
+    Some(batch)
+}
 
-        // Add Homebrew paths to PATH (not inherited when launched from Finder)
-        #[cfg(target_os = "macos")]
-        {
-            let current_path = env_vars.get("PATH").cloned().unwrap_or_default();
-            let homebrew_paths = vec![
-                "/opt/homebrew/bin",      // Apple Silicon
-                "/opt/homebrew/sbin",
-                "/usr/local/bin",         // Intel Mac
-                "/usr/local/sbin",
-            ];
-            let mut new_path = homebrew_paths.join(":");
-            if !current_path.is_empty() {
-                new_path.push(':');
-                new_path.push_str(&current_path);
-            }
-            env_vars.insert("PATH".to_string(), new_path);
+/// Value in `CreatePtyOptions::env` that unsets an inherited variable
+/// (a lone NUL, which can never be a real environment value)
+pub const ENV_UNSET: &str = "\0";
+
+/// Apply frontend-provided overrides on top of the computed environment.
+/// Returns the merged variables and the keys that must be removed from the child.
+fn merge_env(
+    mut env_vars: HashMap<String, String>,
+    overrides: Option<&HashMap<String, String>>,
+) -> (HashMap<String, String>, Vec<String>) {
+    let mut unset = Vec::new();
+    for (key, value) in overrides.into_iter().flatten() {
+        if value == ENV_UNSET {
+            env_vars.remove(key);
+            unset.push(key.clone());
+        } else {
+            env_vars.insert(key.clone(), value.clone());
         }
+    }
+    (env_vars, unset)
+}
 
-        // UTF-8 encoding
-        env_vars.insert("LANG".to_string(), "en_US.UTF-8".to_string());
-        env_vars.insert("LC_ALL".to_string(), "en_US.UTF-8".to_string());
-        env_vars.insert("PYTHONIOENCODING".to_string(), "utf-8".to_string());
-        env_vars.insert("PYTHONUTF8".to_string(), "1".to_string());
+/// Shell families that differ in how login/interactive mode is requested
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShellFlavor {
+    Zsh,
+    Bash,
+    Fish,
+    PowerShell,
+    Other,
+}
 
-        env_vars
+impl ShellFlavor {
+    fn detect(shell: &str) -> Self {
+        let name = std::path::Path::new(shell)
+            .file_stem()
+            .map(|n| n.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        match name.as_str() {
+            "zsh" => Self::Zsh,
+            "bash" => Self::Bash,
+            "fish" => Self::Fish,
+            "pwsh" | "powershell" => Self::PowerShell,
+            _ => Self::Other,
+        }
     }
 
-    /// Create a new PTY instance
-    pub fn create(&self, options: CreatePtyOptions) -> Result<bool, String> {
-        // Check if PTY with this ID already exists - prevent duplicate creation
-        // This can happen when React components unmount/remount during render transitions
-        {
-            let instances = self.instances.lock();
-            if instances.contains_key(&options.id) {
-                println!("PTY instance {} already exists, skipping creation", options.id);
-                return Ok(false);
-            }
+    /// Flag that starts a login shell (sources .zprofile/.bash_profile)
+    fn login_flag(self) -> Option<&'static str> {
+        match self {
+            Self::Zsh | Self::Fish | Self::Other => Some("-l"),
+            Self::Bash => Some("--login"),
+            Self::PowerShell => None,
         }
+    }
 
-        let (shell, args) = Self::get_default_shell();
-        let mut env_vars = Self::create_utf8_env();
-
-        // Merge custom environment variables if provided
-        // Custom vars override system vars (workspace + .env + .envrc)
-        if let Some(custom_env) = &options.custom_env {
-            for (key, value) in custom_env {
-                env_vars.insert(key.clone(), value.clone());
-            }
+    /// Flag that starts an interactive, non-login shell (sources .zshrc/.bashrc only)
+    fn interactive_flag(self) -> Option<&'static str> {
+        match self {
+            Self::PowerShell => None,
+            _ => Some("-i"),
         }
-
-        // Use portable-pty only - no fallback to avoid duplicate output issues
-        self.create_with_portable_pty(&options, &shell, &args, &env_vars)?;
-        println!("Created terminal using portable-pty: {}", options.id);
-        Ok(true)
     }
+}
 
-    fn create_with_portable_pty(
-        &self,
-        options: &CreatePtyOptions,
-        shell: &str,
-        args: &[String],
-        env_vars: &HashMap<String, String>,
-    ) -> Result<(), String> {
-        let pty_system = native_pty_system();
+/// Apply the requested login mode to the default shell arguments
+fn apply_login_mode(shell: &str, mut args: Vec<String>, login_shell: Option<bool>) -> Vec<String> {
+    let flavor = ShellFlavor::detect(shell);
+    let Some(login) = login_shell else {
+        return args;
+    };
+    if flavor == ShellFlavor::PowerShell {
+        return args;
+    }
 
-        // Use reasonable defaults that work well with most terminal UIs
-        // The frontend will send the actual size via resize() shortly after creation
-        // Using 24x80 (classic VT100 size) reduces visual glitches during initialization
-        let pair = pty_system
-            .openpty(PtySize {
-                rows: 24,
-                cols: 80,
-                pixel_width: 0,
-                pixel_height: 0,
-            })
-            .map_err(|e| format!("Failed to open pty: {}", e))?;
+    args.retain(|arg| !matches!(arg.as_str(), "-l" | "--login" | "-i"));
+    let flag = if login {
+        flavor.login_flag()
+    } else {
+        flavor.interactive_flag()
+    };
+    if let Some(flag) = flag {
+        args.insert(0, flag.to_string());
+    }
+    args
+}
 
-        let mut cmd = CommandBuilder::new(shell);
-        cmd.args(args);
-        cmd.cwd(&options.cwd);
+/// Resolve a user-supplied shell: paths must exist, bare names are looked up on PATH
+fn resolve_shell(shell: &str) -> Result<String, String> {
+    if shell.trim().is_empty() {
+        return Err("Shell must not be empty".to_string());
+    }
 
-        for (key, value) in env_vars {
-            cmd.env(key, value);
+    if shell.contains('/') || shell.contains('\\') {
+        if std::path::Path::new(shell).is_file() {
+            return Ok(shell.to_string());
         }
+        return Err(format!("Shell not found: {}", shell));
+    }
 
-        let mut child = pair
-            .slave
-            .spawn_command(cmd)
-            .map_err(|e| format!("Failed to spawn command: {}", e))?;
+    platform::find_in_path(shell)
+        .map(|path| path.to_string_lossy().to_string())
+        .ok_or_else(|| format!("Shell '{}' not found in PATH", shell))
+}
 
-        // Get reader from master BEFORE taking writer
-        let reader = pair
-            .master
-            .try_clone_reader()
-            .map_err(|e| format!("Failed to clone reader: {}", e))?;
+/// Options for attaching a terminal to a tmux session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateTmuxOptions {
+    pub id: String,
+    pub session_name: String,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Create the session (detached) first if it doesn't exist yet
+    #[serde(default)]
+    pub new_session_if_missing: bool,
+}
 
-        // Get writer from master
-        let writer = pair
-            .master
-            .take_writer()
-            .map_err(|e| format!("Failed to take writer: {}", e))?;
+/// Options for opening a shell in a WSL distribution (Windows only)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateWslOptions {
+    pub id: String,
+    pub distro: String,
+    /// Linux path to start in (defaults to the distro user's home)
+    #[serde(default)]
+    pub cwd: Option<String>,
+    #[serde(default)]
+    pub user: Option<String>,
+}
 
-        let writer_arc = Arc::new(Mutex::new(writer as Box<dyn Write + Send>));
+/// Installed WSL distributions, queried once via `wsl.exe --list --quiet`
+#[cfg(target_os = "windows")]
+fn wsl_distributions() -> &'static [String] {
+    static DISTROS: std::sync::OnceLock<Vec<String>> = std::sync::OnceLock::new();
+    DISTROS.get_or_init(|| {
+        let output = match Command::new("wsl.exe").args(["--list", "--quiet"]).output() {
+            Ok(output) if output.status.success() => output.stdout,
+            _ => return vec![],
+        };
+
+        // wsl.exe writes UTF-16LE regardless of the console code page
+        let wide: Vec<u16> = output
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16_lossy(&wide)
+            .lines()
+            .map(|line| line.trim_matches(|c: char| c.is_whitespace() || c == '\0').to_string())
+            .filter(|line| !line.is_empty())
+            .collect()
+    })
+}
 
-        // IMPORTANT: Keep master alive to prevent pty from closing
-        let master_arc = Arc::new(Mutex::new(pair.master));
+/// Default cap on concurrently running instances
+const DEFAULT_MAX_INSTANCES: usize = 64;
 
-        // Spawn reader thread
-        let id = options.id.clone();
-        let app_handle = self.app_handle.clone();
+/// Current instance count and the configured limit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PtyInstanceLimit {
+    pub count: usize,
+    pub max_instances: usize,
+}
 
-        thread::spawn(move || {
-            let mut buf_reader = BufReader::new(reader);
-            // Larger buffer to reduce ANSI sequence fragmentation
-            // Claude Code and other TUI apps emit many escape sequences
-            let mut buf = [0u8; 16384];
-            // Pending incomplete UTF-8/ANSI bytes from previous read
-            let mut pending: Vec<u8> = Vec::new();
+/// Summary of a live terminal, returned by `PtyManager::list`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PtyInfo {
+    pub id: String,
+    pub cwd: String,
+    pub shell: String,
+    pub uses_pty: bool,
+    pub read_only: bool,
+    pub pid: Option<u32>,
+    pub title: Option<String>,
+}
 
-            loop {
-                match buf_reader.read(&mut buf) {
-                    Ok(0) => break, // EOF
-                    Ok(n) => {
-                        // Combine pending bytes with new data
-                        let mut data_bytes = std::mem::take(&mut pending);
-                        data_bytes.extend_from_slice(&buf[..n]);
+/// Terminals sharing a broadcast group, returned by `PtyManager::list_broadcast_groups`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PtyBroadcastGroup {
+    pub name: String,
+    pub members: Vec<String>,
+}
 
-                        // Find the safe boundary for both UTF-8 and ANSI sequences
-                        // Use the minimum to ensure neither is split
-                        let utf8_bound = find_utf8_boundary(&data_bytes);
-                        let ansi_bound = find_ansi_boundary(&data_bytes[..utf8_bound]);
-                        let valid_len = ansi_bound;
+/// Payload of the `pty:command-started` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PtyCommandEvent {
+    pub id: String,
+    pub command: String,
+}
 
-                        if valid_len > 0 {
-                            let data = String::from_utf8_lossy(&data_bytes[..valid_len]).to_string();
-                            let _ = app_handle.emit("pty:output", (&id, &data));
-                        }
+/// Payload of the `pty:hyperlink` event, sent when an OSC 8 link is closed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PtyHyperlinkEvent {
+    pub id: String,
+    pub url: String,
+    /// Visible link text
+    pub text: String,
+    /// Character offsets of the text in the session's visible output
+    /// (escape sequences and control characters not counted)
+    pub start: usize,
+    pub end: usize,
+}
 
-                        // Keep incomplete bytes for next iteration
-                        if valid_len < data_bytes.len() {
-                            pending = data_bytes[valid_len..].to_vec();
+/// Feed written input into the pending line buffer and return the lines
+/// completed by `\r` or `\n`. Handles backspace and Ctrl-C/Ctrl-U, and drops
+/// escape sequences (arrow keys etc.).
+fn track_input(line: &mut String, data: &str) -> Vec<String> {
+    let mut completed = Vec::new();
+    let mut chars = data.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' | '\n' => {
+                let text = std::mem::take(line);
+                if !text.trim().is_empty() {
+                    completed.push(text);
+                }
+            }
+            '\x7f' | '\x08' => {
+                line.pop();
+            }
+            '\x03' | '\x15' => line.clear(),
+            '\x1b' => {
+                // CSI sequences end with a byte in '@'..='~'; others are a single char
+                if chars.next_if_eq(&'[').is_some() {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
                         }
                     }
-                    Err(e) => {
-                        eprintln!("Read error: {}", e);
-                        break;
-                    }
+                } else {
+                    chars.next();
                 }
             }
+            c if c.is_control() && c != '\t' => {}
+            c => line.push(c),
+        }
+    }
 
-            // Flush any remaining pending bytes
+    completed
+}
+
+/// Turn an input line into a command: trims whitespace and strips a shell
+/// prompt prefix (up to the first `$ ` or `# `). The text before the marker
+/// only counts as a prompt if it doesn't end in a space or contain quotes, so
+/// markers inside the command itself (`echo "a # b"`, `ls # note`) are kept.
+fn parse_command_line(line: &str) -> Option<String> {
+    let line = line.trim_start();
+    let prompt_end = ["$ ", "# "]
+        .iter()
+        .filter_map(|marker| line.find(marker).map(|i| (i, i + marker.len())))
+        .min()
+        .filter(|&(start, _)| {
+            let prefix = &line[..start];
+            prefix.is_empty()
+                || (!prefix.ends_with(char::is_whitespace) && !prefix.contains(['"', '\'', '`']))
+        })
+        .map(|(_, end)| end);
+    let command = match prompt_end {
+        Some(end) => line[end..].trim(),
+        None => line.trim_end(),
+    };
+
+    if command.is_empty() {
+        None
+    } else {
+        Some(command.to_string())
+    }
+}
+
+/// Exit status published by the exit monitor; `None` until the process exits
+type ExitSender = Arc<tokio::sync::watch::Sender<Option<PtyExitEvent>>>;
+
+fn exit_channel() -> ExitSender {
+    Arc::new(tokio::sync::watch::channel(None).0)
+}
+
+/// Payload of the `pty:exit` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PtyExitEvent {
+    pub id: String,
+    /// Exit code, or None if the process was terminated by a signal
+    pub exit_code: Option<u32>,
+    /// Signal name (e.g. "SIGKILL") if the process was terminated by a signal
+    pub signal: Option<String>,
+    /// Signal number matching `signal`, when known
+    pub signal_number: Option<i32>,
+    /// Whether the signal produced a core dump (Unix fallback mode only)
+    pub core_dumped: bool,
+    pub success: bool,
+    /// Set when moonterm ended the session itself, e.g. `IDLE_TIMEOUT_REASON`
+    pub reason: Option<String>,
+}
+
+/// `PtyExitEvent::reason` for sessions killed by the idle watchdog
+pub const IDLE_TIMEOUT_REASON: &str = "idle_timeout";
+
+impl PtyExitEvent {
+    fn from_portable(id: &str, status: &portable_pty::ExitStatus) -> Self {
+        match status.signal() {
+            Some(signal) => {
+                let name = signal_name_from_description(signal);
+                Self {
+                    id: id.to_string(),
+                    exit_code: None,
+                    signal_number: signal_number(&name),
+                    signal: Some(name),
+                    // portable-pty doesn't report core dumps
+                    core_dumped: false,
+                    success: false,
+                    reason: None,
+                }
+            }
+            None => Self {
+                id: id.to_string(),
+                exit_code: Some(status.exit_code()),
+                signal: None,
+                signal_number: None,
+                core_dumped: false,
+                success: status.success(),
+                reason: None,
+            },
+        }
+    }
+
+    fn from_std(id: &str, status: &std::process::ExitStatus) -> Self {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Some(signum) = status.signal() {
+                return Self {
+                    id: id.to_string(),
+                    exit_code: None,
+                    signal: Some(
+                        signal_name(signum)
+                            .map(|name| name.to_string())
+                            .unwrap_or_else(|| format!("SIG{}", signum)),
+                    ),
+                    signal_number: Some(signum),
+                    core_dumped: status.core_dumped(),
+                    success: false,
+                    reason: None,
+                };
+            }
+        }
+
+        Self {
+            id: id.to_string(),
+            exit_code: Some(status.code().unwrap_or(1) as u32),
+            signal: None,
+            signal_number: None,
+            core_dumped: false,
+            success: status.success(),
+            reason: None,
+        }
+    }
+
+    /// Exit event for a process whose status could not be collected
+    fn unknown(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            exit_code: Some(1),
+            signal: None,
+            signal_number: None,
+            core_dumped: false,
+            success: false,
+            reason: None,
+        }
+    }
+}
+
+/// POSIX signal numbers that are the same on Linux and macOS
+const SIGNAL_NAMES: &[(i32, &str, &str)] = &[
+    (1, "SIGHUP", "Hangup"),
+    (2, "SIGINT", "Interrupt"),
+    (3, "SIGQUIT", "Quit"),
+    (4, "SIGILL", "Illegal instruction"),
+    (5, "SIGTRAP", "Trace/breakpoint trap"),
+    (6, "SIGABRT", "Aborted"),
+    (8, "SIGFPE", "Floating point exception"),
+    (9, "SIGKILL", "Killed"),
+    (11, "SIGSEGV", "Segmentation fault"),
+    (13, "SIGPIPE", "Broken pipe"),
+    (14, "SIGALRM", "Alarm clock"),
+    (15, "SIGTERM", "Terminated"),
+];
+
+/// Map a signal number to its name
+fn signal_name(signum: i32) -> Option<&'static str> {
+    SIGNAL_NAMES
+        .iter()
+        .find(|(num, _, _)| *num == signum)
+        .map(|(_, name, _)| *name)
+}
+
+/// Map a signal name to its number
+fn signal_number(name: &str) -> Option<i32> {
+    SIGNAL_NAMES
+        .iter()
+        .find(|(_, sig, _)| *sig == name)
+        .map(|(num, _, _)| *num)
+}
+
+/// Signals `PtyManager::signal` accepts
+const SENDABLE_SIGNALS: &[&str] = &["SIGHUP", "SIGINT", "SIGKILL", "SIGTERM"];
+
+/// Parse a signal name (`"SIGINT"`, `"int"`, ...) into its number
+fn parse_signal(name: &str) -> Result<i32, String> {
+    let upper = name.trim().to_ascii_uppercase();
+    let full = if upper.starts_with("SIG") {
+        upper
+    } else {
+        format!("SIG{}", upper)
+    };
+
+    if !SENDABLE_SIGNALS.contains(&full.as_str()) {
+        return Err(format!(
+            "Unsupported signal '{}' (expected one of {})",
+            name,
+            SENDABLE_SIGNALS.join(", ")
+        ));
+    }
+    signal_number(&full).ok_or_else(|| format!("Unsupported signal '{}'", name))
+}
+
+/// portable-pty reports signals by their strsignal() description; map known ones back to names
+fn signal_name_from_description(description: &str) -> String {
+    SIGNAL_NAMES
+        .iter()
+        .find(|(_, _, desc)| description.eq_ignore_ascii_case(desc))
+        .map(|(_, name, _)| name.to_string())
+        .unwrap_or_else(|| description.to_string())
+}
+
+/// Query a shell's version by running it with its version flag (not via a PTY).
+/// Returns the first non-empty line of output.
+pub fn get_shell_version(shell_path: &str) -> Result<String, String> {
+    let name = std::path::Path::new(shell_path)
+        .file_stem()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    let args: &[&str] = match name.as_str() {
+        "zsh" | "bash" | "fish" | "ksh" | "mksh" | "tcsh" | "csh" | "nu" | "elvish" | "xonsh" => {
+            &["--version"]
+        }
+        "pwsh" | "powershell" => &["-NoLogo", "-NoProfile", "-Command", "$PSVersionTable.PSVersion.ToString()"],
+        "tmux" => &["-V"],
+        "" => return Err("No shell for this terminal".to_string()),
+        _ => return Err(format!("Don't know how to query the version of '{}'", name)),
+    };
+
+    let output = Command::new(shell_path)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", shell_path, e))?;
+
+    // Some shells (older ksh) print the version on stderr
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    stdout
+        .lines()
+        .chain(stderr.lines())
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .ok_or_else(|| format!("{} printed no version information", shell_path))
+}
+
+/// Raw fd of a PTY master, if the backend exposes one
+#[cfg(unix)]
+fn master_raw_fd(master: &(dyn MasterPty + Send)) -> Option<std::os::unix::io::RawFd> {
+    master.as_raw_fd()
+}
+
+/// Find every descendant of `root` in `ps -A -o pid= -o ppid=` output,
+/// parents before their children
+fn descendant_pids(ps_output: &str, root: u32) -> Vec<u32> {
+    let pairs: Vec<(u32, u32)> = ps_output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pid = fields.next()?.parse().ok()?;
+            let ppid = fields.next()?.parse().ok()?;
+            Some((pid, ppid))
+        })
+        .collect();
+
+    let mut descendants = Vec::new();
+    let mut frontier = vec![root];
+    while let Some(parent) = frontier.pop() {
+        for &(pid, ppid) in &pairs {
+            if ppid == parent && pid != root && !descendants.contains(&pid) {
+                descendants.push(pid);
+                frontier.push(pid);
+            }
+        }
+    }
+    descendants
+}
+
+/// Kill a process and everything it started. portable-pty starts the shell in its
+/// own session, so its process group is signalled too; jobs the shell moved into
+/// other groups are found by walking the process table.
+#[cfg(unix)]
+pub fn kill_process_tree(pid: u32) -> Result<(), String> {
+    let descendants = Command::new("ps")
+        .args(["-A", "-o", "pid=", "-o", "ppid="])
+        .output()
+        .map(|output| descendant_pids(&String::from_utf8_lossy(&output.stdout), pid))
+        .unwrap_or_default();
+
+    // SAFETY: kill() has no memory-safety requirements
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+    }
+    // Children first so nothing gets reparented and missed
+    for target in descendants.iter().rev().chain(std::iter::once(&pid)) {
+        // SAFETY: as above
+        unsafe {
+            libc::kill(*target as libc::pid_t, libc::SIGKILL);
+        }
+    }
+
+    Ok(())
+}
+
+/// Kill a process and everything it started (`taskkill /T` walks the tree)
+#[cfg(windows)]
+pub fn kill_process_tree(pid: u32) -> Result<(), String> {
+    let status = Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &pid.to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|e| format!("Failed to run taskkill: {}", e))?;
+    if !status.success() {
+        return Err(format!("taskkill failed for process {}", pid));
+    }
+    Ok(())
+}
+
+/// Extract `(code, payload)` from each complete OSC sequence
+/// (`ESC ] code ; payload` terminated by BEL or ST) in `data`
+fn parse_osc_sequences(data: &str) -> Vec<(u32, String)> {
+    let mut sequences = Vec::new();
+    let mut rest = data;
+
+    while let Some(start) = rest.find("\x1b]") {
+        let body = &rest[start + 2..];
+        let (end, terminator_len) = match (body.find('\x07'), body.find("\x1b\\")) {
+            (Some(bel), Some(st)) if st < bel => (st, 2),
+            (Some(bel), _) => (bel, 1),
+            (None, Some(st)) => (st, 2),
+            (None, None) => break,
+        };
+
+        if let Some((code, payload)) = body[..end].split_once(';') {
+            if let Ok(code) = code.parse::<u32>() {
+                sequences.push((code, payload.to_string()));
+            }
+        }
+        rest = &body[end + terminator_len..];
+    }
+
+    sequences
+}
+
+/// Count BEL characters in `data`, ignoring those that terminate OSC sequences
+fn count_bells(data: &str) -> usize {
+    let mut count = 0;
+    let mut in_osc = false;
+    let mut chars = data.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' if chars.peek() == Some(&']') => {
+                chars.next();
+                in_osc = true;
+            }
+            '\x1b' if in_osc && chars.peek() == Some(&'\\') => {
+                chars.next();
+                in_osc = false;
+            }
+            '\x07' if in_osc => in_osc = false,
+            '\x07' => count += 1,
+            _ => {}
+        }
+    }
+
+    count
+}
+
+/// Minimum time between `pty:bell` events for one terminal
+const BELL_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(200);
+
+/// Debounce state for `pty:bell`; bells rung too soon are held and reported together
+#[derive(Default)]
+struct BellDebouncer {
+    pending: usize,
+    last: Option<std::time::Instant>,
+}
+
+impl BellDebouncer {
+    fn ring(&mut self, count: usize) {
+        self.pending += count;
+    }
+
+    /// Take the held bells if there are any and the debounce has passed
+    fn take_due(&mut self) -> Option<usize> {
+        if self.pending == 0 || self.wait().is_some_and(|wait| !wait.is_zero()) {
+            return None;
+        }
+        self.last = Some(std::time::Instant::now());
+        Some(std::mem::take(&mut self.pending))
+    }
+
+    /// Time until held bells are due; None when nothing is held
+    fn wait(&self) -> Option<std::time::Duration> {
+        if self.pending == 0 {
+            return None;
+        }
+        Some(match self.last {
+            Some(at) => BELL_DEBOUNCE.saturating_sub(at.elapsed()),
+            None => std::time::Duration::ZERO,
+        })
+    }
+}
+
+/// Decode `%XX` escapes; None if an escape is malformed or the result isn't UTF-8
+fn percent_decode(input: &str) -> Option<String> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = input.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Extract the directory from an OSC 7 payload (`file://host/path`)
+fn parse_osc7_path(payload: &str) -> Option<String> {
+    let rest = payload.strip_prefix("file://")?;
+    let path = percent_decode(&rest[rest.find('/')?..])?;
+
+    // file:///C:/Users/... on Windows
+    let bytes = path.as_bytes();
+    if bytes.len() >= 3 && bytes[1].is_ascii_alphabetic() && bytes[2] == b':' {
+        return Some(path[1..].to_string());
+    }
+    Some(path)
+}
+
+/// Decode the text of an OSC 52 set request (`selection;base64`).
+/// Queries (`?`) and malformed payloads yield None.
+fn parse_osc52(payload: &str) -> Option<String> {
+    let (_selection, data) = payload.split_once(';')?;
+    if data == "?" {
+        return None;
+    }
+    let decoded = BASE64.decode(data.trim()).ok()?;
+    String::from_utf8(decoded).ok()
+}
+
+/// A chunk of scripted input, written after waiting `delay_ms`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DelayedWrite {
+    pub delay_ms: u64,
+    pub text: String,
+}
+
+/// Default number of output lines retained per session
+const DEFAULT_SCROLLBACK_LINES: usize = 10_000;
+
+/// Default number of raw output bytes retained per session
+const DEFAULT_SCROLLBACK_BYTES: usize = 1024 * 1024;
+
+/// Bounded buffer of recent output, kept both as lines and as raw bytes
+struct Scrollback {
+    lines: VecDeque<String>,
+    /// Trailing output not yet terminated by a newline
+    partial: String,
+    capacity: usize,
+    /// Output exactly as read, for repainting after a frontend reload
+    raw: VecDeque<u8>,
+    raw_capacity: usize,
+}
+
+impl Scrollback {
+    fn new(capacity: usize, raw_capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            partial: String::new(),
+            capacity: capacity.max(1),
+            raw: VecDeque::new(),
+            raw_capacity: raw_capacity.max(1),
+        }
+    }
+
+    fn shared(capacity: Option<usize>, raw_capacity: Option<usize>) -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(Self::new(
+            capacity.unwrap_or(DEFAULT_SCROLLBACK_LINES),
+            raw_capacity.unwrap_or(DEFAULT_SCROLLBACK_BYTES),
+        )))
+    }
+
+    fn push(&mut self, data: &str) {
+        self.push_raw(data.as_bytes());
+        self.push_lines(data);
+    }
+
+    /// Record bytes that may not be valid UTF-8 (binary mode)
+    fn push_bytes(&mut self, data: &[u8]) {
+        self.push_raw(data);
+        self.push_lines(&String::from_utf8_lossy(data));
+    }
+
+    fn push_raw(&mut self, data: &[u8]) {
+        self.raw.extend(data);
+        let excess = self.raw.len().saturating_sub(self.raw_capacity);
+        self.raw.drain(..excess);
+    }
+
+    fn push_lines(&mut self, data: &str) {
+        self.partial.push_str(data);
+        while let Some(pos) = self.partial.find('\n') {
+            let line: String = self.partial.drain(..=pos).collect();
+            self.lines.push_back(line);
+            if self.lines.len() > self.capacity {
+                self.lines.pop_front();
+            }
+        }
+
+        // Output without newlines (progress bars, TUIs, binary) keeps only its tail
+        if self.partial.len() > self.raw_capacity {
+            let mut cut = self.partial.len() - self.raw_capacity;
+            while !self.partial.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.partial.drain(..cut);
+        }
+    }
+
+    /// The most recent `max_bytes` of raw output (all of it when None)
+    fn raw_tail(&self, max_bytes: Option<usize>) -> Vec<u8> {
+        let skip = max_bytes
+            .map(|max| self.raw.len().saturating_sub(max))
+            .unwrap_or(0);
+        self.raw.iter().skip(skip).copied().collect()
+    }
+
+    fn clear(&mut self) {
+        self.lines.clear();
+        self.partial.clear();
+        self.raw.clear();
+    }
+
+    /// Completed line `index` counting back from the most recent (0), without its line ending
+    fn line(&self, index: usize) -> Option<String> {
+        if index >= self.lines.len() {
+            return None;
+        }
+        self.lines
+            .get(self.lines.len() - 1 - index)
+            .map(|line| line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    /// Lines `start..end` counting back from the most recent, most recent first
+    fn range(&self, start: usize, end: usize) -> Vec<String> {
+        (start..end.min(self.lines.len()))
+            .filter_map(|index| self.line(index))
+            .collect()
+    }
+}
+
+/// Default throttle window for `input_rate_limit_bytes`
+const DEFAULT_INPUT_RATE_INTERVAL_MS: u64 = 10;
+
+/// Cancellation flag a sleeping thread can wait on, so cancelling wakes it
+/// instead of taking effect only after the sleep
+#[derive(Default)]
+struct CancelSignal {
+    cancelled: Mutex<bool>,
+    wake: parking_lot::Condvar,
+}
+
+impl CancelSignal {
+    fn cancel(&self) {
+        *self.cancelled.lock() = true;
+        self.wake.notify_all();
+    }
+
+    /// Wait up to `timeout`; returns true as soon as the signal is cancelled
+    fn wait(&self, timeout: std::time::Duration) -> bool {
+        let deadline = std::time::Instant::now() + timeout;
+        let mut cancelled = self.cancelled.lock();
+        while !*cancelled {
+            if self.wake.wait_until(&mut cancelled, deadline).timed_out() {
+                break;
+            }
+        }
+        *cancelled
+    }
+}
+
+/// Resize waiting out the debounce. One timer thread per terminal waits for
+/// the deadline; later resizes just replace the size and push the deadline back.
+#[derive(Default)]
+struct PendingResize {
+    latest: Option<(PtySize, std::time::Instant)>,
+    /// Whether a timer thread is currently running
+    timer_running: bool,
+}
+
+impl PendingResize {
+    /// Record the latest size; true if a timer thread needs to be started
+    fn schedule(&mut self, size: PtySize, deadline: std::time::Instant) -> bool {
+        self.latest = Some((size, deadline));
+        !std::mem::replace(&mut self.timer_running, true)
+    }
+
+    /// Called by the timer: Break with the size once it is due (the timer then
+    /// exits), Continue with how much longer to wait
+    fn poll(&mut self, now: std::time::Instant) -> ControlFlow<Option<PtySize>, std::time::Duration> {
+        match self.latest {
+            Some((_, deadline)) if deadline > now => ControlFlow::Continue(deadline - now),
+            latest => {
+                self.latest = None;
+                self.timer_running = false;
+                ControlFlow::Break(latest.map(|(size, _)| size))
+            }
+        }
+    }
+}
+
+/// Per-terminal input throttle: at most `bytes_per_interval` bytes are written
+/// per window, the rest waits in `queue` for a drain thread
+struct InputThrottle {
+    bytes_per_interval: usize,
+    interval: std::time::Duration,
+    window_start: Option<std::time::Instant>,
+    sent_in_window: usize,
+    queue: VecDeque<u8>,
+    /// Whether a drain thread is currently running
+    draining: bool,
+}
+
+impl InputThrottle {
+    fn new(bytes_per_interval: usize, interval: std::time::Duration) -> Self {
+        Self {
+            bytes_per_interval,
+            interval,
+            window_start: None,
+            sent_in_window: 0,
+            queue: VecDeque::new(),
+            draining: false,
+        }
+    }
+
+    /// Throttle from the create options, if enabled
+    fn from_options(options: &CreatePtyOptions) -> Option<Arc<Mutex<Self>>> {
+        let limit = options.input_rate_limit_bytes.filter(|&limit| limit > 0)?;
+        let interval = std::time::Duration::from_millis(
+            options
+                .input_rate_interval_ms
+                .unwrap_or(DEFAULT_INPUT_RATE_INTERVAL_MS)
+                .max(1),
+        );
+        Some(Arc::new(Mutex::new(Self::new(limit, interval))))
+    }
+
+    /// Bytes still allowed at `now`, starting a fresh window once the last expired
+    fn allowance(&mut self, now: std::time::Instant) -> usize {
+        match self.window_start {
+            Some(start) if now.duration_since(start) < self.interval => {}
+            _ => {
+                self.window_start = Some(now);
+                self.sent_in_window = 0;
+            }
+        }
+        self.bytes_per_interval.saturating_sub(self.sent_in_window)
+    }
+
+    /// Take the queued bytes that fit in the current window, in order
+    fn next_batch(&mut self, now: std::time::Instant) -> Vec<u8> {
+        let n = self.allowance(now).min(self.queue.len());
+        self.sent_in_window += n;
+        self.queue.drain(..n).collect()
+    }
+
+    /// Time left until the current window ends
+    fn until_next_window(&self, now: std::time::Instant) -> std::time::Duration {
+        match self.window_start {
+            Some(start) => (start + self.interval).saturating_duration_since(now),
+            None => std::time::Duration::ZERO,
+        }
+    }
+}
+
+/// Parser state for `AnsiStripper`
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+enum AnsiState {
+    #[default]
+    Ground,
+    /// After ESC
+    Escape,
+    /// ESC followed by intermediate bytes, e.g. `ESC ( B`
+    Intermediate,
+    /// CSI parameters, until a final byte
+    Csi,
+    /// OSC/DCS/SOS/PM/APC payload, until BEL or ST
+    String,
+    /// ESC inside a string, possibly the start of ST (`ESC \`)
+    StringEscape,
+    /// SS2/SS3, which apply to exactly one following byte
+    SingleShift,
+}
+
+/// Removes ANSI escape sequences and control characters (except newline and
+/// tab) from a byte stream. Sequences may be split across calls.
+#[derive(Debug, Default)]
+struct AnsiStripper {
+    state: AnsiState,
+}
+
+impl AnsiStripper {
+    fn strip(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len());
+        for &byte in data {
+            self.state = match (self.state, byte) {
+                (AnsiState::Ground, 0x1b) => AnsiState::Escape,
+                (AnsiState::Ground, b'\n' | b'\t') => {
+                    out.push(byte);
+                    AnsiState::Ground
+                }
+                (AnsiState::Ground, 0x00..=0x1f | 0x7f) => AnsiState::Ground,
+                (AnsiState::Ground, _) => {
+                    out.push(byte);
+                    AnsiState::Ground
+                }
+                (AnsiState::Escape, b'[') => AnsiState::Csi,
+                (AnsiState::Escape, b']' | b'P' | b'X' | b'^' | b'_') => AnsiState::String,
+                (AnsiState::Escape, b'N' | b'O') => AnsiState::SingleShift,
+                (AnsiState::Escape | AnsiState::Intermediate, 0x20..=0x2f) => AnsiState::Intermediate,
+                (AnsiState::Escape | AnsiState::Intermediate, _) => AnsiState::Ground,
+                (AnsiState::Csi, 0x40..=0x7e) => AnsiState::Ground,
+                (AnsiState::Csi, _) => AnsiState::Csi,
+                (AnsiState::String, 0x07) => AnsiState::Ground,
+                (AnsiState::String | AnsiState::StringEscape, 0x1b) => AnsiState::StringEscape,
+                (AnsiState::String, _) => AnsiState::String,
+                (AnsiState::StringEscape, b'\\') => AnsiState::Ground,
+                (AnsiState::StringEscape, _) => AnsiState::String,
+                (AnsiState::SingleShift, _) => AnsiState::Ground,
+            };
+        }
+        out
+    }
+}
+
+/// Output tee'd to a file by `PtyManager::start_logging`
+struct OutputLog {
+    file: std::fs::File,
+    /// Set when the log should be plain text
+    stripper: Option<AnsiStripper>,
+}
+
+impl OutputLog {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        match self.stripper {
+            Some(ref mut stripper) => self.file.write_all(&stripper.strip(data)),
+            None => self.file.write_all(data),
+        }
+    }
+}
+
+/// Active output log of an instance, shared with its reader thread(s)
+type SharedLog = Arc<Mutex<Option<OutputLog>>>;
+
+/// Record a batch of binary-mode output exactly as read and return it
+/// base64-encoded for `pty:output-bytes`
+fn record_binary_output(
+    scrollback: &Mutex<Scrollback>,
+    log: &Mutex<Option<OutputLog>>,
+    bytes: &[u8],
+) -> String {
+    scrollback.lock().push_bytes(bytes);
+    append_log(log, bytes);
+    BASE64.encode(bytes)
+}
+
+/// Append to the log if one is active; a failing log is closed
+fn append_log(log: &Mutex<Option<OutputLog>>, data: &[u8]) {
+    let mut log = log.lock();
+    if let Some(ref mut output) = *log {
+        if let Err(e) = output.write(data) {
+            eprintln!("Failed to write terminal log, closing it: {}", e);
+            *log = None;
+        }
+    }
+}
+
+/// Follows OSC 8 hyperlinks (`ESC ] 8 ; params ; URL ST text ESC ] 8 ; ; ST`)
+/// across output chunks. Chunks must not split escape sequences.
+#[derive(Debug, Default)]
+struct HyperlinkTracker {
+    stripper: AnsiStripper,
+    /// Visible characters seen so far
+    offset: usize,
+    /// Link currently open: (url, start offset, text so far)
+    open: Option<(String, usize, String)>,
+}
+
+impl HyperlinkTracker {
+    /// Feed output and return the links closed in it as (url, text, start, end)
+    fn feed(&mut self, data: &str) -> Vec<(String, String, usize, usize)> {
+        let mut links = Vec::new();
+        let mut rest = data;
+
+        while let Some(pos) = rest.find("\x1b]8;") {
+            self.push_visible(&rest[..pos]);
+            let body = &rest[pos + 4..];
+            let (end, terminator_len) = match (body.find('\x07'), body.find("\x1b\\")) {
+                (Some(bel), Some(st)) if st < bel => (st, 2),
+                (Some(bel), _) => (bel, 1),
+                (None, Some(st)) => (st, 2),
+                (None, None) => return links,
+            };
+
+            // Opening a link implicitly closes the previous one
+            if let Some((url, start, text)) = self.open.take() {
+                links.push((url, text, start, self.offset));
+            }
+            let url = body[..end].split_once(';').map(|(_, url)| url).unwrap_or("");
+            if !url.is_empty() {
+                self.open = Some((url.to_string(), self.offset, String::new()));
+            }
+            rest = &body[end + terminator_len..];
+        }
+        self.push_visible(rest);
+
+        links
+    }
+
+    fn push_visible(&mut self, data: &str) {
+        let visible = self.stripper.strip(data.as_bytes());
+        // Count characters, not bytes: skip UTF-8 continuation bytes
+        self.offset += visible.iter().filter(|&&b| b & 0xC0 != 0x80).count();
+        if let Some((_, _, ref mut text)) = self.open {
+            text.push_str(&String::from_utf8_lossy(&visible));
+        }
+    }
+}
+
+/// What a PTY was spawned with, so it can be forked
+#[derive(Clone)]
+struct SpawnConfig {
+    options: CreatePtyOptions,
+    args: Vec<String>,
+    env_vars: HashMap<String, String>,
+}
+
+struct PtyInstance {
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+    /// Current directory, updated from OSC 7 reports
+    cwd: Arc<Mutex<String>>,
+    /// Latest title set via OSC 0/2
+    title: Arc<Mutex<Option<String>>>,
+    /// Program running in the terminal (empty for file tails)
+    shell: String,
+    /// Cached result of `get_shell_version`
+    shell_version: Option<String>,
+    /// Writes are rejected while set
+    read_only: bool,
+    uses_pty: bool,
+    // Keep these alive to prevent PTY from closing
+    #[allow(dead_code)]
+    master: Option<Arc<Mutex<Box<dyn MasterPty + Send>>>>,
+    #[allow(dead_code)]
+    child_handle: Option<Arc<Mutex<Child>>>,
+    /// Stop flag for file-tail instances (no shell process behind them)
+    tail_stop: Option<Arc<AtomicBool>>,
+    /// Set by the reader thread once the first output arrives
+    first_output_received: Arc<AtomicBool>,
+    /// Recent output, fed by the reader thread(s)
+    scrollback: Arc<Mutex<Scrollback>>,
+    /// Input typed since the last newline
+    input_line: String,
+    /// Last command submitted via `write`
+    last_command: Arc<Mutex<Option<String>>>,
+    /// Spawn parameters (None for file tails)
+    spawn: Option<SpawnConfig>,
+    /// OS process id of the shell, if known
+    pid: Option<u32>,
+    /// Notifies `wait` callers when the process exits
+    exit: ExitSender,
+    /// Resize waiting out `resize_debounce`
+    pending_resize: Arc<Mutex<PendingResize>>,
+    /// Quiet period before a resize is applied
+    resize_debounce: std::time::Duration,
+    /// Last size requested as (cols, rows), kept even when it can't be applied
+    requested_size: Mutex<Option<(u16, u16)>>,
+    /// Input rate limit, if enabled
+    input_throttle: Option<Arc<Mutex<InputThrottle>>>,
+    /// Last input or output, for the idle watchdog
+    last_activity: Arc<Mutex<std::time::Instant>>,
+    /// Reported in `pty:exit` when moonterm ended the session itself
+    exit_reason: Arc<Mutex<Option<String>>>,
+    /// Output log started by `start_logging`
+    log: SharedLog,
+    /// Input written to this instance is copied to every member of the group
+    broadcast_group: Option<String>,
+}
+
+pub struct PtyManager {
+    instances: Arc<Mutex<HashMap<String, PtyInstance>>>,
+    /// Cancellation signals for in-flight delayed writes, keyed by cancel token
+    delayed_writes: Arc<Mutex<HashMap<String, Arc<CancelSignal>>>>,
+    /// Creating more instances than this fails
+    max_instances: AtomicUsize,
+    app_handle: AppHandle,
+}
+
+impl PtyManager {
+    pub fn new(app_handle: AppHandle) -> Self {
+        Self {
+            instances: Arc::new(Mutex::new(HashMap::new())),
+            delayed_writes: Arc::new(Mutex::new(HashMap::new())),
+            max_instances: AtomicUsize::new(DEFAULT_MAX_INSTANCES),
+            app_handle,
+        }
+    }
+
+    /// Current instance count and limit
+    pub fn instance_limit(&self) -> PtyInstanceLimit {
+        PtyInstanceLimit {
+            count: self.instances.lock().len(),
+            max_instances: self.max_instances.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Change the instance limit; running instances above it are left alone
+    pub fn set_max_instances(&self, max_instances: usize) -> Result<(), String> {
+        if max_instances == 0 {
+            return Err("Instance limit must be at least 1".to_string());
+        }
+        self.max_instances.store(max_instances, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Fail before spawning anything if the instance limit is reached
+    fn ensure_capacity(&self) -> Result<(), String> {
+        let limit = self.instance_limit();
+        if limit.count >= limit.max_instances {
+            return Err(format!(
+                "Too many terminals: limit of {} reached",
+                limit.max_instances
+            ));
+        }
+        Ok(())
+    }
+
+    /// Detect the default shell for the current platform
+    fn get_default_shell() -> (String, Vec<String>) {
+        #[cfg(target_os = "windows")]
+        {
+            (
+                "powershell.exe".to_string(),
+                vec![
+                    "-ExecutionPolicy".to_string(),
+                    "Bypass".to_string(),
+                    "-NoLogo".to_string(),
+                ],
+            )
+        }
+
+        #[cfg(target_os = "macos")]
+        {
+            let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/zsh".to_string());
+            // Use -l (login) to load .zprofile/.zshrc for proper PATH setup
+            // This ensures mise, homebrew, nvm, etc. paths are loaded
+            (shell, vec!["-l".to_string()])
+        }
+
+        #[cfg(target_os = "linux")]
+        {
+            let shell = std::env::var("SHELL")
+                .or_else(|_| {
+                    if std::path::Path::new("/bin/bash").exists() {
+                        Ok("/bin/bash".to_string())
+                    } else {
+                        Ok("/bin/sh".to_string())
+                    }
+                })
+                .unwrap();
+
+            // Under WSL the app is usually launched through WSLg without a login
+            // environment, so use a login shell to pick up PATH from the profile
+            if platform::is_wsl() {
+                return (shell, vec!["-l".to_string()]);
+            }
+
+            (shell, vec![])
+        }
+    }
+
+    /// Create UTF-8 environment variables with proper terminal settings
+    fn create_utf8_env() -> HashMap<String, String> {
+        let mut env_vars: HashMap<String, String> = std::env::vars().collect();
+
+        // CRITICAL: Set TERM for proper terminal emulation
+        // Without this, zsh plugins (syntax-highlighting, autosuggestions, powerlevel10k)
+        // don't know how to use cursor movement sequences and output raw characters
+        env_vars.insert("TERM".to_string(), "xterm-256color".to_string());
+        env_vars.insert("COLORTERM".to_string(), "truecolor".to_string());
+
+        // Terminal program identification - helps Claude Code and other TUI apps
+        // detect they're running in a proper terminal with full capabilities
+        env_vars.insert("TERM_PROGRAM".to_string(), "moonterm".to_string());
+        env_vars.insert("TERM_PROGRAM_VERSION".to_string(), env!("CARGO_PKG_VERSION").to_string());
+
+        // Force color output for CLI tools that check for TTY
+        env_vars.insert("FORCE_COLOR".to_string(), "1".to_string());
+
+        // Explicitly set CI to empty string to prevent tools from entering CI mode
+        // Claude Code and many CLI tools check this to decide output format
+        env_vars.insert("CI".to_string(), "".to_string());
+
+        // Add Homebrew paths to PATH (not inherited when launched from Finder)
+        #[cfg(target_os = "macos")]
+        {
+            let current_path = env_vars.get("PATH").cloned().unwrap_or_default();
+            let homebrew_paths = vec![
+                "/opt/homebrew/bin",      // Apple Silicon
+                "/opt/homebrew/sbin",
+                "/usr/local/bin",         // Intel Mac
+                "/usr/local/sbin",
+            ];
+            let mut new_path = homebrew_paths.join(":");
+            if !current_path.is_empty() {
+                new_path.push(':');
+                new_path.push_str(&current_path);
+            }
+            env_vars.insert("PATH".to_string(), new_path);
+        }
+
+        // UTF-8 encoding
+        env_vars.insert("LANG".to_string(), "en_US.UTF-8".to_string());
+        env_vars.insert("LC_ALL".to_string(), "en_US.UTF-8".to_string());
+        env_vars.insert("PYTHONIOENCODING".to_string(), "utf-8".to_string());
+        env_vars.insert("PYTHONUTF8".to_string(), "1".to_string());
+
+        env_vars
+    }
+
+    /// Create a new PTY instance
+    pub fn create(&self, options: CreatePtyOptions) -> Result<bool, String> {
+        // Check if PTY with this ID already exists - prevent duplicate creation
+        // This can happen when React components unmount/remount during render transitions
+        {
+            let instances = self.instances.lock();
+            if instances.contains_key(&options.id) {
+                println!("PTY instance {} already exists, skipping creation", options.id);
+                return Ok(false);
+            }
+        }
+
+        let (shell, args) = match &options.shell {
+            Some(shell) => (resolve_shell(shell)?, options.args.clone().unwrap_or_default()),
+            None => {
+                let (shell, default_args) = Self::get_default_shell();
+                (shell, options.args.clone().unwrap_or(default_args))
+            }
+        };
+        let args = apply_login_mode(&shell, args, options.login_shell);
+        let mut env_vars = Self::create_utf8_env();
+
+        // Merge custom environment variables if provided
+        // Custom vars override system vars (workspace + .env + .envrc)
+        if let Some(custom_env) = &options.custom_env {
+            for (key, value) in custom_env {
+                env_vars.insert(key.clone(), value.clone());
+            }
+        }
+
+        // Use portable-pty only - no fallback to avoid duplicate output issues
+        self.create_with_portable_pty(&options, &shell, &args, &env_vars)?;
+        println!("Created terminal using portable-pty: {}", options.id);
+        Ok(true)
+    }
+
+    /// Attach a new PTY to a tmux session, creating it first if requested
+    pub fn create_tmux(&self, options: CreateTmuxOptions) -> Result<bool, String> {
+        {
+            let instances = self.instances.lock();
+            if instances.contains_key(&options.id) {
+                println!("PTY instance {} already exists, skipping creation", options.id);
+                return Ok(false);
+            }
+        }
+        // Check before creating a session, so a refusal doesn't leave one orphaned
+        self.ensure_capacity()?;
+
+        let tmux = platform::find_in_path("tmux")
+            .ok_or_else(|| "tmux not found in PATH - install tmux to use tmux sessions".to_string())?;
+        let tmux = tmux.to_string_lossy().to_string();
+
+        if options.session_name.is_empty() || options.session_name.contains(':') {
+            return Err(format!("Invalid tmux session name: '{}'", options.session_name));
+        }
+
+        let cwd = options
+            .cwd
+            .clone()
+            .or_else(|| std::env::var("HOME").ok())
+            .unwrap_or_else(|| ".".to_string());
+
+        // `has-session -t` does prefix matching, `=name` forces an exact match
+        let exact_target = format!("={}", options.session_name);
+        let has_session = Command::new(&tmux)
+            .args(["has-session", "-t", &exact_target])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false);
+
+        if !has_session {
+            if !options.new_session_if_missing {
+                return Err(format!("tmux session '{}' does not exist", options.session_name));
+            }
+
+            let output = Command::new(&tmux)
+                .args(["new-session", "-d", "-s", &options.session_name, "-c", &cwd])
+                .output()
+                .map_err(|e| format!("Failed to run tmux: {}", e))?;
+            if !output.status.success() {
+                return Err(format!(
+                    "Failed to create tmux session '{}': {}",
+                    options.session_name,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+        }
+
+        let mut env_vars = Self::create_utf8_env();
+        // When moonterm itself was launched from inside tmux, the inherited TMUX
+        // variable makes `attach` refuse to nest. Sessions that are already
+        // attached elsewhere are shared, not stolen (no `-d`).
+        env_vars.remove("TMUX");
+        env_vars.remove("TMUX_PANE");
+
+        let args = vec![
+            "attach-session".to_string(),
+            "-t".to_string(),
+            exact_target,
+        ];
+        let pty_options = CreatePtyOptions {
+            id: options.id.clone(),
+            cwd,
+            tmux_session: Some(options.session_name.clone()),
+            ..Default::default()
+        };
+
+        self.create_with_portable_pty(&pty_options, &tmux, &args, &env_vars)?;
+        println!("Attached terminal {} to tmux session {}", options.id, options.session_name);
+        Ok(true)
+    }
+
+    /// Open a bash shell in a specific WSL distribution
+    #[cfg(target_os = "windows")]
+    pub fn create_wsl(&self, options: CreateWslOptions) -> Result<bool, String> {
+        {
+            let instances = self.instances.lock();
+            if instances.contains_key(&options.id) {
+                println!("PTY instance {} already exists, skipping creation", options.id);
+                return Ok(false);
+            }
+        }
+
+        let distros = wsl_distributions();
+        if !distros.iter().any(|d| d.eq_ignore_ascii_case(&options.distro)) {
+            return Err(format!(
+                "WSL distribution '{}' is not installed (available: {})",
+                options.distro,
+                distros.join(", ")
+            ));
+        }
+
+        let mut args = vec!["--distribution".to_string(), options.distro.clone()];
+        if let Some(ref cwd) = options.cwd {
+            args.push("--cd".to_string());
+            args.push(cwd.clone());
+        }
+        if let Some(ref user) = options.user {
+            args.push("--user".to_string());
+            args.push(user.clone());
+        }
+        args.push("--exec".to_string());
+        args.push("/bin/bash".to_string());
+
+        // The Linux cwd is passed via --cd; wsl.exe itself needs a Windows directory
+        let pty_options = CreatePtyOptions {
+            id: options.id.clone(),
+            cwd: std::env::var("USERPROFILE").unwrap_or_else(|_| "C:\\".to_string()),
+            ..Default::default()
+        };
+
+        self.create_with_portable_pty(&pty_options, "wsl.exe", &args, &Self::create_utf8_env())?;
+        println!("Created WSL terminal {} ({})", options.id, options.distro);
+        Ok(true)
+    }
+
+    /// WSL sessions need wsl.exe, which only exists on Windows
+    #[cfg(not(target_os = "windows"))]
+    pub fn create_wsl(&self, _options: CreateWslOptions) -> Result<bool, String> {
+        Err("WSL sessions are only available on Windows".to_string())
+    }
+
+    fn create_with_portable_pty(
+        &self,
+        options: &CreatePtyOptions,
+        shell: &str,
+        args: &[String],
+        env_vars: &HashMap<String, String>,
+    ) -> Result<(), String> {
+        self.ensure_capacity()?;
+        let pty_system = native_pty_system();
+
+        // Use the caller's size when known so the first output doesn't reflow.
+        // Otherwise fall back to 24x80 (classic VT100 size), which reduces visual
+        // glitches until the frontend sends the actual size via resize()
+        let pair = pty_system
+            .openpty(PtySize {
+                rows: options.rows.filter(|&rows| rows > 0).unwrap_or(24),
+                cols: options.cols.filter(|&cols| cols > 0).unwrap_or(80),
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .map_err(|e| format!("Failed to open pty: {}", e))?;
+
+        let mut cmd = CommandBuilder::new(shell);
+        cmd.args(args);
+        cmd.cwd(&options.cwd);
+
+        let (merged_env, unset_env) = merge_env(env_vars.clone(), options.env.as_ref());
+        for (key, value) in &merged_env {
+            cmd.env(key, value);
+        }
+        for key in &unset_env {
+            cmd.env_remove(key);
+        }
+
+        let mut child = pair
+            .slave
+            .spawn_command(cmd)
+            .map_err(|e| format!("Failed to spawn command: {}", e))?;
+        let pid = child.process_id();
+
+        // Get reader from master BEFORE taking writer
+        let reader = pair
+            .master
+            .try_clone_reader()
+            .map_err(|e| format!("Failed to clone reader: {}", e))?;
+
+        // Get writer from master
+        let writer = pair
+            .master
+            .take_writer()
+            .map_err(|e| format!("Failed to take writer: {}", e))?;
+
+        let writer_arc = Arc::new(Mutex::new(writer as Box<dyn Write + Send>));
+
+        // IMPORTANT: Keep master alive to prevent pty from closing
+        let master_arc = Arc::new(Mutex::new(pair.master));
+
+        // Spawn reader thread
+        let id = options.id.clone();
+        let app_handle = self.app_handle.clone();
+        let first_output_received = Arc::new(AtomicBool::new(false));
+        let first_output_reader = first_output_received.clone();
+        let scrollback = Scrollback::shared(options.scrollback_lines, options.scrollback_bytes);
+        let scrollback_reader = scrollback.clone();
+        let binary = options.binary;
+        let cwd = Arc::new(Mutex::new(options.cwd.clone()));
+        let cwd_reader = cwd.clone();
+        let title = Arc::new(Mutex::new(None));
+        let title_reader = title.clone();
+        let last_activity = Arc::new(Mutex::new(std::time::Instant::now()));
+        let activity_reader = last_activity.clone();
+        let log: SharedLog = Arc::new(Mutex::new(None));
+        let log_reader = log.clone();
+        let mut bells = BellDebouncer::default();
+        let mut hyperlinks = HyperlinkTracker::default();
+        let allow_clipboard_write = options.allow_clipboard_write;
+        let flush_interval = std::time::Duration::from_millis(
+            options.output_flush_ms.unwrap_or(DEFAULT_OUTPUT_FLUSH_MS),
+        );
+        let max_batch_bytes = options
+            .output_max_batch_bytes
+            .unwrap_or(DEFAULT_OUTPUT_MAX_BATCH_BYTES)
+            .max(1);
+
+        // The raw reader only moves bytes off the PTY; the thread below coalesces
+        // them so fast producers (`yes`, builds) don't emit thousands of tiny events
+        let (chunk_tx, chunks) = std::sync::mpsc::sync_channel::<Vec<u8>>(64);
+        // Larger buffer to reduce ANSI sequence fragmentation
+        // Claude Code and other TUI apps emit many escape sequences
+        let mut buf = read_buffer(options.read_buffer_size, 16384);
+        thread::spawn(move || {
+            let mut buf_reader = BufReader::new(reader);
+
+            loop {
+                match buf_reader.read(&mut buf) {
+                    Ok(0) => break, // EOF
+                    Ok(n) => {
+                        if chunk_tx.send(buf[..n].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Read error: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+
+        thread::spawn(move || {
+            // Pending incomplete UTF-8/ANSI bytes from previous batch
+            let mut pending: Vec<u8> = Vec::new();
+
+            // While bells are held back, wake up when they're due even if the
+            // terminal has gone quiet
+            while let Some(batch) =
+                next_output_batch(&chunks, flush_interval, max_batch_bytes, bells.wait())
+            {
+                if batch.is_empty() {
+                    if let Some(count) = bells.take_due() {
+                        let _ = app_handle.emit("pty:bell", (&id, count));
+                    }
+                    continue;
+                }
+                *activity_reader.lock() = std::time::Instant::now();
+                match batch.len() {
+                    n if binary => {
+                        // Raw mode: pass bytes through untouched, the frontend decodes
+                        let encoded =
+                            record_binary_output(&scrollback_reader, &log_reader, &batch[..n]);
+                        let _ = app_handle.emit("pty:output-bytes", (&id, &encoded));
+                        first_output_reader.store(true, Ordering::SeqCst);
+                    }
+                    n => {
+                        // Combine pending bytes with new data
+                        let mut data_bytes = std::mem::take(&mut pending);
+                        data_bytes.extend_from_slice(&batch[..n]);
+
+                        // Find the safe boundary for both UTF-8 and ANSI sequences
+                        // Use the minimum to ensure neither is split
+                        let utf8_bound = find_utf8_boundary(&data_bytes);
+                        let ansi_bound = find_ansi_boundary(&data_bytes[..utf8_bound]);
+                        let valid_len = ansi_bound;
+
+                        if valid_len > 0 {
+                            let data = String::from_utf8_lossy(&data_bytes[..valid_len]).to_string();
+                            scrollback_reader.lock().push(&data);
+                            append_log(&log_reader, data.as_bytes());
+                            let _ = app_handle.emit("pty:output", (&id, &data));
+                            first_output_reader.store(true, Ordering::SeqCst);
+
+                            // Bells stay in the output; the event is debounced so a
+                            // program spamming BEL doesn't flood IPC
+                            if data.contains('\x07') {
+                                bells.ring(count_bells(&data));
+                            }
+                            if let Some(count) = bells.take_due() {
+                                let _ = app_handle.emit("pty:bell", (&id, count));
+                            }
+
+                            for (url, text, start, end) in hyperlinks.feed(&data) {
+                                let event = PtyHyperlinkEvent {
+                                    id: id.clone(),
+                                    url,
+                                    text,
+                                    start,
+                                    end,
+                                };
+                                let _ = app_handle.emit("pty:hyperlink", &event);
+                            }
+
+                            if data.contains("\x1b]") {
+                                for (code, payload) in parse_osc_sequences(&data) {
+                                    match code {
+                                        // Window/icon title
+                                        0 | 2 => {
+                                            *title_reader.lock() = Some(payload.clone());
+                                            let _ = app_handle.emit("pty:title", (&id, &payload));
+                                        }
+                                        // Current directory
+                                        7 => {
+                                            let Some(path) = parse_osc7_path(&payload) else {
+                                                continue;
+                                            };
+                                            let mut cwd = cwd_reader.lock();
+                                            if *cwd != path {
+                                                cwd.clone_from(&path);
+                                                let _ = app_handle.emit("pty:cwd-changed", (&id, &path));
+                                            }
+                                        }
+                                        // Clipboard write, only when the session allows it
+                                        52 if allow_clipboard_write => {
+                                            if let Some(text) = parse_osc52(&payload) {
+                                                let _ = app_handle.emit("pty:clipboard-set", (&id, &text));
+                                            }
+                                        }
+                                        _ => {}
+                                    }
+                                }
+                            }
+                        }
+
+                        // Keep incomplete bytes for next iteration
+                        if valid_len < data_bytes.len() {
+                            pending = data_bytes[valid_len..].to_vec();
+                        }
+                    }
+                }
+            }
+
+            // Flush any remaining pending bytes
+            if !pending.is_empty() {
+                let data = String::from_utf8_lossy(&pending).to_string();
+                scrollback_reader.lock().push(&data);
+                append_log(&log_reader, data.as_bytes());
+                let _ = app_handle.emit("pty:output", (&id, &data));
+            }
+        });
+
+        // Spawn exit monitor thread
+        let id_exit = options.id.clone();
+        let app_handle_exit = self.app_handle.clone();
+        let instances = self.instances.clone();
+
+        let exit = exit_channel();
+        let exit_thread = exit.clone();
+        let exit_reason = Arc::new(Mutex::new(None));
+        let exit_reason_thread = exit_reason.clone();
+
+        thread::spawn(move || {
+            let mut event = match child.wait() {
+                Ok(status) => PtyExitEvent::from_portable(&id_exit, &status),
+                Err(_) => PtyExitEvent::unknown(&id_exit),
+            };
+            event.reason = exit_reason_thread.lock().take();
+
+            let _ = app_handle_exit.emit("pty:exit", &event);
+            exit_thread.send_replace(Some(event));
+            instances.lock().remove(&id_exit);
+        });
+
+        self.instances.lock().insert(
+            options.id.clone(),
+            PtyInstance {
+                writer: writer_arc,
+                cwd,
+                title,
+                shell: shell.to_string(),
+                shell_version: None,
+                read_only: options.read_only,
+                uses_pty: true,
+                master: Some(master_arc), // Store master to keep it alive
+                child_handle: None,
+                tail_stop: None,
+                first_output_received: first_output_received.clone(),
+                scrollback,
+                input_line: String::new(),
+                last_command: Arc::new(Mutex::new(None)),
+                spawn: Some(SpawnConfig {
+                    options: options.clone(),
+                    args: args.to_vec(),
+                    env_vars: env_vars.clone(),
+                }),
+                pid,
+                exit,
+                pending_resize: Arc::default(),
+                resize_debounce: std::time::Duration::from_millis(
+                    options.resize_debounce_ms.unwrap_or(DEFAULT_RESIZE_DEBOUNCE_MS),
+                ),
+                requested_size: Mutex::new(options.cols.zip(options.rows)),
+                input_throttle: InputThrottle::from_options(options),
+                last_activity: last_activity.clone(),
+                exit_reason,
+                log,
+                broadcast_group: None,
+            },
+        );
+
+        if let Some(timeout_ms) = options.startup_timeout_ms {
+            self.spawn_startup_timer(options.id.clone(), first_output_received, timeout_ms);
+        }
+        if let Some(idle_secs) = options.idle_timeout_secs {
+            self.spawn_idle_watchdog(options.id.clone(), last_activity, idle_secs);
+        }
+
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    fn create_with_fallback(
+        &self,
+        options: &CreatePtyOptions,
+        shell: &str,
+        args: &[String],
+        env_vars: &HashMap<String, String>,
+    ) -> Result<(), String> {
+        // Pipes are read as text below; there is no raw byte stream to forward
+        if options.binary {
+            return Err("Binary output is not supported in fallback mode".to_string());
+        }
+        self.ensure_capacity()?;
+        let mut cmd = Command::new(shell);
+        cmd.args(args);
+        cmd.current_dir(&options.cwd);
+        cmd.stdin(Stdio::piped());
+        cmd.stdout(Stdio::piped());
+        cmd.stderr(Stdio::piped());
+
+        let (merged_env, unset_env) = merge_env(env_vars.clone(), options.env.as_ref());
+        for (key, value) in &merged_env {
+            cmd.env(key, value);
+        }
+        for key in &unset_env {
+            cmd.env_remove(key);
+        }
+
+        // For PowerShell, add UTF-8 encoding command
+        #[cfg(target_os = "windows")]
+        if shell.contains("powershell") {
+            cmd.args(&[
+                "-NoExit",
+                "-Command",
+                "[Console]::OutputEncoding = [System.Text.Encoding]::UTF8; \
+                 [Console]::InputEncoding = [System.Text.Encoding]::UTF8; \
+                 $OutputEncoding = [System.Text.Encoding]::UTF8",
+            ]);
+        }
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| format!("Failed to spawn process: {}", e))?;
+
+        // Take stdin
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| "Failed to take stdin".to_string())?;
+
+        // Create writer wrapper for stdin
+        struct StdinWriter(std::process::ChildStdin);
+        impl Write for StdinWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.0.flush()
+            }
+        }
+        // Make StdinWriter Send
+        unsafe impl Send for StdinWriter {}
+
+        let writer_arc = Arc::new(Mutex::new(Box::new(StdinWriter(stdin)) as Box<dyn Write + Send>));
+
+        // Capture stdout
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| "Failed to take stdout".to_string())?;
+        let id_stdout = options.id.clone();
+        let app_handle_stdout = self.app_handle.clone();
+        let first_output_received = Arc::new(AtomicBool::new(false));
+        let first_output_stdout = first_output_received.clone();
+        let scrollback = Scrollback::shared(options.scrollback_lines, options.scrollback_bytes);
+        let scrollback_stdout = scrollback.clone();
+        let last_activity = Arc::new(Mutex::new(std::time::Instant::now()));
+        let activity_stdout = last_activity.clone();
+        let log: SharedLog = Arc::new(Mutex::new(None));
+        let log_stdout = log.clone();
+
+        let mut buf = read_buffer(options.read_buffer_size, 4096);
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+            let mut pending: Vec<u8> = Vec::new();
+
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        *activity_stdout.lock() = std::time::Instant::now();
+                        let data = decode_utf8_chunk(&mut pending, &buf[..n]);
+                        if data.is_empty() {
+                            continue;
+                        }
+                        scrollback_stdout.lock().push(&data);
+                        append_log(&log_stdout, data.as_bytes());
+                        let _ = app_handle_stdout.emit("pty:output", (&id_stdout, &data));
+                        first_output_stdout.store(true, Ordering::SeqCst);
+                    }
+                    Err(_) => break,
+                }
+            }
+
+            // Don't lose a truncated sequence at the end of the stream
             if !pending.is_empty() {
-                let data = String::from_utf8_lossy(&pending).to_string();
-                let _ = app_handle.emit("pty:output", (&id, &data));
+                let data = flush_utf8_pending(&mut pending);
+                scrollback_stdout.lock().push(&data);
+                append_log(&log_stdout, data.as_bytes());
+                let _ = app_handle_stdout.emit("pty:output", (&id_stdout, &data));
+            }
+        });
+
+        // Capture stderr
+        let stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| "Failed to take stderr".to_string())?;
+        let id_stderr = options.id.clone();
+        let app_handle_stderr = self.app_handle.clone();
+        let scrollback_stderr = scrollback.clone();
+        let activity_stderr = last_activity.clone();
+        let log_stderr = log.clone();
+
+        let mut buf = read_buffer(options.read_buffer_size, 4096);
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stderr);
+            let mut pending: Vec<u8> = Vec::new();
+
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        *activity_stderr.lock() = std::time::Instant::now();
+                        let data = decode_utf8_chunk(&mut pending, &buf[..n]);
+                        if data.is_empty() {
+                            continue;
+                        }
+                        scrollback_stderr.lock().push(&data);
+                        append_log(&log_stderr, data.as_bytes());
+                        let _ = app_handle_stderr.emit("pty:output", (&id_stderr, &data));
+                    }
+                    Err(_) => break,
+                }
+            }
+
+            // Don't lose a truncated sequence at the end of the stream
+            if !pending.is_empty() {
+                let data = flush_utf8_pending(&mut pending);
+                scrollback_stderr.lock().push(&data);
+                append_log(&log_stderr, data.as_bytes());
+                let _ = app_handle_stderr.emit("pty:output", (&id_stderr, &data));
+            }
+        });
+
+        // Send initial message
+        let _ = self.app_handle.emit(
+            "pty:output",
+            (&options.id, "[Terminal - fallback mode]\r\n"),
+        );
+
+        let pid = Some(child.id());
+        let child_arc = Arc::new(Mutex::new(child));
+
+        // Spawn exit monitor
+        let id_exit = options.id.clone();
+        let app_handle_exit = self.app_handle.clone();
+        let instances = self.instances.clone();
+        let child_clone = child_arc.clone();
+
+        let exit = exit_channel();
+        let exit_thread = exit.clone();
+        let exit_reason = Arc::new(Mutex::new(None));
+        let exit_reason_thread = exit_reason.clone();
+
+        thread::spawn(move || {
+            let mut event = match child_clone.lock().wait() {
+                Ok(status) => PtyExitEvent::from_std(&id_exit, &status),
+                Err(_) => PtyExitEvent::unknown(&id_exit),
+            };
+            event.reason = exit_reason_thread.lock().take();
+
+            let _ = app_handle_exit.emit("pty:exit", &event);
+            exit_thread.send_replace(Some(event));
+            instances.lock().remove(&id_exit);
+        });
+
+        self.instances.lock().insert(
+            options.id.clone(),
+            PtyInstance {
+                writer: writer_arc,
+                cwd: Arc::new(Mutex::new(options.cwd.clone())),
+                title: Arc::new(Mutex::new(None)),
+                shell: shell.to_string(),
+                shell_version: None,
+                read_only: options.read_only,
+                uses_pty: false,
+                master: None,
+                child_handle: Some(child_arc),
+                tail_stop: None,
+                first_output_received: first_output_received.clone(),
+                scrollback,
+                input_line: String::new(),
+                last_command: Arc::new(Mutex::new(None)),
+                spawn: Some(SpawnConfig {
+                    options: options.clone(),
+                    args: args.to_vec(),
+                    env_vars: env_vars.clone(),
+                }),
+                pid,
+                exit,
+                pending_resize: Arc::default(),
+                resize_debounce: std::time::Duration::from_millis(
+                    options.resize_debounce_ms.unwrap_or(DEFAULT_RESIZE_DEBOUNCE_MS),
+                ),
+                requested_size: Mutex::new(options.cols.zip(options.rows)),
+                input_throttle: InputThrottle::from_options(options),
+                last_activity: last_activity.clone(),
+                exit_reason,
+                log,
+                broadcast_group: None,
+            },
+        );
+
+        if let Some(timeout_ms) = options.startup_timeout_ms {
+            self.spawn_startup_timer(options.id.clone(), first_output_received, timeout_ms);
+        }
+        if let Some(idle_secs) = options.idle_timeout_secs {
+            self.spawn_idle_watchdog(options.id.clone(), last_activity, idle_secs);
+        }
+
+        println!("Created terminal using child_process fallback");
+        Ok(())
+    }
+
+    /// Stream appended content of a file as `pty:output` events, like `tail -f`.
+    /// No shell is spawned; writes are discarded and `kill(id)` stops the tail.
+    pub fn tail_file(&self, id: String, file_path: String) -> Result<(), String> {
+        use notify::{RecursiveMode, Watcher};
+        use std::io::{Seek, SeekFrom};
+
+        if self.instances.lock().contains_key(&id) {
+            return Err(format!("PTY instance {} already exists", id));
+        }
+        self.ensure_capacity()?;
+
+        let path = std::path::PathBuf::from(&file_path);
+        let mut file =
+            std::fs::File::open(&path).map_err(|e| format!("Failed to open {}: {}", file_path, e))?;
+        let mut position = file
+            .seek(SeekFrom::End(0))
+            .map_err(|e| format!("Failed to seek {}: {}", file_path, e))?;
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(tx).map_err(|e| format!("Failed to create file watcher: {}", e))?;
+        watcher
+            .watch(&path, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Failed to watch {}: {}", file_path, e))?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_thread = stop.clone();
+        let app_handle = self.app_handle.clone();
+        let instances = self.instances.clone();
+        let id_thread = id.clone();
+        let scrollback = Scrollback::shared(None, None);
+        let scrollback_thread = scrollback.clone();
+        let exit = exit_channel();
+        let exit_thread = exit.clone();
+
+        thread::spawn(move || {
+            // Keep the watcher alive for the lifetime of the thread
+            let _watcher = watcher;
+            let mut buf = [0u8; 16384];
+            let mut pending: Vec<u8> = Vec::new();
+
+            while !stop_thread.load(Ordering::SeqCst) {
+                match rx.recv_timeout(std::time::Duration::from_millis(250)) {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => {
+                        eprintln!("Tail watch error: {}", e);
+                        continue;
+                    }
+                    Err(std::sync::mpsc::RecvTimeoutError::Timeout) => continue,
+                    Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+                }
+
+                // File was truncated (e.g. log rotation with copytruncate): start over
+                let len = file.metadata().map(|m| m.len()).unwrap_or(position);
+                if len < position {
+                    position = file.seek(SeekFrom::Start(0)).unwrap_or(0);
+                    pending.clear();
+                }
+
+                loop {
+                    match file.read(&mut buf) {
+                        Ok(0) => break,
+                        Ok(n) => {
+                            position += n as u64;
+                            let data = decode_utf8_chunk(&mut pending, &buf[..n]);
+                            if !data.is_empty() {
+                                scrollback_thread.lock().push(&data);
+                                let _ = app_handle.emit("pty:output", (&id_thread, &data));
+                            }
+                        }
+                        Err(e) => {
+                            eprintln!("Tail read error: {}", e);
+                            break;
+                        }
+                    }
+                }
+            }
+
+            // A read that stops mid-file can still complete a held-back sequence,
+            // so only flush it once the tail has ended
+            if !pending.is_empty() {
+                let data = flush_utf8_pending(&mut pending);
+                scrollback_thread.lock().push(&data);
+                let _ = app_handle.emit("pty:output", (&id_thread, &data));
+            }
+
+            // Only clean up if we weren't killed (kill already removed the entry)
+            if !stop_thread.load(Ordering::SeqCst) {
+                let event = PtyExitEvent {
+                    id: id_thread.clone(),
+                    exit_code: Some(0),
+                    signal: None,
+                    signal_number: None,
+                    core_dumped: false,
+                    success: true,
+                    reason: None,
+                };
+                let _ = app_handle.emit("pty:exit", &event);
+                exit_thread.send_replace(Some(event));
+                instances.lock().remove(&id_thread);
+            }
+        });
+
+        self.instances.lock().insert(
+            id,
+            PtyInstance {
+                writer: Arc::new(Mutex::new(Box::new(std::io::sink()) as Box<dyn Write + Send>)),
+                cwd: Arc::new(Mutex::new(
+                    path.parent()
+                        .map(|p| p.to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                )),
+                title: Arc::new(Mutex::new(None)),
+                shell: String::new(),
+                shell_version: None,
+                read_only: true,
+                uses_pty: false,
+                master: None,
+                child_handle: None,
+                tail_stop: Some(stop),
+                // Tails have no shell startup to wait for
+                first_output_received: Arc::new(AtomicBool::new(true)),
+                scrollback,
+                input_line: String::new(),
+                last_command: Arc::new(Mutex::new(None)),
+                spawn: None,
+                pid: None,
+                exit,
+                pending_resize: Arc::default(),
+                resize_debounce: std::time::Duration::ZERO,
+                requested_size: Mutex::new(None),
+                input_throttle: None,
+                last_activity: Arc::new(Mutex::new(std::time::Instant::now())),
+                exit_reason: Arc::new(Mutex::new(None)),
+                log: Arc::new(Mutex::new(None)),
+                broadcast_group: None,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Large paste chunking threshold (bytes)
+    /// Pastes larger than this will be split into chunks to prevent terminal overwhelm
+    const CHUNK_SIZE: usize = 2048;
+    /// Delay between chunks (microseconds) - 10ms
+    const CHUNK_DELAY_US: u64 = 10_000;
+
+    /// Write data to PTY with automatic chunking for large pastes
+    ///
+    /// Differentiated from Tony's JS approach:
+    /// - Chunking happens in Rust, avoiding multiple IPC round-trips
+    /// - Native thread sleep for precise timing
+    /// - Single IPC call from frontend, chunking is transparent
+    pub fn write(&self, id: String, data: String) -> Result<(), String> {
+        let mut instances = self.instances.lock();
+        let instance = instances
+            .get_mut(&id)
+            .ok_or_else(|| "PTY instance not found".to_string())?;
+        if instance.read_only {
+            return Err("PTY is read-only".to_string());
+        }
+
+        for line in track_input(&mut instance.input_line, &data) {
+            if let Some(command) = parse_command_line(&line) {
+                *instance.last_command.lock() = Some(command.clone());
+                let _ = self
+                    .app_handle
+                    .emit("pty:command-started", &PtyCommandEvent { id: id.clone(), command });
+            }
+        }
+
+        let group = instance.broadcast_group.clone();
+        Self::send_input(instance, data.as_bytes())?;
+
+        // Fan out to the rest of the group; one failing member doesn't fail the write
+        if let Some(group) = group {
+            for (member_id, member) in instances.iter() {
+                if *member_id == id || member.read_only || member.broadcast_group.as_ref() != Some(&group) {
+                    continue;
+                }
+                if let Err(e) = Self::send_input(member, data.as_bytes()) {
+                    eprintln!("Broadcast to {} failed: {}", member_id, e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Join a broadcast group, or leave it with None
+    pub fn set_broadcast_group(&self, id: String, group: Option<String>) -> Result<(), String> {
+        let mut instances = self.instances.lock();
+        let instance = instances
+            .get_mut(&id)
+            .ok_or_else(|| "PTY instance not found".to_string())?;
+        instance.broadcast_group = group.filter(|g| !g.is_empty());
+        Ok(())
+    }
+
+    /// All broadcast groups with their members, sorted by name
+    pub fn list_broadcast_groups(&self) -> Vec<PtyBroadcastGroup> {
+        let mut groups: std::collections::BTreeMap<String, Vec<String>> = std::collections::BTreeMap::new();
+        for (id, instance) in self.instances.lock().iter() {
+            if let Some(ref group) = instance.broadcast_group {
+                groups.entry(group.clone()).or_default().push(id.clone());
+            }
+        }
+        groups
+            .into_iter()
+            .map(|(name, mut members)| {
+                members.sort();
+                PtyBroadcastGroup { name, members }
+            })
+            .collect()
+    }
+
+    /// Write raw bytes to PTY, unlike `write` the data needn't be UTF-8
+    pub fn write_bytes(&self, id: String, data: Vec<u8>) -> Result<(), String> {
+        let instances = self.instances.lock();
+        let instance = instances
+            .get(&id)
+            .ok_or_else(|| "PTY instance not found".to_string())?;
+        if instance.read_only {
+            return Err("PTY is read-only".to_string());
+        }
+
+        Self::send_input(instance, &data)
+    }
+
+    /// Paste text, wrapped in bracketed-paste markers unless `bracketed` is false.
+    /// Large pastes go out in chunks like any other write.
+    pub fn paste(&self, id: String, data: String, bracketed: bool) -> Result<(), String> {
+        let instances = self.instances.lock();
+        let instance = instances
+            .get(&id)
+            .ok_or_else(|| "PTY instance not found".to_string())?;
+        if instance.read_only {
+            return Err("PTY is read-only".to_string());
+        }
+
+        Self::send_input(instance, &paste_payload(&data, bracketed))
+    }
+
+    /// Write input now, or queue it behind earlier input when throttled
+    fn send_input(instance: &PtyInstance, bytes: &[u8]) -> Result<(), String> {
+        *instance.last_activity.lock() = std::time::Instant::now();
+        Self::send_to(instance.input_throttle.as_ref(), &instance.writer, bytes)
+    }
+
+    /// `send_input` for a cloned writer/throttle pair, used by writes that run off the instances lock
+    fn send_to(
+        throttle: Option<&Arc<Mutex<InputThrottle>>>,
+        writer: &Arc<Mutex<Box<dyn Write + Send>>>,
+        bytes: &[u8],
+    ) -> Result<(), String> {
+        let Some(throttle) = throttle else {
+            return Self::write_bytes_to(writer, bytes);
+        };
+
+        let mut state = throttle.lock();
+        state.queue.extend(bytes);
+        if !state.draining {
+            state.draining = true;
+            Self::spawn_input_drain(throttle.clone(), writer.clone());
+        }
+        Ok(())
+    }
+
+    /// Drain a throttled input queue, one window's worth at a time
+    fn spawn_input_drain(
+        throttle: Arc<Mutex<InputThrottle>>,
+        writer: Arc<Mutex<Box<dyn Write + Send>>>,
+    ) {
+        thread::spawn(move || loop {
+            let now = std::time::Instant::now();
+            let (batch, wait) = {
+                let mut state = throttle.lock();
+                let batch = state.next_batch(now);
+                if batch.is_empty() && state.queue.is_empty() {
+                    state.draining = false;
+                    return;
+                }
+                (batch, state.until_next_window(now))
+            };
+
+            if !batch.is_empty() {
+                let mut writer_lock = writer.lock();
+                if writer_lock.write_all(&batch).is_err() || writer_lock.flush().is_err() {
+                    eprintln!("Throttled write failed, dropping queued input");
+                    let mut state = throttle.lock();
+                    state.queue.clear();
+                    state.draining = false;
+                    return;
+                }
+            }
+            if !wait.is_zero() {
+                thread::sleep(wait);
+            }
+        });
+    }
+
+    /// Shared write/flush logic for `write` and `write_bytes`
+    fn write_bytes_to(writer: &Mutex<Box<dyn Write + Send>>, bytes: &[u8]) -> Result<(), String> {
+        let mut writer_lock = writer.lock();
+
+        // If data is small, write directly
+        if bytes.len() <= Self::CHUNK_SIZE {
+            writer_lock
+                .write_all(bytes)
+                .map_err(|e| format!("Failed to write: {}", e))?;
+            writer_lock
+                .flush()
+                .map_err(|e| format!("Failed to flush: {}", e))?;
+            return Ok(());
+        }
+
+        // Large paste: chunk it to prevent terminal overwhelm
+        // This is especially important for TUI apps like vim, Claude Code
+        for chunk in bytes.chunks(Self::CHUNK_SIZE) {
+            writer_lock
+                .write_all(chunk)
+                .map_err(|e| format!("Failed to write chunk: {}", e))?;
+            writer_lock
+                .flush()
+                .map_err(|e| format!("Failed to flush chunk: {}", e))?;
+
+            // Small delay between chunks to let terminal process
+            // Using sleep here is acceptable since we're in a locked context
+            // and this only happens for large pastes (rare event)
+            if chunk.len() == Self::CHUNK_SIZE {
+                std::thread::sleep(std::time::Duration::from_micros(Self::CHUNK_DELAY_US));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write raw bytes in `chunk_size` pieces, pausing briefly between chunks
+    /// so the reader thread isn't starved while a large payload drains
+    pub fn write_chunked(&self, id: String, data: Vec<u8>, chunk_size: usize) -> Result<(), String> {
+        if chunk_size == 0 {
+            return Err("Chunk size must be greater than zero".to_string());
+        }
+
+        // Clone the writer so the instances lock isn't held for the whole transfer
+        let (writer, throttle) = {
+            let instances = self.instances.lock();
+            let instance = instances
+                .get(&id)
+                .ok_or_else(|| "PTY instance not found".to_string())?;
+            if instance.read_only {
+                return Err("PTY is read-only".to_string());
+            }
+            *instance.last_activity.lock() = std::time::Instant::now();
+            (instance.writer.clone(), instance.input_throttle.clone())
+        };
+
+        Self::write_chunks_to(throttle.as_ref(), &writer, &data, chunk_size)
+    }
+
+    /// Write `data` in `chunk_size` pieces. A throttled terminal queues it behind
+    /// earlier input instead, and the throttle does the pacing.
+    fn write_chunks_to(
+        throttle: Option<&Arc<Mutex<InputThrottle>>>,
+        writer: &Arc<Mutex<Box<dyn Write + Send>>>,
+        data: &[u8],
+        chunk_size: usize,
+    ) -> Result<(), String> {
+        if throttle.is_some() {
+            return Self::send_to(throttle, writer, data);
+        }
+
+        let mut writer_lock = writer.lock();
+        let chunk_count = data.len().div_ceil(chunk_size);
+        for (i, chunk) in data.chunks(chunk_size).enumerate() {
+            writer_lock
+                .write_all(chunk)
+                .map_err(|e| format!("Failed to write chunk: {}", e))?;
+            writer_lock
+                .flush()
+                .map_err(|e| format!("Failed to flush chunk: {}", e))?;
+
+            if i + 1 < chunk_count {
+                thread::sleep(std::time::Duration::from_micros(100));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write scripted input with a delay before each chunk (e.g. for demo recordings)
+    ///
+    /// Runs on a background thread and returns immediately. The sequence stops
+    /// early when `cancel_delayed_write` is called with the same token or the
+    /// PTY goes away.
+    pub fn write_with_delay(
+        &self,
+        id: String,
+        chars: Vec<(u64, String)>,
+        cancel_token: String,
+    ) -> Result<(), String> {
+        match self.instances.lock().get(&id) {
+            None => return Err("PTY instance not found".to_string()),
+            Some(instance) if instance.read_only => return Err("PTY is read-only".to_string()),
+            Some(_) => {}
+        }
+
+        let cancelled = Arc::new(CancelSignal::default());
+        if let Some(previous) = self
+            .delayed_writes
+            .lock()
+            .insert(cancel_token.clone(), cancelled.clone())
+        {
+            // Reusing a token replaces the sequence still running under it
+            previous.cancel();
+        }
+
+        let instances = self.instances.clone();
+        let delayed_writes = self.delayed_writes.clone();
+
+        thread::spawn(move || {
+            for (delay_ms, text) in chars {
+                // Cancelling wakes the wait, so long delays stop promptly
+                if cancelled.wait(std::time::Duration::from_millis(delay_ms)) {
+                    break;
+                }
+
+                // Clone the writer so the instances lock isn't held while writing
+                let (writer, throttle) = match instances.lock().get(&id) {
+                    Some(instance) if !instance.read_only => {
+                        *instance.last_activity.lock() = std::time::Instant::now();
+                        (instance.writer.clone(), instance.input_throttle.clone())
+                    }
+                    _ => break,
+                };
+                if Self::send_to(throttle.as_ref(), &writer, text.as_bytes()).is_err() {
+                    eprintln!("Delayed write to {} failed, stopping sequence", id);
+                    break;
+                }
+            }
+
+            let mut delayed_writes = delayed_writes.lock();
+            if delayed_writes
+                .get(&cancel_token)
+                .map(|flag| Arc::ptr_eq(flag, &cancelled))
+                .unwrap_or(false)
+            {
+                delayed_writes.remove(&cancel_token);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Cancel a delayed write sequence; returns false if the token is unknown
+    pub fn cancel_delayed_write(&self, cancel_token: String) -> bool {
+        match self.delayed_writes.lock().remove(&cancel_token) {
+            Some(cancelled) => {
+                cancelled.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Resize PTY; fallback instances only record the size and emit `pty:resize-unsupported`
+    /// Rapid calls for the same id are collapsed; only the latest size is
+    /// applied once the instance's debounce period passes without another call.
+    pub fn resize(&self, id: String, cols: u16, rows: u16) -> Result<(), String> {
+        let size = PtySize {
+            rows,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        };
+
+        let instances = self.instances.lock();
+        let Some(inst) = instances.get(&id) else {
+            return Ok(());
+        };
+
+        if inst.resize_debounce.is_zero() {
+            Self::apply_resize(&self.app_handle, &id, inst, size);
+            return Ok(());
+        }
+
+        let deadline = std::time::Instant::now() + inst.resize_debounce;
+        if !inst.pending_resize.lock().schedule(size, deadline) {
+            return Ok(()); // The running timer picks up the new size and deadline
+        }
+
+        let pending = inst.pending_resize.clone();
+        let instances = self.instances.clone();
+        let app_handle = self.app_handle.clone();
+        thread::spawn(move || {
+            let size = loop {
+                let poll = pending.lock().poll(std::time::Instant::now());
+                match poll {
+                    ControlFlow::Continue(wait) => thread::sleep(wait),
+                    ControlFlow::Break(Some(size)) => break size,
+                    ControlFlow::Break(None) => return,
+                }
+            };
+            let instances = instances.lock();
+            // The id may have been reused by a new session in the meantime
+            if let Some(inst) = instances.get(&id) {
+                if Arc::ptr_eq(&inst.pending_resize, &pending) {
+                    Self::apply_resize(&app_handle, &id, inst, size);
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Resize every PTY to the same size; returns how many were resized.
+    /// All resizes happen under a single hold of the instances lock.
+    pub fn resize_all(&self, cols: u16, rows: u16) -> Result<usize, String> {
+        self.resize_all_with_pixel(cols, rows, 0, 0)
+    }
+
+    /// Pixel-aware variant of `resize_all`
+    pub fn resize_all_with_pixel(
+        &self,
+        cols: u16,
+        rows: u16,
+        pixel_width: u16,
+        pixel_height: u16,
+    ) -> Result<usize, String> {
+        let size = PtySize {
+            rows,
+            cols,
+            pixel_width,
+            pixel_height,
+        };
+
+        let instances = self.instances.lock();
+        Ok(instances
+            .values()
+            .filter(|inst| Self::resize_instance(inst, size))
+            .count())
+    }
+
+    /// Resize one instance, telling the frontend when the size can't be applied
+    fn apply_resize(app_handle: &AppHandle, id: &str, inst: &PtyInstance, size: PtySize) {
+        if !Self::resize_instance(inst, size) && !inst.uses_pty {
+            // Fallback mode talks to the child over pipes, there is no console to resize
+            let _ = app_handle.emit("pty:resize-unsupported", (id, size.cols, size.rows));
+        }
+    }
+
+    /// Resize a single instance; false for fallback instances or on error.
+    /// The size is recorded either way so a fork can start at the right size.
+    fn resize_instance(inst: &PtyInstance, size: PtySize) -> bool {
+        *inst.requested_size.lock() = Some((size.cols, size.rows));
+        if !inst.uses_pty {
+            return false;
+        }
+        match inst.master {
+            Some(ref master) => master.lock().resize(size).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Kill PTY instance
+    pub fn kill(&self, id: String) -> Result<bool, String> {
+        // Release the lock before shutting down, which can block on the child
+        let removed = self.instances.lock().remove(&id);
+
+        if let Some(instance) = removed {
+            Self::shutdown_instance(instance);
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Kill every instance; returns how many were shut down
+    pub fn kill_all(&self) -> usize {
+        let drained: Vec<PtyInstance> = self.instances.lock().drain().map(|(_, inst)| inst).collect();
+        let count = drained.len();
+        for instance in drained {
+            Self::shutdown_instance(instance);
+        }
+        count
+    }
+
+    /// Stop whatever is behind a removed instance
+    fn shutdown_instance(instance: PtyInstance) {
+        if let Some(pid) = instance.pid {
+            if let Err(e) = kill_process_tree(pid) {
+                eprintln!("Failed to kill process tree of {}: {}", pid, e);
+            }
+        }
+        if let Some(child_handle) = instance.child_handle {
+            let _ = child_handle.lock().kill();
+        }
+        if let Some(tail_stop) = instance.tail_stop {
+            tail_stop.store(true, Ordering::SeqCst);
+        }
+        // For PTY, dropping the master will close the connection
+    }
+
+    /// Kill the session once it has seen no input or output for `idle_secs`
+    fn spawn_idle_watchdog(
+        &self,
+        id: String,
+        last_activity: Arc<Mutex<std::time::Instant>>,
+        idle_secs: u64,
+    ) {
+        let instances = self.instances.clone();
+        let timeout = std::time::Duration::from_secs(idle_secs);
+
+        thread::spawn(move || loop {
+            let idle = last_activity.lock().elapsed();
+            if idle < timeout {
+                // Wake up periodically so the thread ends soon after the session does
+                thread::sleep((timeout - idle).min(std::time::Duration::from_secs(5)));
+                let alive = matches!(
+                    instances.lock().get(&id),
+                    Some(inst) if Arc::ptr_eq(&inst.last_activity, &last_activity)
+                );
+                if !alive {
+                    return;
+                }
+                continue;
+            }
+
+            // Only kill the instance this watchdog was started for
+            let instance = {
+                let mut instances = instances.lock();
+                match instances.get(&id) {
+                    Some(inst) if Arc::ptr_eq(&inst.last_activity, &last_activity) => {
+                        instances.remove(&id)
+                    }
+                    _ => None,
+                }
+            };
+            if let Some(instance) = instance {
+                println!("Terminal {} idle for {}s, killing it", id, idle_secs);
+                // The exit monitor reports this in `pty:exit`
+                *instance.exit_reason.lock() = Some(IDLE_TIMEOUT_REASON.to_string());
+                Self::shutdown_instance(instance);
+            }
+            return;
+        });
+    }
+
+    /// Kill the session if no output arrives before the deadline
+    fn spawn_startup_timer(&self, id: String, first_output_received: Arc<AtomicBool>, timeout_ms: u64) {
+        let instances = self.instances.clone();
+        let app_handle = self.app_handle.clone();
+
+        thread::spawn(move || {
+            let deadline = std::time::Instant::now() + std::time::Duration::from_millis(timeout_ms);
+            while std::time::Instant::now() < deadline {
+                if first_output_received.load(Ordering::SeqCst) {
+                    return;
+                }
+                thread::sleep(std::time::Duration::from_millis(50));
+            }
+            if first_output_received.load(Ordering::SeqCst) {
+                return;
+            }
+
+            // Only kill the instance this timer was started for, not a
+            // later session that reused the id
+            let instance = {
+                let mut instances = instances.lock();
+                match instances.get(&id) {
+                    Some(inst) if Arc::ptr_eq(&inst.first_output_received, &first_output_received) => {
+                        instances.remove(&id)
+                    }
+                    _ => None,
+                }
+            };
+
+            if let Some(instance) = instance {
+                eprintln!("PTY {} produced no output within {}ms, killing it", id, timeout_ms);
+                let _ = app_handle.emit("pty:startup-timeout", &id);
+                Self::shutdown_instance(instance);
+            }
+        });
+    }
+
+    /// Restart PTY instance
+    pub fn restart(&self, id: String, cwd: String) -> Result<bool, String> {
+        // Check if instance exists
+        {
+            let instances = self.instances.lock();
+            if !instances.contains_key(&id) {
+                return Err("PTY instance not found".to_string());
+            }
+        }
+
+        self.kill(id.clone())?;
+
+        self.create(CreatePtyOptions {
+            id,
+            cwd,
+            ..Default::default()
+        })
+    }
+
+    /// Start a new PTY running the same shell, arguments and environment as
+    /// `source_id`, in its current directory. Terminals attached to a tmux
+    /// session can't be forked: re-attaching would mirror the same session.
+    pub fn fork(&self, source_id: String, new_id: String) -> Result<bool, String> {
+        let (shell, spawn, cwd, size) = {
+            let instances = self.instances.lock();
+            if instances.contains_key(&new_id) {
+                return Err(format!("PTY instance {} already exists", new_id));
+            }
+            let source = instances
+                .get(&source_id)
+                .ok_or_else(|| "PTY instance not found".to_string())?;
+            let spawn = source
+                .spawn
+                .clone()
+                .ok_or_else(|| "PTY instance has no shell to fork".to_string())?;
+            if let Some(session) = &spawn.options.tmux_session {
+                return Err(format!(
+                    "Cannot fork a terminal attached to tmux session '{}'",
+                    session
+                ));
             }
+            let size = *source.requested_size.lock();
+            (source.shell.clone(), spawn, source.cwd.lock().clone(), size)
+        };
+
+        let mut options = CreatePtyOptions {
+            id: new_id,
+            cwd,
+            ..spawn.options
+        };
+        if let Some((cols, rows)) = size {
+            options.cols = Some(cols);
+            options.rows = Some(rows);
+        }
+        self.create_with_portable_pty(&options, &shell, &spawn.args, &spawn.env_vars)?;
+        println!("Forked terminal {} from {}", options.id, source_id);
+        Ok(true)
+    }
+
+    /// Toggle read-only mode for a PTY
+    pub fn set_read_only(&self, id: String, read_only: bool) -> Result<(), String> {
+        let mut instances = self.instances.lock();
+        let instance = instances
+            .get_mut(&id)
+            .ok_or_else(|| "PTY instance not found".to_string())?;
+        instance.read_only = read_only;
+        Ok(())
+    }
+
+    /// Discard the in-memory scrollback without touching the session
+    pub fn clear_scrollback(&self, id: String) -> Result<(), String> {
+        self.scrollback(&id)?.lock().clear();
+
+        let _ = self.app_handle.emit("pty:scrollback-cleared", &id);
+        Ok(())
+    }
+
+    /// Get the most recent raw output of a PTY (at most `max_bytes`), e.g. to repaint after a reload
+    pub fn get_scrollback(&self, id: String, max_bytes: Option<usize>) -> Result<Vec<u8>, String> {
+        let scrollback = self.scrollback(&id)?;
+        let bytes = scrollback.lock().raw_tail(max_bytes);
+        Ok(bytes)
+    }
+
+    /// Append the session's output to `path`, replacing any log already running.
+    /// With `strip_ansi` the file gets plain text; the live stream is unaffected.
+    pub fn start_logging(&self, id: String, path: String, strip_ansi: bool) -> Result<(), String> {
+        let log = self
+            .instances
+            .lock()
+            .get(&id)
+            .map(|inst| inst.log.clone())
+            .ok_or_else(|| "PTY instance not found".to_string())?;
+
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open log file: {}", e))?;
+        *log.lock() = Some(OutputLog {
+            file,
+            stripper: strip_ansi.then(AnsiStripper::default),
         });
+        Ok(())
+    }
+
+    /// Stop logging; returns false if no log was active
+    pub fn stop_logging(&self, id: String) -> Result<bool, String> {
+        let log = self
+            .instances
+            .lock()
+            .get(&id)
+            .map(|inst| inst.log.clone())
+            .ok_or_else(|| "PTY instance not found".to_string())?;
+        let stopped = log.lock().take().is_some();
+        Ok(stopped)
+    }
+
+    /// Replay the scrollback through the usual output event so a reloaded
+    /// frontend can rebind without restarting the shell.
+    /// Returns false if the instance no longer exists.
+    pub fn reconnect(&self, id: String) -> Result<bool, String> {
+        let (scrollback, binary) = {
+            let instances = self.instances.lock();
+            match instances.get(&id) {
+                Some(inst) => (
+                    inst.scrollback.clone(),
+                    inst.spawn.as_ref().is_some_and(|spawn| spawn.options.binary),
+                ),
+                None => return Ok(false),
+            }
+        };
+
+        let bytes = scrollback.lock().raw_tail(None);
+        if binary {
+            let _ = self.app_handle.emit("pty:output-bytes", (&id, BASE64.encode(&bytes)));
+        } else {
+            // The buffer may have been trimmed mid-character
+            let start = bytes
+                .iter()
+                .position(|b| b & 0xC0 != 0x80)
+                .unwrap_or(bytes.len());
+            let data = String::from_utf8_lossy(&bytes[start..]);
+            let _ = self.app_handle.emit("pty:output", (&id, &data));
+        }
+        Ok(true)
+    }
+
+    /// Get a scrollback line by recency (0 is the most recent completed line)
+    pub fn get_history_line(&self, id: String, index: usize) -> Result<Option<String>, String> {
+        let scrollback = self.scrollback(&id)?;
+        let line = scrollback.lock().line(index);
+        Ok(line)
+    }
+
+    /// Get scrollback lines `start..end` by recency, most recent first
+    pub fn get_history_range(&self, id: String, start: usize, end: usize) -> Result<Vec<String>, String> {
+        let scrollback = self.scrollback(&id)?;
+        let lines = scrollback.lock().range(start, end);
+        Ok(lines)
+    }
+
+    /// Clone the scrollback handle of an instance
+    fn scrollback(&self, id: &str) -> Result<Arc<Mutex<Scrollback>>, String> {
+        self.instances
+            .lock()
+            .get(id)
+            .map(|instance| instance.scrollback.clone())
+            .ok_or_else(|| "PTY instance not found".to_string())
+    }
+
+    /// Send a signal to the foreground process of a PTY without closing the session.
+    /// On Unix the PTY's foreground process group is signalled (falling back to the
+    /// shell). Windows has no signals: SIGINT and SIGTERM write Ctrl-C, SIGKILL kills
+    /// the session, and SIGHUP is a no-op.
+    pub fn signal(&self, id: String, signal: String) -> Result<(), String> {
+        let signum = parse_signal(&signal)?;
+
+        #[cfg(unix)]
+        {
+            let (pgrp, pid) = {
+                let instances = self.instances.lock();
+                let instance = instances
+                    .get(&id)
+                    .ok_or_else(|| "PTY instance not found".to_string())?;
+                let pgrp = instance
+                    .master
+                    .as_ref()
+                    .and_then(|master| master_raw_fd(master.lock().as_ref()))
+                    // SAFETY: the fd belongs to a master we keep alive while the lock is held
+                    .map(|fd| unsafe { libc::tcgetpgrp(fd) })
+                    .filter(|pgrp| *pgrp > 0);
+                (pgrp, instance.pid)
+            };
+
+            // SAFETY: kill() has no memory-safety requirements
+            let ret = match (pgrp, pid) {
+                (Some(pgrp), _) => unsafe { libc::kill(-pgrp, signum) },
+                (None, Some(pid)) => unsafe { libc::kill(pid as libc::pid_t, signum) },
+                (None, None) => return Err("PTY has no process to signal".to_string()),
+            };
+            if ret != 0 {
+                return Err(format!(
+                    "Failed to send {}: {}",
+                    signal,
+                    std::io::Error::last_os_error()
+                ));
+            }
+            Ok(())
+        }
+
+        #[cfg(windows)]
+        {
+            match signum {
+                2 | 15 => self.write(id, "\x03".to_string()),
+                9 => self.kill(id).map(|_| ()),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    /// Get the OS process id of a PTY's shell (None once the session is gone)
+    pub fn get_pid(&self, id: String) -> Option<u32> {
+        self.instances.lock().get(&id).and_then(|instance| instance.pid)
+    }
+
+    /// Get the last command submitted to a PTY, if any
+    pub fn get_last_command(&self, id: String) -> Result<Option<String>, String> {
+        let instances = self.instances.lock();
+        let instance = instances
+            .get(&id)
+            .ok_or_else(|| "PTY instance not found".to_string())?;
+        let last_command = instance.last_command.lock().clone();
+        Ok(last_command)
+    }
+
+    /// Get the version string of the shell running in a PTY (cached after first query)
+    pub fn get_shell_version(&self, id: String) -> Result<String, String> {
+        let shell = {
+            let instances = self.instances.lock();
+            let instance = instances
+                .get(&id)
+                .ok_or_else(|| "PTY instance not found".to_string())?;
+            if let Some(ref version) = instance.shell_version {
+                return Ok(version.clone());
+            }
+            instance.shell.clone()
+        };
+
+        // Run the subprocess without holding the instances lock
+        let version = get_shell_version(&shell)?;
+
+        if let Some(instance) = self.instances.lock().get_mut(&id) {
+            instance.shell_version = Some(version.clone());
+        }
+        Ok(version)
+    }
+
+    /// Get the raw file descriptor of a PTY's master side (for ioctls, recorders, etc.)
+    ///
+    /// Returns `Ok(None)` for instances without a real PTY (fallback mode, file tails).
+    /// The descriptor is still owned by the PTY: callers must not close it, and it
+    /// becomes invalid once the terminal is killed.
+    #[cfg(unix)]
+    #[allow(dead_code)]
+    pub fn get_master_fd(&self, id: String) -> Result<Option<std::os::unix::io::RawFd>, String> {
+        let instances = self.instances.lock();
+        let instance = instances
+            .get(&id)
+            .ok_or_else(|| "PTY instance not found".to_string())?;
+
+        Ok(instance
+            .master
+            .as_ref()
+            .and_then(|master| master_raw_fd(master.lock().as_ref())))
+    }
+
+    /// Wait until a PTY's process exits and return its exit code
+    /// (None when it was killed by a signal). Any number of callers may wait.
+    pub async fn wait(&self, id: String) -> Result<Option<u32>, String> {
+        let mut exit = self
+            .instances
+            .lock()
+            .get(&id)
+            .map(|instance| instance.exit.subscribe())
+            .ok_or_else(|| "PTY instance not found".to_string())?;
+
+        let event = exit
+            .wait_for(|event| event.is_some())
+            .await
+            .map_err(|_| "PTY closed without an exit status".to_string())?;
+        Ok(event.as_ref().and_then(|event| event.exit_code))
+    }
 
-        // Spawn exit monitor thread
-        let id_exit = options.id.clone();
-        let app_handle_exit = self.app_handle.clone();
-        let instances = self.instances.clone();
+    /// List live terminals, sorted by id
+    pub fn list(&self) -> Vec<PtyInfo> {
+        let instances = self.instances.lock();
+        let mut list: Vec<PtyInfo> = instances
+            .iter()
+            .map(|(id, instance)| PtyInfo {
+                id: id.clone(),
+                cwd: instance.cwd.lock().clone(),
+                shell: instance.shell.clone(),
+                uses_pty: instance.uses_pty,
+                read_only: instance.read_only,
+                pid: instance.pid,
+                title: instance.title.lock().clone(),
+            })
+            .collect();
+        list.sort_by(|a, b| a.id.cmp(&b.id));
+        list
+    }
 
-        thread::spawn(move || {
-            let exit_status = child.wait();
-            let exit_code = match exit_status {
-                Ok(status) => status.exit_code(),
-                Err(_) => 1,
-            };
+    /// Get the latest title a PTY set via OSC 0/2
+    pub fn get_title(&self, id: String) -> Result<Option<String>, String> {
+        let instances = self.instances.lock();
+        let instance = instances
+            .get(&id)
+            .ok_or_else(|| "PTY instance not found".to_string())?;
+        let title = instance.title.lock().clone();
+        Ok(title)
+    }
 
-            let _ = app_handle_exit.emit("pty:exit", (&id_exit, exit_code));
-            instances.lock().remove(&id_exit);
-        });
+    /// Get current working directory
+    pub fn get_cwd(&self, id: String) -> Result<Option<String>, String> {
+        let instances = self.instances.lock();
+        Ok(instances.get(&id).map(|inst| inst.cwd.lock().clone()))
+    }
+}
 
-        self.instances.lock().insert(
-            options.id.clone(),
-            PtyInstance {
-                writer: writer_arc,
-                cwd: options.cwd.clone(),
-                uses_pty: true,
-                master: Some(master_arc), // Store master to keep it alive
-                child_handle: None,
-            },
-        );
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        Ok(())
+    #[cfg(unix)]
+    #[test]
+    fn test_master_raw_fd() {
+        let pair = native_pty_system()
+            .openpty(PtySize {
+                rows: 24,
+                cols: 80,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .unwrap();
+
+        let fd = master_raw_fd(pair.master.as_ref()).expect("unix PTY should expose its fd");
+        assert!(fd >= 0);
     }
 
-    #[allow(dead_code)]
-    fn create_with_fallback(
-        &self,
-        options: &CreatePtyOptions,
-        shell: &str,
-        args: &[String],
-        env_vars: &HashMap<String, String>,
-    ) -> Result<(), String> {
-        let mut cmd = Command::new(shell);
-        cmd.args(args);
-        cmd.current_dir(&options.cwd);
-        cmd.stdin(Stdio::piped());
-        cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::piped());
+    #[test]
+    fn test_shell_flavor_detect() {
+        assert_eq!(ShellFlavor::detect("/bin/zsh"), ShellFlavor::Zsh);
+        assert_eq!(ShellFlavor::detect("/usr/local/bin/bash"), ShellFlavor::Bash);
+        assert_eq!(ShellFlavor::detect("/opt/homebrew/bin/fish"), ShellFlavor::Fish);
+        assert_eq!(ShellFlavor::detect("powershell.exe"), ShellFlavor::PowerShell);
+        assert_eq!(ShellFlavor::detect("pwsh"), ShellFlavor::PowerShell);
+        assert_eq!(ShellFlavor::detect("/bin/sh"), ShellFlavor::Other);
+    }
 
-        for (key, value) in env_vars {
-            cmd.env(key, value);
+    #[test]
+    fn test_apply_login_mode() {
+        let login = |shell: &str, args: &[&str], mode| {
+            apply_login_mode(shell, args.iter().map(|a| a.to_string()).collect(), mode)
+        };
+
+        // None keeps platform defaults
+        assert_eq!(login("/bin/zsh", &["-l"], None), vec!["-l"]);
+        assert!(login("/bin/bash", &[], None).is_empty());
+
+        // Login mode per flavor
+        assert_eq!(login("/bin/zsh", &[], Some(true)), vec!["-l"]);
+        assert_eq!(login("/bin/bash", &[], Some(true)), vec!["--login"]);
+        assert_eq!(login("/usr/bin/fish", &[], Some(true)), vec!["-l"]);
+
+        // Interactive-only replaces an existing login flag
+        assert_eq!(login("/bin/zsh", &["-l"], Some(false)), vec!["-i"]);
+        assert_eq!(login("/bin/bash", &["--login"], Some(false)), vec!["-i"]);
+
+        // PowerShell has no equivalent flags; args are left alone
+        let ps_args = ["-ExecutionPolicy", "Bypass", "-NoLogo"];
+        assert_eq!(login("powershell.exe", &ps_args, Some(true)), ps_args);
+        assert_eq!(login("powershell.exe", &ps_args, Some(false)), ps_args);
+    }
+
+    #[test]
+    fn test_scrollback_caps_lines() {
+        let mut scrollback = Scrollback::new(2, DEFAULT_SCROLLBACK_BYTES);
+        scrollback.push("one\ntwo\nthr");
+        scrollback.push("ee\npartial");
+        assert_eq!(scrollback.lines, vec!["two\n", "three\n"]);
+        assert_eq!(scrollback.partial, "partial");
+
+        assert_eq!(scrollback.line(0), Some("three".to_string()));
+        assert_eq!(scrollback.line(1), Some("two".to_string()));
+        assert_eq!(scrollback.line(2), None);
+        assert_eq!(scrollback.range(0, 10), vec!["three", "two"]);
+        assert_eq!(scrollback.range(1, 2), vec!["two"]);
+        assert!(scrollback.range(5, 10).is_empty());
+        assert!(scrollback.range(2, 1).is_empty());
+
+        scrollback.clear();
+        assert!(scrollback.lines.is_empty());
+        assert!(scrollback.partial.is_empty());
+
+        // A long run without newlines stays within the raw capacity
+        let mut scrollback = Scrollback::new(2, 16);
+        for i in 0..1000 {
+            scrollback.push(&format!("\rprogress {}% é", i));
         }
+        assert!(scrollback.partial.len() <= 16);
+        assert!(scrollback.partial.ends_with("999% é"));
+        assert!(scrollback.lines.is_empty());
+    }
 
-        // For PowerShell, add UTF-8 encoding command
-        #[cfg(target_os = "windows")]
-        if shell.contains("powershell") {
-            cmd.args(&[
-                "-NoExit",
-                "-Command",
-                "[Console]::OutputEncoding = [System.Text.Encoding]::UTF8; \
-                 [Console]::InputEncoding = [System.Text.Encoding]::UTF8; \
-                 $OutputEncoding = [System.Text.Encoding]::UTF8",
-            ]);
+    #[test]
+    fn test_track_input_and_parse_command() {
+        let mut line = String::new();
+        assert!(track_input(&mut line, "cargo bui").is_empty());
+        assert_eq!(track_input(&mut line, "lx\x7fd\r"), vec!["cargo build"]);
+        assert!(line.is_empty());
+
+        // Arrow keys and Ctrl-C don't leak into the next command
+        assert!(track_input(&mut line, "ls\x1b[A\x03").is_empty());
+        assert_eq!(track_input(&mut line, "  \r"), Vec::<String>::new());
+
+        assert_eq!(parse_command_line("  make test"), Some("make test".to_string()));
+        assert_eq!(parse_command_line("user@host:~/src$ git status"), Some("git status".to_string()));
+        assert_eq!(parse_command_line("root@box:/# apt update"), Some("apt update".to_string()));
+        assert_eq!(parse_command_line("echo $HOME"), Some("echo $HOME".to_string()));
+        assert_eq!(parse_command_line("user$ "), None);
+        assert_eq!(parse_command_line("$ cargo build"), Some("cargo build".to_string()));
+
+        // Markers inside the command don't cut it short
+        assert_eq!(parse_command_line(r#"echo "a # b""#), Some(r#"echo "a # b""#.to_string()));
+        assert_eq!(parse_command_line("grep '$ ' f"), Some("grep '$ ' f".to_string()));
+        assert_eq!(parse_command_line("ls # note"), Some("ls # note".to_string()));
+        assert_eq!(
+            parse_command_line("user@host:~$ echo 'a # b' $ c"),
+            Some("echo 'a # b' $ c".to_string())
+        );
+    }
+
+    #[test]
+    fn test_decode_utf8_chunk_across_split() {
+        let text = "ls 目录 🦀 done";
+        let bytes = text.as_bytes();
+
+        // Split at every offset, including inside the 3-byte CJK and 4-byte emoji sequences
+        for split in 0..=bytes.len() {
+            let mut pending = Vec::new();
+            let mut decoded = decode_utf8_chunk(&mut pending, &bytes[..split]);
+            decoded.push_str(&decode_utf8_chunk(&mut pending, &bytes[split..]));
+            assert_eq!(decoded, text, "split at {}", split);
+            assert!(pending.is_empty());
         }
 
-        let mut child = cmd
-            .spawn()
-            .map_err(|e| format!("Failed to spawn process: {}", e))?;
+        // The incomplete tail is held back, not replaced with U+FFFD
+        let mut pending = Vec::new();
+        let crab = "🦀".as_bytes();
+        assert_eq!(decode_utf8_chunk(&mut pending, &crab[..2]), "");
+        assert_eq!(pending.len(), 2);
+        assert_eq!(decode_utf8_chunk(&mut pending, &crab[2..]), "🦀");
+
+        // At end of stream the held-back bytes are flushed lossily
+        assert_eq!(decode_utf8_chunk(&mut pending, b"ok\xe7\x9b"), "ok");
+        assert_eq!(flush_utf8_pending(&mut pending), "\u{FFFD}");
+        assert!(pending.is_empty());
+    }
 
-        // Take stdin
-        let stdin = child
-            .stdin
-            .take()
-            .ok_or_else(|| "Failed to take stdin".to_string())?;
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_shell() {
+        assert!(resolve_shell("/bin/sh").is_ok());
+        assert!(resolve_shell("sh").unwrap().ends_with("/sh"));
+        assert!(resolve_shell("/no/such/shell").is_err());
+        assert!(resolve_shell("no-such-shell-moonterm").is_err());
+        assert!(resolve_shell("  ").is_err());
+    }
 
-        // Create writer wrapper for stdin
-        struct StdinWriter(std::process::ChildStdin);
-        impl Write for StdinWriter {
-            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-                self.0.write(buf)
-            }
-            fn flush(&mut self) -> std::io::Result<()> {
-                self.0.flush()
-            }
-        }
-        // Make StdinWriter Send
-        unsafe impl Send for StdinWriter {}
+    #[test]
+    fn test_merge_env_precedence() {
+        let base: HashMap<String, String> = [
+            ("TERM", "xterm-256color"),
+            ("NODE_ENV", "production"),
+            ("AWS_PROFILE", "default"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let overrides: HashMap<String, String> = [
+            ("NODE_ENV", "development"),
+            ("DEBUG", ""),
+            ("AWS_PROFILE", ENV_UNSET),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        let (merged, unset) = merge_env(base.clone(), Some(&overrides));
+        assert_eq!(merged.get("NODE_ENV"), Some(&"development".to_string()));
+        assert_eq!(merged.get("DEBUG"), Some(&"".to_string()));
+        assert_eq!(merged.get("TERM"), Some(&"xterm-256color".to_string()));
+        assert!(!merged.contains_key("AWS_PROFILE"));
+        assert_eq!(unset, vec!["AWS_PROFILE".to_string()]);
+
+        let (merged, unset) = merge_env(base.clone(), None);
+        assert_eq!(merged, base);
+        assert!(unset.is_empty());
+    }
 
-        let writer_arc = Arc::new(Mutex::new(Box::new(StdinWriter(stdin)) as Box<dyn Write + Send>));
+    #[test]
+    fn test_parse_signal() {
+        assert_eq!(parse_signal("SIGINT"), Ok(2));
+        assert_eq!(parse_signal("term"), Ok(15));
+        assert_eq!(parse_signal("SigHup"), Ok(1));
+        assert_eq!(parse_signal("KILL"), Ok(9));
+        assert!(parse_signal("SIGSEGV").is_err());
+        assert!(parse_signal("bogus").is_err());
+    }
 
-        // Capture stdout
-        let stdout = child
-            .stdout
-            .take()
-            .ok_or_else(|| "Failed to take stdout".to_string())?;
-        let id_stdout = options.id.clone();
-        let app_handle_stdout = self.app_handle.clone();
+    #[test]
+    fn test_descendant_pids() {
+        let ps = "    1     0\n  100     1\n  101   100\n  102   101\n  103   100\n  200     1\n garbage\n";
+        let mut descendants = descendant_pids(ps, 100);
+        descendants.sort();
+        assert_eq!(descendants, vec![101, 102, 103]);
+        assert!(descendant_pids(ps, 102).is_empty());
+    }
 
-        thread::spawn(move || {
-            let mut reader = BufReader::new(stdout);
-            let mut buf = [0u8; 4096];
+    #[cfg(unix)]
+    #[test]
+    fn test_kill_process_tree() {
+        let mut child = Command::new("sh")
+            .args(["-c", "sleep 30 & sleep 30"])
+            .spawn()
+            .unwrap();
 
-            loop {
-                match reader.read(&mut buf) {
-                    Ok(0) => break,
-                    Ok(n) => {
-                        let data = String::from_utf8_lossy(&buf[..n]).to_string();
-                        let _ = app_handle_stdout.emit("pty:output", (&id_stdout, &data));
-                    }
-                    Err(_) => break,
-                }
-            }
-        });
+        kill_process_tree(child.id()).unwrap();
+        let status = child.wait().unwrap();
+        assert!(!status.success());
+    }
 
-        // Capture stderr
-        let stderr = child
-            .stderr
-            .take()
-            .ok_or_else(|| "Failed to take stderr".to_string())?;
-        let id_stderr = options.id.clone();
-        let app_handle_stderr = self.app_handle.clone();
+    #[test]
+    fn test_scrollback_raw_bytes() {
+        let mut scrollback = Scrollback::new(10, 8);
+        scrollback.push("hello ");
+        scrollback.push_bytes(&[b'w', 0xff, b'r', b'l', b'd']);
+        assert_eq!(scrollback.raw_tail(None), b"lo w\xffrld".to_vec());
+        assert_eq!(scrollback.raw_tail(Some(3)), b"rld".to_vec());
+        assert_eq!(scrollback.raw_tail(Some(100)).len(), 8);
+
+        scrollback.clear();
+        assert!(scrollback.raw_tail(None).is_empty());
+    }
 
-        thread::spawn(move || {
-            let mut reader = BufReader::new(stderr);
-            let mut buf = [0u8; 4096];
+    #[test]
+    fn test_binary_output_reaches_scrollback_raw() {
+        let scrollback = Mutex::new(Scrollback::new(10, 1024));
+        let log: SharedLog = Arc::new(Mutex::new(None));
+
+        // Invalid UTF-8, NUL and half of a multi-byte character, split across batches
+        let first = [0x00, 0xff, b'a', 0xe7];
+        let second = [0x9b, 0xae, b'\n'];
+        let encoded = record_binary_output(&scrollback, &log, &first);
+        assert_eq!(BASE64.decode(encoded).unwrap(), first.to_vec());
+        record_binary_output(&scrollback, &log, &second);
+
+        assert_eq!(
+            scrollback.lock().raw_tail(None),
+            [first.as_slice(), second.as_slice()].concat()
+        );
+    }
 
-            loop {
-                match reader.read(&mut buf) {
-                    Ok(0) => break,
-                    Ok(n) => {
-                        let data = String::from_utf8_lossy(&buf[..n]).to_string();
-                        let _ = app_handle_stderr.emit("pty:output", (&id_stderr, &data));
-                    }
-                    Err(_) => break,
-                }
-            }
-        });
+    #[test]
+    fn test_parse_osc7_cwd() {
+        let data = "prompt\x1b]7;file://host/home/user/my%20dir\x07$ \x1b]7;file:///tmp\x1b\\";
+        let sequences = parse_osc_sequences(data);
+        assert_eq!(sequences.len(), 2);
+        assert_eq!(parse_osc7_path(&sequences[0].1), Some("/home/user/my dir".to_string()));
+        assert_eq!(parse_osc7_path(&sequences[1].1), Some("/tmp".to_string()));
+
+        assert_eq!(parse_osc7_path("file:///C:/Users/me"), Some("C:/Users/me".to_string()));
+        assert_eq!(parse_osc7_path("http://host/path"), None);
+        assert_eq!(parse_osc7_path("file://host/bad%zz"), None);
+        assert_eq!(parse_osc7_path("file://hostonly"), None);
+        assert!(parse_osc_sequences("\x1b]7;file:///unterminated").is_empty());
+    }
 
-        // Send initial message
-        let _ = self.app_handle.emit(
-            "pty:output",
-            (&options.id, "[Terminal - fallback mode]\r\n"),
+    #[test]
+    fn test_parse_osc_titles() {
+        let titles = parse_osc_sequences("\x1b]0;vim main.rs\x07out\x1b]2;cargo build\x1b\\");
+        assert_eq!(
+            titles,
+            vec![(0, "vim main.rs".to_string()), (2, "cargo build".to_string())]
         );
+    }
 
-        let child_arc = Arc::new(Mutex::new(child));
+    #[test]
+    fn test_count_bells() {
+        assert_eq!(count_bells("done\x07"), 1);
+        assert_eq!(count_bells("\x1b]0;title\x07\x07\x07"), 2);
+        assert_eq!(count_bells("\x1b]2;t\x1b\\\x07"), 1);
+        assert_eq!(count_bells("plain"), 0);
+    }
 
-        // Spawn exit monitor
-        let id_exit = options.id.clone();
-        let app_handle_exit = self.app_handle.clone();
-        let instances = self.instances.clone();
-        let child_clone = child_arc.clone();
+    #[test]
+    fn test_held_bell_flushes_when_idle() {
+        let (tx, rx) = std::sync::mpsc::sync_channel::<Vec<u8>>(16);
+        let flush = std::time::Duration::from_millis(5);
+        let mut bells = BellDebouncer::default();
 
-        thread::spawn(move || {
-            let exit_code = child_clone
-                .lock()
-                .wait()
-                .map(|status| status.code().unwrap_or(1))
-                .unwrap_or(1);
+        bells.ring(1);
+        assert_eq!(bells.take_due(), Some(1));
 
-            let _ = app_handle_exit.emit("pty:exit", (&id_exit, exit_code));
-            instances.lock().remove(&id_exit);
-        });
+        // A second bell inside the debounce window is held, then no more output comes
+        bells.ring(1);
+        assert_eq!(bells.take_due(), None);
+        let wait = bells.wait().expect("bell should be held");
+        assert!(wait <= BELL_DEBOUNCE);
 
-        self.instances.lock().insert(
-            options.id.clone(),
-            PtyInstance {
-                writer: writer_arc,
-                cwd: options.cwd.clone(),
-                uses_pty: false,
-                master: None,
-                child_handle: Some(child_arc),
-            },
-        );
+        // The idle tick comes back empty once the bell is due, and releases it
+        assert_eq!(next_output_batch(&rx, flush, 1024, Some(wait)), Some(Vec::new()));
+        assert_eq!(bells.take_due(), Some(1));
+        assert_eq!(bells.wait(), None);
 
-        println!("Created terminal using child_process fallback");
-        Ok(())
+        drop(tx);
+        assert_eq!(next_output_batch(&rx, flush, 1024, bells.wait()), None);
     }
 
-    /// Large paste chunking threshold (bytes)
-    /// Pastes larger than this will be split into chunks to prevent terminal overwhelm
-    const CHUNK_SIZE: usize = 2048;
-    /// Delay between chunks (microseconds) - 10ms
-    const CHUNK_DELAY_US: u64 = 10_000;
+    #[test]
+    fn test_next_output_batch_coalesces() {
+        let (tx, rx) = std::sync::mpsc::sync_channel::<Vec<u8>>(16);
+        tx.send(b"ab".to_vec()).unwrap();
+        tx.send(b"cd".to_vec()).unwrap();
+        tx.send(b"ef".to_vec()).unwrap();
 
-    /// Write data to PTY with automatic chunking for large pastes
-    ///
-    /// Differentiated from Tony's JS approach:
-    /// - Chunking happens in Rust, avoiding multiple IPC round-trips
-    /// - Native thread sleep for precise timing
-    /// - Single IPC call from frontend, chunking is transparent
-    pub fn write(&self, id: String, data: String) -> Result<(), String> {
-        let instances = self.instances.lock();
-        let instance = instances
-            .get(&id)
-            .ok_or_else(|| "PTY instance not found".to_string())?;
+        let flush = std::time::Duration::from_millis(20);
+        assert_eq!(next_output_batch(&rx, flush, 1024, None), Some(b"abcdef".to_vec()));
 
-        let bytes = data.as_bytes();
-        let mut writer_lock = instance.writer.lock();
+        // The size cap emits early; the rest comes in the next batch
+        tx.send(b"0123".to_vec()).unwrap();
+        tx.send(b"4567".to_vec()).unwrap();
+        assert_eq!(next_output_batch(&rx, flush, 4, None), Some(b"0123".to_vec()));
+        assert_eq!(next_output_batch(&rx, flush, 4, None), Some(b"4567".to_vec()));
 
-        // If data is small, write directly
-        if bytes.len() <= Self::CHUNK_SIZE {
-            writer_lock
-                .write_all(bytes)
-                .map_err(|e| format!("Failed to write: {}", e))?;
-            writer_lock
-                .flush()
-                .map_err(|e| format!("Failed to flush: {}", e))?;
-            return Ok(());
-        }
+        drop(tx);
+        assert_eq!(next_output_batch(&rx, flush, 1024, None), None);
+    }
 
-        // Large paste: chunk it to prevent terminal overwhelm
-        // This is especially important for TUI apps like vim, Claude Code
-        for chunk in bytes.chunks(Self::CHUNK_SIZE) {
-            writer_lock
-                .write_all(chunk)
-                .map_err(|e| format!("Failed to write chunk: {}", e))?;
-            writer_lock
-                .flush()
-                .map_err(|e| format!("Failed to flush chunk: {}", e))?;
+    #[test]
+    fn test_read_buffer_size_clamped() {
+        assert_eq!(read_buffer(None, 4096).len(), 4096);
+        assert_eq!(read_buffer(Some(64 * 1024), 4096).len(), 64 * 1024);
+        assert_eq!(read_buffer(Some(16), 4096).len(), MIN_READ_BUFFER_SIZE);
+        assert_eq!(read_buffer(Some(usize::MAX), 4096).len(), MAX_READ_BUFFER_SIZE);
+    }
 
-            // Small delay between chunks to let terminal process
-            // Using sleep here is acceptable since we're in a locked context
-            // and this only happens for large pastes (rare event)
-            if chunk.len() == Self::CHUNK_SIZE {
-                std::thread::sleep(std::time::Duration::from_micros(Self::CHUNK_DELAY_US));
-            }
-        }
+    #[test]
+    fn test_cancel_signal_wakes_waiter() {
+        let signal = Arc::new(CancelSignal::default());
+        assert!(!signal.wait(std::time::Duration::ZERO));
 
-        Ok(())
+        let waiter = signal.clone();
+        let started = std::time::Instant::now();
+        let handle = thread::spawn(move || waiter.wait(std::time::Duration::from_secs(30)));
+        thread::sleep(std::time::Duration::from_millis(20));
+        signal.cancel();
+
+        assert!(handle.join().unwrap());
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
     }
 
-    /// Resize PTY (only works with portable-pty, ignored for fallback)
-    pub fn resize(&self, id: String, cols: u16, rows: u16) -> Result<(), String> {
-        let instances = self.instances.lock();
-        let instance = instances.get(&id);
-
-        if let Some(inst) = instance {
-            if inst.uses_pty {
-                if let Some(ref master) = inst.master {
-                    let master_lock = master.lock();
-                    let _ = master_lock.resize(PtySize {
-                        rows,
-                        cols,
-                        pixel_width: 0,
-                        pixel_height: 0,
-                    });
-                }
+    #[test]
+    fn test_pending_resize_reuses_timer() {
+        let size = |cols| PtySize {
+            rows: 24,
+            cols,
+            pixel_width: 0,
+            pixel_height: 0,
+        };
+        let start = std::time::Instant::now();
+        let debounce = std::time::Duration::from_millis(50);
+        let mut pending = PendingResize::default();
+
+        // Only the first resize of a burst starts a timer; later ones move the deadline
+        assert!(pending.schedule(size(80), start + debounce));
+        assert!(!pending.schedule(size(100), start + debounce * 2));
+        assert_eq!(pending.poll(start + debounce), ControlFlow::Continue(debounce));
+        assert_eq!(pending.poll(start + debounce * 2), ControlFlow::Break(Some(size(100))));
+
+        // Once the timer has exited the next resize starts a new one
+        assert!(pending.schedule(size(120), start + debounce * 3));
+    }
+
+    #[test]
+    fn test_paste_payload() {
+        assert_eq!(paste_payload("ls\npwd\n", false), b"ls\npwd\n".to_vec());
+        assert_eq!(
+            paste_payload("ls\npwd\n", true),
+            b"\x1b[200~ls\npwd\n\x1b[201~".to_vec()
+        );
+        // An embedded end marker must not terminate the paste early
+        assert_eq!(
+            paste_payload("a\x1b[201~rm -rf ~\n", true),
+            b"\x1b[200~a[201~rm -rf ~\n\x1b[201~".to_vec()
+        );
+        // Removing one marker must not assemble another
+        assert_eq!(
+            paste_payload("\x1b[20\x1b[201~1~rm -rf ~\n", true),
+            b"\x1b[200~[20[201~1~rm -rf ~\n\x1b[201~".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_throttled_writes_keep_order() {
+        #[derive(Clone)]
+        struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+        impl Write for SharedBuf {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
             }
         }
 
-        Ok(())
-    }
+        let buf = SharedBuf(Arc::new(Mutex::new(Vec::new())));
+        let writer: Arc<Mutex<Box<dyn Write + Send>>> = Arc::new(Mutex::new(Box::new(buf.clone())));
+        let throttle = Arc::new(Mutex::new(InputThrottle::new(4, std::time::Duration::from_millis(5))));
 
-    /// Kill PTY instance
-    pub fn kill(&self, id: String) -> Result<bool, String> {
-        let mut instances = self.instances.lock();
+        // The first write is mostly still queued when the chunked write arrives
+        PtyManager::send_to(Some(&throttle), &writer, b"queued-input").unwrap();
+        PtyManager::write_chunks_to(Some(&throttle), &writer, b"|chunked", 2).unwrap();
+        PtyManager::send_to(Some(&throttle), &writer, b"|last").unwrap();
 
-        if let Some(instance) = instances.remove(&id) {
-            if let Some(child_handle) = instance.child_handle {
-                let _ = child_handle.lock().kill();
-            }
-            // For PTY, dropping the master will close the connection
-            Ok(true)
-        } else {
-            Ok(false)
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while throttle.lock().draining && std::time::Instant::now() < deadline {
+            thread::sleep(std::time::Duration::from_millis(5));
         }
+        assert_eq!(buf.0.lock().as_slice(), b"queued-input|chunked|last");
     }
 
-    /// Restart PTY instance
-    pub fn restart(&self, id: String, cwd: String) -> Result<bool, String> {
-        // Check if instance exists
-        {
-            let instances = self.instances.lock();
-            if !instances.contains_key(&id) {
-                return Err("PTY instance not found".to_string());
-            }
-        }
+    #[test]
+    fn test_input_throttle_accounting() {
+        let start = std::time::Instant::now();
+        let interval = std::time::Duration::from_millis(10);
+        let mut throttle = InputThrottle::new(4, interval);
+        throttle.queue.extend(b"abcdefghij");
+
+        assert_eq!(throttle.next_batch(start), b"abcd".to_vec());
+        // Budget spent: nothing more until the window ends
+        assert!(throttle.next_batch(start + interval / 2).is_empty());
+        assert_eq!(throttle.until_next_window(start + interval / 2), interval / 2);
+
+        assert_eq!(throttle.next_batch(start + interval), b"efgh".to_vec());
+        throttle.queue.extend(b"k");
+        assert_eq!(throttle.next_batch(start + interval * 2), b"ijk".to_vec());
+        assert!(throttle.queue.is_empty());
+    }
 
-        self.kill(id.clone())?;
+    #[cfg(unix)]
+    #[test]
+    fn test_exit_event_reports_signal() {
+        use std::os::unix::process::ExitStatusExt;
+
+        // Raw wait status: SIGSEGV with the core dump bit set
+        let crashed = PtyExitEvent::from_std("t", &std::process::ExitStatus::from_raw(11 | 0x80));
+        assert_eq!(crashed.signal.as_deref(), Some("SIGSEGV"));
+        assert_eq!(crashed.signal_number, Some(11));
+        assert!(crashed.core_dumped);
+        assert_eq!(crashed.exit_code, None);
+
+        let clean = PtyExitEvent::from_std("t", &std::process::ExitStatus::from_raw(0));
+        assert_eq!(clean.exit_code, Some(0));
+        assert_eq!(clean.signal_number, None);
+        assert!(clean.success);
+    }
+
+    #[test]
+    fn test_ansi_stripper() {
+        let sample = b"\x1b[1;31mred\x1b[0m plain\r\n\x1b[2J\x1b[10;5Hmoved\x1b(B\n\
+            \x1b]0;title\x07\x1b]7;file://host/tmp\x1b\\\x1bOPdone\ttab\x07\n";
+        let mut stripper = AnsiStripper::default();
+        assert_eq!(
+            String::from_utf8(stripper.strip(sample)).unwrap(),
+            "red plain\nmoved\ndone\ttab\n"
+        );
 
-        self.create(CreatePtyOptions { id, cwd, custom_env: None })
+        // Sequences split across chunks are still removed
+        let mut stripper = AnsiStripper::default();
+        let mut out = stripper.strip(b"a\x1b[3");
+        out.extend(stripper.strip(b"8;5;196mb\x1b]2;x"));
+        out.extend(stripper.strip(b"y\x1b\\c"));
+        assert_eq!(out, b"abc".to_vec());
     }
 
-    /// Get current working directory
-    pub fn get_cwd(&self, id: String) -> Result<Option<String>, String> {
-        let instances = self.instances.lock();
-        Ok(instances.get(&id).map(|inst| inst.cwd.clone()))
+    #[test]
+    fn test_hyperlink_tracker() {
+        let mut tracker = HyperlinkTracker::default();
+        let links = tracker.feed("see \x1b[1m\x1b]8;;https://example.com\x07docs\x1b]8;;\x07\x1b[0m!");
+        assert_eq!(
+            links,
+            vec![("https://example.com".to_string(), "docs".to_string(), 4, 8)]
+        );
+
+        // A link whose text spans chunks, with params and an ST terminator
+        assert!(tracker.feed("\r\n\x1b]8;id=1;file:///tmp/a\x1b\\ré").is_empty());
+        let links = tracker.feed("sumé\x1b]8;;\x1b\\");
+        assert_eq!(
+            links,
+            vec![("file:///tmp/a".to_string(), "résumé".to_string(), 10, 16)]
+        );
+    }
+
+    #[test]
+    fn test_parse_osc52() {
+        assert_eq!(parse_osc52("c;aGVsbG8gd29ybGQ="), Some("hello world".to_string()));
+        assert_eq!(parse_osc52(";aGk="), Some("hi".to_string()));
+        assert_eq!(parse_osc52("c;?"), None);
+        assert_eq!(parse_osc52("c;not base64!"), None);
+        assert_eq!(parse_osc52("aGk="), None);
     }
 }
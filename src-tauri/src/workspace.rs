@@ -1,14 +1,113 @@
 use std::fs::{self, File};
 use std::io::{Read, Write};
-use std::path::PathBuf;
-use tauri::Manager;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager};
 use flate2::read::GzDecoder;
-use flate2::write::GzEncoder;
-use flate2::Compression;
+use flate2::{Compression, GzBuilder};
 use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use crate::crypto::{self, EncryptedEnvelope};
+use crate::platform;
 
 const MAX_HISTORY_FILES: usize = 10;
 
+/// Upper bound for the configurable history retention
+const MAX_HISTORY_FILES_LIMIT: usize = 1000;
+
+/// User-tunable save settings, persisted in `workspace_settings.json`.
+/// Unset fields fall back to the built-in defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorkspaceSettings {
+    /// History snapshots to keep (1..=1000, default 10)
+    #[serde(default)]
+    pub max_history_files: Option<usize>,
+    /// Gzip level 0 (fastest) ..= 9 (smallest), default 6
+    #[serde(default)]
+    pub compression_level: Option<u32>,
+}
+
+impl WorkspaceSettings {
+    fn validate(&self) -> Result<(), String> {
+        if let Some(count) = self.max_history_files {
+            if !(1..=MAX_HISTORY_FILES_LIMIT).contains(&count) {
+                return Err(format!(
+                    "History retention must be between 1 and {}",
+                    MAX_HISTORY_FILES_LIMIT
+                ));
+            }
+        }
+        if let Some(level) = self.compression_level {
+            if level > 9 {
+                return Err("Compression level must be between 0 and 9".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    fn history_limit(&self) -> usize {
+        self.max_history_files.unwrap_or(MAX_HISTORY_FILES)
+    }
+
+    fn compression(&self) -> Compression {
+        self.compression_level.map(Compression::new).unwrap_or_default()
+    }
+}
+
+/// Name of the workspace stored in `workspaces.json.gz`
+pub const DEFAULT_WORKSPACE_NAME: &str = "default";
+
+/// Maximum length of a workspace comment, in characters
+const MAX_COMMENT_LEN: usize = 256;
+
+/// Current on-disk workspace schema version.
+/// Version 0 is the legacy format: app data stored without an envelope.
+pub const WORKSPACE_SCHEMA_VERSION: u32 = 1;
+
+/// Workspace data together with the schema version it was stored in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionedWorkspace {
+    pub schema_version: u32,
+    pub data: String,
+}
+
+/// `format` value identifying a whole-file encrypted workspace
+const ENCRYPTED_WORKSPACE_FORMAT: &str = "moonterm-encrypted-workspace";
+
+/// On-disk wrapper for a workspace saved with `save_workspace_encrypted`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EncryptedWorkspace {
+    format: String,
+    envelope: EncryptedEnvelope,
+}
+
+/// A workspace file on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceEntry {
+    pub name: String,
+    pub size_bytes: u64,
+    /// Last modification time (Unix seconds)
+    pub modified_at: u64,
+    /// Description stored in the gzip header
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+/// A history snapshot on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub filename: String,
+    pub size_bytes: u64,
+    /// Last modification time (Unix seconds)
+    pub modified_at: u64,
+    /// Description stored in the gzip header
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
 /// Get the path to the workspace configuration file
 pub fn get_config_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
     let app_data_dir = app_handle
@@ -19,6 +118,142 @@ pub fn get_config_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, String>
     Ok(app_data_dir.join("workspaces.json.gz"))
 }
 
+/// Path of the workspace settings file
+fn get_settings_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    Ok(get_app_data_dir(app_handle)?.join("workspace_settings.json"))
+}
+
+/// Load workspace settings; missing or invalid files give the defaults
+pub fn load_settings(app_handle: &tauri::AppHandle) -> WorkspaceSettings {
+    let Ok(path) = get_settings_path(app_handle) else {
+        return WorkspaceSettings::default();
+    };
+    let Ok(data) = fs::read_to_string(&path) else {
+        return WorkspaceSettings::default();
+    };
+    match serde_json::from_str::<WorkspaceSettings>(&data) {
+        Ok(settings) if settings.validate().is_ok() => settings,
+        _ => {
+            eprintln!("Ignoring invalid workspace settings in {}", path.display());
+            WorkspaceSettings::default()
+        }
+    }
+}
+
+/// Validate and persist workspace settings
+pub fn save_settings(app_handle: &tauri::AppHandle, settings: &WorkspaceSettings) -> Result<(), String> {
+    settings.validate()?;
+
+    let path = get_settings_path(app_handle)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+    let data = serde_json::to_string_pretty(settings)
+        .map_err(|e| format!("Failed to serialize workspace settings: {}", e))?;
+    platform::write_file_atomic(&path, data.as_bytes())
+}
+
+/// Get the app data directory holding all workspace files
+fn get_app_data_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data dir: {}", e))
+}
+
+/// Validate a workspace name (used in file names, so keep it to a safe charset)
+pub fn validate_workspace_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > 64 {
+        return Err("Workspace name must be 1-64 characters".to_string());
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!(
+            "Invalid workspace name '{}': use letters, digits, '-' or '_'",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Get the path of a named workspace file (`default` maps to `workspaces.json.gz`)
+pub fn get_named_config_path(app_handle: &tauri::AppHandle, name: &str) -> Result<PathBuf, String> {
+    if name == DEFAULT_WORKSPACE_NAME {
+        return get_config_path(app_handle);
+    }
+    validate_workspace_name(name)?;
+    Ok(get_app_data_dir(app_handle)?.join(format!("workspaces_{}.json.gz", name)))
+}
+
+/// Extract the workspace name from a workspace file name
+fn workspace_name_from_file(file_name: &str) -> Option<String> {
+    if file_name == "workspaces.json.gz" {
+        return Some(DEFAULT_WORKSPACE_NAME.to_string());
+    }
+    let name = file_name.strip_prefix("workspaces_")?.strip_suffix(".json.gz")?;
+    validate_workspace_name(name).ok()?;
+    Some(name.to_string())
+}
+
+/// List all workspace files in the app data directory
+pub fn list_workspaces(app_handle: &tauri::AppHandle) -> Result<Vec<WorkspaceEntry>, String> {
+    let app_data_dir = get_app_data_dir(app_handle)?;
+    if !app_data_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut entries: Vec<WorkspaceEntry> = fs::read_dir(&app_data_dir)
+        .map_err(|e| format!("Failed to read app data dir: {}", e))?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = workspace_name_from_file(&e.file_name().to_string_lossy())?;
+            let metadata = e.metadata().ok()?;
+            let modified_at = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            Some(WorkspaceEntry {
+                name,
+                size_bytes: metadata.len(),
+                modified_at,
+                comment: read_gz_comment(&e.path()),
+            })
+        })
+        .collect();
+
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+/// Names of all saved workspace profiles, sorted
+pub fn list_profiles(app_handle: &tauri::AppHandle) -> Result<Vec<String>, String> {
+    Ok(list_workspaces(app_handle)?.into_iter().map(|entry| entry.name).collect())
+}
+
+/// List the `limit` most recently modified workspaces, newest first
+pub fn list_recent(app_handle: &tauri::AppHandle, limit: usize) -> Result<Vec<WorkspaceEntry>, String> {
+    let mut entries = list_workspaces(app_handle)?;
+    entries.sort_by(|a, b| b.modified_at.cmp(&a.modified_at));
+    entries.truncate(limit);
+    Ok(entries)
+}
+
+/// Bump a workspace's modification time without changing its content
+pub fn touch_workspace(app_handle: &tauri::AppHandle, name: &str) -> Result<(), String> {
+    let path = get_named_config_path(app_handle, name)?;
+    if !path.exists() {
+        return Err(format!("Workspace '{}' does not exist", name));
+    }
+
+    File::options()
+        .append(true)
+        .open(&path)
+        .and_then(|file| file.set_modified(std::time::SystemTime::now()))
+        .map_err(|e| format!("Failed to touch workspace: {}", e))
+}
+
 /// Get the path to the history directory
 fn get_history_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
     let app_data_dir = app_handle
@@ -29,16 +264,27 @@ fn get_history_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf, String> {
     Ok(app_data_dir.join("history"))
 }
 
-/// Rotate current workspace file to history
-fn rotate_to_history(app_handle: &tauri::AppHandle) -> Result<(), String> {
-    let config_path = get_config_path(app_handle)?;
+/// History directory of a profile: `history/<profile>/`, except the default
+/// profile which keeps using `history/` itself
+fn get_profile_history_dir(app_handle: &tauri::AppHandle, profile: &str) -> Result<PathBuf, String> {
+    let history_dir = get_history_dir(app_handle)?;
+    if profile == DEFAULT_WORKSPACE_NAME {
+        return Ok(history_dir);
+    }
+    validate_workspace_name(profile)?;
+    Ok(history_dir.join(profile))
+}
+
+/// Rotate a profile's current workspace file to its history
+fn rotate_to_history(app_handle: &tauri::AppHandle, profile: &str) -> Result<(), String> {
+    let config_path = get_named_config_path(app_handle, profile)?;
 
     // Only rotate if current file exists
     if !config_path.exists() {
         return Ok(());
     }
 
-    let history_dir = get_history_dir(app_handle)?;
+    let history_dir = get_profile_history_dir(app_handle, profile)?;
 
     // Create history directory if it doesn't exist
     fs::create_dir_all(&history_dir)
@@ -52,21 +298,27 @@ fn rotate_to_history(app_handle: &tauri::AppHandle) -> Result<(), String> {
     fs::copy(&config_path, &history_file)
         .map_err(|e| format!("Failed to copy to history: {}", e))?;
 
-    // Clean up old history files (keep only MAX_HISTORY_FILES)
-    cleanup_old_history(&history_dir)?;
+    // Clean up old history files beyond the configured retention
+    cleanup_old_history(&history_dir, load_settings(app_handle).history_limit())?;
 
     Ok(())
 }
 
 /// Clean up old history files, keeping only the most recent ones
-fn cleanup_old_history(history_dir: &PathBuf) -> Result<(), String> {
-    let mut entries: Vec<_> = fs::read_dir(history_dir)
+fn cleanup_old_history(history_dir: &Path, keep: usize) -> Result<(), String> {
+    cleanup_old_files(history_dir, "workspaces_", keep)
+}
+
+/// Remove `{prefix}*.json.gz` files in `dir` beyond the `keep` newest ones.
+/// Relies on the timestamp in the file name sorting chronologically.
+fn cleanup_old_files(dir: &Path, prefix: &str, keep: usize) -> Result<(), String> {
+    let mut entries: Vec<_> = fs::read_dir(dir)
         .map_err(|e| format!("Failed to read history dir: {}", e))?
         .filter_map(|e| e.ok())
         .filter(|e| {
             e.path()
                 .file_name()
-                .map(|n| n.to_string_lossy().starts_with("workspaces_") && n.to_string_lossy().ends_with(".json.gz"))
+                .map(|n| n.to_string_lossy().starts_with(prefix) && n.to_string_lossy().ends_with(".json.gz"))
                 .unwrap_or(false)
         })
         .collect();
@@ -74,8 +326,8 @@ fn cleanup_old_history(history_dir: &PathBuf) -> Result<(), String> {
     // Sort by file name (which includes timestamp) in reverse order (newest first)
     entries.sort_by(|a, b| b.path().cmp(&a.path()));
 
-    // Remove files beyond MAX_HISTORY_FILES
-    for entry in entries.into_iter().skip(MAX_HISTORY_FILES) {
+    // Remove files beyond the retention count
+    for entry in entries.into_iter().skip(keep) {
         if let Err(e) = fs::remove_file(entry.path()) {
             eprintln!("Failed to remove old history file: {}", e);
         }
@@ -84,54 +336,1398 @@ fn cleanup_old_history(history_dir: &PathBuf) -> Result<(), String> {
     Ok(())
 }
 
-/// Save workspace data to the config file (gzip compressed)
-pub fn save_workspace(app_handle: &tauri::AppHandle, data: String) -> Result<bool, String> {
+/// Number of external backups kept by the auto-backup task
+const MAX_EXTERNAL_BACKUPS: usize = 5;
+
+/// Handle of the running auto-backup thread (managed Tauri state)
+#[derive(Default)]
+pub struct AutoBackup {
+    task: parking_lot::Mutex<Option<(thread::JoinHandle<()>, Arc<AtomicBool>)>>,
+}
+
+impl AutoBackup {
+    /// Stop the running backup task, if any. Returns whether one was running.
+    pub fn stop(&self) -> bool {
+        // Take the task out first so the lock isn't held while joining
+        let task = self.task.lock().take();
+        Self::stop_task(task)
+    }
+
+    fn stop_task(task: Option<(thread::JoinHandle<()>, Arc<AtomicBool>)>) -> bool {
+        match task {
+            Some((handle, stop)) => {
+                stop.store(true, Ordering::SeqCst);
+                let _ = handle.join();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Copy the current workspace file to `dest_dir` with a timestamped name
+fn backup_to_dir(config_path: &Path, dest_dir: &Path) -> Result<(), String> {
+    if !config_path.exists() {
+        return Ok(());
+    }
+
+    let timestamp = Utc::now().format("%Y%m%d_%H%M%S").to_string();
+    let backup_file = dest_dir.join(format!("moonterm-backup-{}.json.gz", timestamp));
+    fs::copy(config_path, &backup_file)
+        .map_err(|e| format!("Failed to copy backup: {}", e))?;
+
+    cleanup_old_files(dest_dir, "moonterm-backup-", MAX_EXTERNAL_BACKUPS)
+}
+
+/// Start copying the workspace to `dest_dir` every `interval_secs`, replacing any running task
+pub fn start_backup_task(
+    app_handle: &tauri::AppHandle,
+    dest_dir: String,
+    interval_secs: u64,
+) -> Result<(), String> {
+    if interval_secs == 0 {
+        return Err("Backup interval must be at least 1 second".to_string());
+    }
+
+    let dest_dir = PathBuf::from(dest_dir);
+    fs::create_dir_all(&dest_dir)
+        .map_err(|e| format!("Failed to create backup directory: {}", e))?;
+
     let config_path = get_config_path(app_handle)?;
+    let state = app_handle.state::<AutoBackup>();
 
-    // Ensure parent directory exists
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_thread = stop.clone();
+    let handle = thread::spawn(move || {
+        let interval = Duration::from_secs(interval_secs);
+        loop {
+            if let Err(e) = backup_to_dir(&config_path, &dest_dir) {
+                eprintln!("Auto-backup failed: {}", e);
+            }
+
+            // Sleep in short steps so stop requests are picked up quickly
+            let started = Instant::now();
+            while started.elapsed() < interval {
+                if stop_thread.load(Ordering::SeqCst) {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(200));
+            }
+        }
+    });
+
+    // Swap in the new task under one lock so concurrent starts can't both
+    // install a thread; whichever task was replaced is stopped afterwards
+    let previous = std::mem::replace(&mut *state.task.lock(), Some((handle, stop)));
+    AutoBackup::stop_task(previous);
+    Ok(())
+}
+
+/// Validate a history snapshot file name (`workspaces_YYYYMMDD_HHMMSS.json.gz`).
+/// Rejects anything else so callers can't reach outside the history directory.
+fn validate_history_filename(filename: &str) -> Result<(), String> {
+    let valid = filename
+        .strip_prefix("workspaces_")
+        .and_then(|rest| rest.strip_suffix(".json.gz"))
+        .map(|timestamp| {
+            timestamp.len() == 15
+                && timestamp
+                    .char_indices()
+                    .all(|(i, c)| if i == 8 { c == '_' } else { c.is_ascii_digit() })
+        })
+        .unwrap_or(false);
+
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid history file name: {}", filename))
+    }
+}
+
+/// Load a profile's history snapshot JSON without restoring it
+pub fn get_history_snapshot(
+    app_handle: &tauri::AppHandle,
+    profile: &str,
+    filename: String,
+) -> Result<String, String> {
+    validate_history_filename(&filename)?;
+
+    let path = get_profile_history_dir(app_handle, profile)?.join(&filename);
+    if !path.exists() {
+        return Err(format!("History snapshot not found: {}", filename));
+    }
+
+    Ok(unwrap_workspace(&read_gz(&path)?)?.data)
+}
+
+/// Restore a history snapshot as the profile's current workspace.
+/// The current workspace is rotated to history first, so the restore can be undone.
+pub fn restore_history(app_handle: &tauri::AppHandle, profile: &str, filename: String) -> Result<(), String> {
+    validate_history_filename(&filename)?;
+
+    let snapshot_path = get_profile_history_dir(app_handle, profile)?.join(&filename);
+    if !snapshot_path.exists() {
+        return Err(format!("History snapshot not found: {}", filename));
+    }
+
+    // Read it before rotating: rotation may prune the oldest snapshots
+    let snapshot = fs::read(&snapshot_path)
+        .map_err(|e| format!("Failed to read history snapshot: {}", e))?;
+    let config_path = get_named_config_path(app_handle, profile)?;
     if let Some(parent) = config_path.parent() {
         fs::create_dir_all(parent)
             .map_err(|e| format!("Failed to create config directory: {}", e))?;
     }
 
-    // Rotate current file to history before saving new one
-    rotate_to_history(app_handle)?;
+    rotate_to_history(app_handle, profile)?;
+    platform::write_file_atomic(&config_path, &snapshot)
+}
+
+/// List a profile's history snapshots, newest first
+pub fn list_history(app_handle: &tauri::AppHandle, profile: &str) -> Result<Vec<HistoryEntry>, String> {
+    let history_dir = get_profile_history_dir(app_handle, profile)?;
+    if !history_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut entries: Vec<HistoryEntry> = fs::read_dir(&history_dir)
+        .map_err(|e| format!("Failed to read history directory: {}", e))?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let filename = e.file_name().to_string_lossy().to_string();
+            validate_history_filename(&filename).ok()?;
+            let metadata = e.metadata().ok()?;
+            let modified_at = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            Some(HistoryEntry {
+                comment: read_gz_comment(&e.path()),
+                filename,
+                size_bytes: metadata.len(),
+                modified_at,
+            })
+        })
+        .collect();
+
+    // Timestamped names sort chronologically
+    entries.sort_by(|a, b| b.filename.cmp(&a.filename));
+    Ok(entries)
+}
+
+/// Make sure the disk holding `path` can take a write of `size` bytes.
+/// Warns below 3x headroom (history rotation copies the file too), errors if it can't fit at all.
+fn check_disk_space(path: &Path, size: u64) -> Result<(), String> {
+    let dir = path.parent().unwrap_or(path);
+    let space = match platform::get_disk_space_bytes(&dir.to_string_lossy()) {
+        Ok(space) => space,
+        Err(e) => {
+            // Not fatal - the write itself will report a real failure
+            eprintln!("Could not check disk space: {}", e);
+            return Ok(());
+        }
+    };
+
+    if space.available_bytes < size {
+        return Err(format!(
+            "Not enough disk space to save workspace ({} bytes needed, {} available)",
+            size, space.available_bytes
+        ));
+    }
+    if space.available_bytes < size.saturating_mul(3) {
+        eprintln!(
+            "Warning: low disk space while saving workspace ({} bytes available)",
+            space.available_bytes
+        );
+    }
 
-    // Compress and write
-    let file = File::create(&config_path)
-        .map_err(|e| format!("Failed to create config file: {}", e))?;
-    let mut encoder = GzEncoder::new(file, Compression::default());
+    Ok(())
+}
+
+/// Validate a workspace comment (stored in the gzip header, which can't hold NUL bytes)
+fn validate_comment(comment: &str) -> Result<(), String> {
+    if comment.chars().count() > MAX_COMMENT_LEN {
+        return Err(format!("Workspace comment must be at most {} characters", MAX_COMMENT_LEN));
+    }
+    if comment.contains('\0') {
+        return Err("Workspace comment must not contain NUL characters".to_string());
+    }
+    Ok(())
+}
+
+/// gzip extra-field subfield ID for the SHA-256 of the uncompressed JSON
+const CHECKSUM_SUBFIELD_ID: [u8; 2] = *b"MT";
+
+/// Prefix of the error for a workspace whose contents don't match its stored checksum
+pub const CHECKSUM_MISMATCH_ERROR: &str = "WorkspaceChecksumMismatch";
+
+/// gzip extra field (RFC 1952 subfield layout) carrying the checksum of `data`
+fn checksum_extra(data: &str) -> Vec<u8> {
+    use sha2::{Digest, Sha256};
+
+    let digest = Sha256::digest(data.as_bytes());
+    let mut extra = CHECKSUM_SUBFIELD_ID.to_vec();
+    extra.extend_from_slice(&(digest.len() as u16).to_le_bytes());
+    extra.extend_from_slice(&digest);
+    extra
+}
+
+/// Find the checksum subfield in a gzip extra field
+fn checksum_from_extra(mut extra: &[u8]) -> Option<&[u8]> {
+    while extra.len() >= 4 {
+        let len = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        let body = extra.get(4..4 + len)?;
+        if extra[..2] == CHECKSUM_SUBFIELD_ID {
+            return Some(body);
+        }
+        extra = &extra[4 + len..];
+    }
+    None
+}
+
+/// Check decompressed data against the stored checksum.
+/// Files written before checksums existed have none and are accepted unverified.
+fn verify_checksum(data: &str, extra: Option<&[u8]>) -> Result<(), String> {
+    let Some(stored) = extra.and_then(checksum_from_extra) else {
+        return Ok(());
+    };
+    if checksum_from_extra(&checksum_extra(data)) != Some(stored) {
+        return Err(format!(
+            "{}: the workspace file decompressed but its contents are corrupt",
+            CHECKSUM_MISMATCH_ERROR
+        ));
+    }
+    Ok(())
+}
+
+/// Gzip workspace data in memory, storing `comment` and a checksum in the gzip header
+fn compress_workspace(data: &str, level: Compression, comment: Option<&str>) -> Result<Vec<u8>, String> {
+    let mut builder = GzBuilder::new().extra(checksum_extra(data));
+    if let Some(comment) = comment.filter(|c| !c.is_empty()) {
+        builder = builder.comment(comment.as_bytes());
+    }
+
+    let mut encoder = builder.write(Vec::new(), level);
     encoder.write_all(data.as_bytes())
         .map_err(|e| format!("Failed to write compressed data: {}", e))?;
     encoder.finish()
-        .map_err(|e| format!("Failed to finish compression: {}", e))?;
+        .map_err(|e| format!("Failed to finish compression: {}", e))
+}
 
+/// Read the comment from a gzip file's header without decompressing the payload
+fn read_gz_comment(path: &Path) -> Option<String> {
+    let file = File::open(path).ok()?;
+    let decoder = GzDecoder::new(file);
+    let comment = decoder.header()?.comment()?;
+    Some(String::from_utf8_lossy(comment).to_string())
+}
+
+/// Save workspace data to the config file (gzip compressed)
+pub fn save_workspace(app_handle: &tauri::AppHandle, data: String) -> Result<bool, String> {
+    save_workspace_with_comment(app_handle, data, None)
+}
+
+/// Save workspace data with an optional description in the gzip header
+pub fn save_workspace_with_comment(
+    app_handle: &tauri::AppHandle,
+    data: String,
+    comment: Option<String>,
+) -> Result<bool, String> {
+    save_profile(app_handle, DEFAULT_WORKSPACE_NAME, data, comment)
+}
+
+/// Save a workspace profile (`workspaces_<profile>.json.gz`), rotating the
+/// previous version into the profile's history
+pub fn save_profile(
+    app_handle: &tauri::AppHandle,
+    profile: &str,
+    data: String,
+    comment: Option<String>,
+) -> Result<bool, String> {
+    if let Some(ref comment) = comment {
+        validate_comment(comment)?;
+    }
+
+    let config_path = get_named_config_path(app_handle, profile)?;
+
+    // Ensure parent directory exists
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+
+    let data = wrap_workspace(&data)?;
+
+    // Compress in memory first so we know the exact size before touching disk
+    let level = load_settings(app_handle).compression();
+    let compressed = compress_workspace(&data, level, comment.as_deref())?;
+
+    check_disk_space(&config_path, compressed.len() as u64)?;
+
+    // Rotate current file to history before saving new one
+    rotate_to_history(app_handle, profile)?;
+
+    // Temp file + rename, so a crash mid-write can't leave a truncated config
+    platform::write_file_atomic(&config_path, &compressed)?;
+
+    Ok(true)
+}
+
+/// Replace the comment of a saved workspace without rotating it to history
+pub fn update_comment(
+    app_handle: &tauri::AppHandle,
+    name: &str,
+    comment: Option<String>,
+) -> Result<(), String> {
+    if let Some(ref comment) = comment {
+        validate_comment(comment)?;
+    }
+
+    let path = get_named_config_path(app_handle, name)?;
+    if !path.exists() {
+        return Err(format!("Workspace '{}' does not exist", name));
+    }
+
+    let data = read_gz(&path)?;
+    let level = load_settings(app_handle).compression();
+    let compressed = compress_workspace(&data, level, comment.as_deref())?;
+    platform::write_file_atomic(&path, &compressed)
+}
+
+/// Whether a JSON object looks like a serialized `EncryptedEnvelope`
+fn is_envelope(map: &serde_json::Map<String, serde_json::Value>) -> bool {
+    ["ciphertext", "nonce", "salt"]
+        .iter()
+        .all(|field| map.get(*field).map(|v| v.is_string()).unwrap_or(false))
+}
+
+/// Re-encrypt every envelope nested anywhere in `value` from `old_password` to
+/// `new_password`. Returns how many envelopes were rotated.
+fn rotate_envelopes(
+    value: &mut serde_json::Value,
+    old_password: &str,
+    new_password: &str,
+) -> Result<usize, String> {
+    match value {
+        serde_json::Value::Object(map) if is_envelope(map) => {
+            let envelope: EncryptedEnvelope = serde_json::from_value(serde_json::Value::Object(map.clone()))
+                .map_err(|e| format!("Failed to parse encrypted value: {}", e))?;
+            let rotated = crypto::reencrypt(&envelope, old_password, new_password, envelope.hint.clone())?;
+            *value = serde_json::to_value(&rotated)
+                .map_err(|e| format!("Failed to serialize encrypted value: {}", e))?;
+            Ok(1)
+        }
+        serde_json::Value::Object(map) => map
+            .values_mut()
+            .map(|v| rotate_envelopes(v, old_password, new_password))
+            .sum(),
+        serde_json::Value::Array(items) => items
+            .iter_mut()
+            .map(|v| rotate_envelopes(v, old_password, new_password))
+            .sum(),
+        _ => Ok(0),
+    }
+}
+
+/// Re-encrypt all field-level encrypted values in the workspace with a new password.
+/// Nothing is written unless every value decrypts with `old_password`.
+pub fn rotate_encryption_key(
+    app_handle: &tauri::AppHandle,
+    old_password: &str,
+    new_password: &str,
+) -> Result<(), String> {
+    if new_password.is_empty() {
+        return Err("New password must not be empty".to_string());
+    }
+
+    // Read the raw file so a whole-file encrypted workspace gets its envelope rotated too
+    let data = read_workspace_file(app_handle)?.ok_or_else(|| "No workspace file to update".to_string())?;
+    let mut root: serde_json::Value =
+        serde_json::from_str(&data).map_err(|e| format!("Failed to parse workspace JSON: {}", e))?;
+
+    if rotate_envelopes(&mut root, old_password, new_password)? == 0 {
+        return Err("No encrypted values found in workspace".to_string());
+    }
+
+    let data = serde_json::to_string(&root)
+        .map_err(|e| format!("Failed to serialize workspace JSON: {}", e))?;
+    let comment = read_gz_comment(&get_config_path(app_handle)?);
+    save_workspace_with_comment(app_handle, data, comment)?;
+    Ok(())
+}
+
+/// Encrypt workspace JSON into the `EncryptedWorkspace` wrapper
+fn encrypt_workspace(data: &str, password: &str) -> Result<String, String> {
+    if password.is_empty() {
+        return Err("Password must not be empty".to_string());
+    }
+    let wrapper = EncryptedWorkspace {
+        format: ENCRYPTED_WORKSPACE_FORMAT.to_string(),
+        envelope: crypto::encrypt(data, password, None)?,
+    };
+    serde_json::to_string(&wrapper).map_err(|e| format!("Failed to serialize encrypted workspace: {}", e))
+}
+
+/// The envelope of an encrypted workspace, or None for a plain one
+fn parse_encrypted_workspace(data: &str) -> Option<EncryptedEnvelope> {
+    serde_json::from_str::<EncryptedWorkspace>(data)
+        .ok()
+        .filter(|wrapper| wrapper.format == ENCRYPTED_WORKSPACE_FORMAT)
+        .map(|wrapper| wrapper.envelope)
+}
+
+/// Save workspace data encrypted with `password`, then gzip it like a normal save
+pub fn save_workspace_encrypted(
+    app_handle: &tauri::AppHandle,
+    data: String,
+    password: &str,
+) -> Result<bool, String> {
+    let encrypted = encrypt_workspace(&data, password)?;
+    save_workspace(app_handle, encrypted)?;
+
+    // Snapshots from before encryption was turned on would still expose the data
+    purge_plaintext_history(&get_profile_history_dir(app_handle, DEFAULT_WORKSPACE_NAME)?)?;
     Ok(true)
 }
 
+/// Delete history snapshots in `history_dir` that aren't encrypted workspaces.
+/// Unreadable snapshots are deleted too, since they may hold plaintext.
+fn purge_plaintext_history(history_dir: &Path) -> Result<usize, String> {
+    let entries = match fs::read_dir(history_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(format!("Failed to read history dir: {}", e)),
+    };
+
+    let mut removed = 0;
+    for entry in entries.filter_map(|e| e.ok()) {
+        let filename = entry.file_name().to_string_lossy().to_string();
+        if validate_history_filename(&filename).is_err() {
+            continue;
+        }
+        let encrypted = read_gz(&entry.path())
+            .and_then(|stored| unwrap_workspace(&stored))
+            .map(|workspace| parse_encrypted_workspace(&workspace.data).is_some())
+            .unwrap_or(false);
+        if !encrypted {
+            fs::remove_file(entry.path())
+                .map_err(|e| format!("Failed to remove history file: {}", e))?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Load workspace data, decrypting it if it was saved encrypted.
+/// Plain workspaces load as-is so users can opt in at any time.
+pub fn load_workspace_encrypted(
+    app_handle: &tauri::AppHandle,
+    password: &str,
+) -> Result<Option<String>, String> {
+    match read_workspace_file(app_handle)? {
+        Some(data) => match parse_encrypted_workspace(&data) {
+            Some(envelope) => crypto::decrypt(&envelope, password).map(Some),
+            None => Ok(Some(data)),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Check that JSON looks like a workspace before it replaces the current one
+fn validate_workspace_json(data: &str) -> Result<(), String> {
+    let root: serde_json::Value =
+        serde_json::from_str(data).map_err(|e| format!("Failed to parse workspace JSON: {}", e))?;
+    if !root.is_object() {
+        return Err("Workspace JSON must be an object".to_string());
+    }
+    sessions_array(&root)?;
+    Ok(())
+}
+
+/// Read an exported workspace, gzip-compressed or plain JSON
+fn read_workspace_export(path: &Path) -> Result<String, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read workspace file: {}", e))?;
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        return read_gz(path);
+    }
+    String::from_utf8(bytes).map_err(|e| format!("Workspace file is not valid UTF-8: {}", e))
+}
+
+/// Write the current workspace to `path` for moving it to another machine.
+/// `current_password` unlocks an encrypted workspace; with `export_password`
+/// the exported file is encrypted with it.
+pub fn export_workspace(
+    app_handle: &tauri::AppHandle,
+    path: &str,
+    current_password: Option<&str>,
+    export_password: Option<&str>,
+) -> Result<(), String> {
+    let data = match current_password {
+        Some(password) => load_workspace_encrypted(app_handle, password)?,
+        None => load_workspace(app_handle)?,
+    }
+    .ok_or_else(|| "No workspace to export".to_string())?;
+
+    let data = match export_password {
+        Some(password) => encrypt_workspace(&data, password)?,
+        None => data,
+    };
+    write_gz(Path::new(path), &data)
+}
+
+/// Replace the current workspace with an exported one, rotating the current
+/// one to history. Encrypted exports stay encrypted with their password.
+pub fn import_workspace(
+    app_handle: &tauri::AppHandle,
+    path: &str,
+    password: Option<&str>,
+) -> Result<(), String> {
+    let data = unwrap_workspace(&read_workspace_export(Path::new(path))?)?.data;
+    match parse_encrypted_workspace(&data) {
+        Some(envelope) => {
+            let password = password
+                .ok_or_else(|| "Imported workspace is encrypted, a password is required".to_string())?;
+            validate_workspace_json(&crypto::decrypt(&envelope, password)?)?;
+        }
+        None => validate_workspace_json(&data)?,
+    }
+
+    save_workspace(app_handle, data)?;
+    Ok(())
+}
+
 /// Load workspace data from the config file (gzip compressed)
 pub fn load_workspace(app_handle: &tauri::AppHandle) -> Result<Option<String>, String> {
-    let config_path = get_config_path(app_handle)?;
+    load_profile(app_handle, DEFAULT_WORKSPACE_NAME)
+}
+
+/// Load a workspace profile; None if it has never been saved
+pub fn load_profile(app_handle: &tauri::AppHandle, profile: &str) -> Result<Option<String>, String> {
+    let data = read_profile_file(app_handle, profile)?;
+    if data.as_deref().and_then(parse_encrypted_workspace).is_some() {
+        return Err("Workspace is encrypted, load it with a password".to_string());
+    }
+    Ok(data)
+}
+
+/// Read the default config file as stored, without decrypting
+fn read_workspace_file(app_handle: &tauri::AppHandle) -> Result<Option<String>, String> {
+    read_profile_file(app_handle, DEFAULT_WORKSPACE_NAME)
+}
+
+/// Load a profile along with the schema version found on disk
+pub fn load_profile_versioned(
+    app_handle: &tauri::AppHandle,
+    profile: &str,
+) -> Result<Option<VersionedWorkspace>, String> {
+    let workspace = match read_stored_profile(app_handle, profile)? {
+        Some(stored) => unwrap_workspace(&stored)?,
+        None => return Ok(None),
+    };
+    if parse_encrypted_workspace(&workspace.data).is_some() {
+        return Err("Workspace is encrypted, load it with a password".to_string());
+    }
+    Ok(Some(workspace))
+}
+
+/// Read a profile's config file without decrypting, migrated and unwrapped to app data
+fn read_profile_file(app_handle: &tauri::AppHandle, profile: &str) -> Result<Option<String>, String> {
+    match read_stored_profile(app_handle, profile)? {
+        Some(stored) => Ok(Some(unwrap_workspace(&stored)?.data)),
+        None => Ok(None),
+    }
+}
+
+/// Read a profile's config file exactly as stored
+fn read_stored_profile(app_handle: &tauri::AppHandle, profile: &str) -> Result<Option<String>, String> {
+    let config_path = get_named_config_path(app_handle, profile)?;
 
     if !config_path.exists() {
-        // Try to load from legacy uncompressed file
-        let legacy_path = config_path.with_extension("");
-        let legacy_path = legacy_path.with_extension("json");
-        if legacy_path.exists() {
-            return fs::read_to_string(&legacy_path)
+        // Fall back to uncompressed workspaces.json (plaintext debug saves and legacy files)
+        let plaintext_path = get_plaintext_path(&config_path);
+        if plaintext_path.exists() {
+            return fs::read_to_string(&plaintext_path)
                 .map(Some)
-                .map_err(|e| format!("Failed to read legacy config: {}", e));
+                .map_err(|e| format!("Failed to read uncompressed config: {}", e));
         }
         return Ok(None);
     }
 
-    // Read and decompress
-    let file = File::open(&config_path)
+    let history_dir = get_profile_history_dir(app_handle, profile)?;
+    let (data, recovery) = read_or_recover(&config_path, &history_dir, profile)?;
+    if let Some(recovery) = recovery {
+        eprintln!("Recovered workspace from {}", recovery.filename);
+        let _ = app_handle.emit("workspace:recovered", &recovery);
+    }
+    Ok(data)
+}
+
+/// Payload of `workspace:recovered`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceRecovery {
+    pub profile: String,
+    /// History snapshot that was loaded instead
+    pub filename: String,
+    /// Why the config file couldn't be read
+    pub reason: String,
+    /// The file decompressed but failed its checksum
+    pub checksum_mismatch: bool,
+}
+
+/// Read a gzip config, falling back to the newest valid snapshot in `history_dir` if it's corrupt.
+/// A checksum mismatch with nothing to recover is an error; other corruption starts fresh (None).
+/// Failing to read the file at all is an error, not corruption, so it never triggers recovery.
+fn read_or_recover(
+    config_path: &Path,
+    history_dir: &Path,
+    profile: &str,
+) -> Result<(Option<String>, Option<WorkspaceRecovery>), String> {
+    let bytes = fs::read(config_path).map_err(|e| format!("Failed to open config file: {}", e))?;
+    let decoded = decode_gz(&bytes).and_then(|data| {
+        serde_json::from_str::<serde::de::IgnoredAny>(&data)
+            .map_err(|e| format!("Failed to parse workspace JSON: {}", e))?;
+        Ok(data)
+    });
+    let reason = match decoded {
+        Ok(data) => return Ok((Some(data), None)),
+        Err(e) => e,
+    };
+    let checksum_mismatch = reason.starts_with(CHECKSUM_MISMATCH_ERROR);
+    eprintln!("Workspace file is corrupt ({}), trying history snapshots", reason);
+
+    match recover_from_history(history_dir) {
+        Some((filename, data)) => Ok((
+            Some(data),
+            Some(WorkspaceRecovery {
+                profile: profile.to_string(),
+                filename,
+                reason,
+                checksum_mismatch,
+            }),
+        )),
+        // Surface the mismatch rather than silently discarding the user's workspace
+        None if checksum_mismatch => Err(reason),
+        // Start fresh rather than leaving the user stuck on an error
+        None => Ok((None, None)),
+    }
+}
+
+/// Schema version of stored workspace JSON (0 for legacy unwrapped data)
+fn detect_schema_version(root: &serde_json::Value) -> u32 {
+    match (root.get("schema_version").and_then(|v| v.as_u64()), root.get("data")) {
+        (Some(version), Some(_)) => u32::try_from(version).unwrap_or(u32::MAX),
+        _ => 0,
+    }
+}
+
+/// Upgrade stored workspace JSON from `from_version` to `WORKSPACE_SCHEMA_VERSION`.
+/// Each step migrates one version, so new formats only need to add their own arm.
+pub fn migrate_workspace(data: &str, from_version: u32) -> Result<String, String> {
+    let mut data = data.to_string();
+    let mut version = from_version;
+    while version < WORKSPACE_SCHEMA_VERSION {
+        data = match version {
+            // v0 -> v1: wrap the bare app data in a versioned envelope
+            0 => wrap_workspace(&data)?,
+            _ => unreachable!("no migration from schema version {}", version),
+        };
+        version += 1;
+    }
+    if version > WORKSPACE_SCHEMA_VERSION {
+        return Err(format!(
+            "Workspace schema version {} is newer than supported version {}",
+            version, WORKSPACE_SCHEMA_VERSION
+        ));
+    }
+    Ok(data)
+}
+
+/// Wrap app data in the current schema envelope
+fn wrap_workspace(data: &str) -> Result<String, String> {
+    serde_json::from_str::<serde::de::IgnoredAny>(data)
+        .map_err(|e| format!("Failed to parse workspace JSON: {}", e))?;
+    Ok(format!(
+        r#"{{"schema_version":{},"data":{}}}"#,
+        WORKSPACE_SCHEMA_VERSION, data
+    ))
+}
+
+/// Stored envelope; the app data is kept as the exact text the frontend saved
+#[derive(Deserialize)]
+struct StoredEnvelope {
+    data: Option<Box<serde_json::value::RawValue>>,
+}
+
+/// Migrate stored workspace JSON and extract its app data
+fn unwrap_workspace(stored: &str) -> Result<VersionedWorkspace, String> {
+    let root: serde_json::Value = serde_json::from_str(stored)
+        .map_err(|e| format!("Failed to parse workspace JSON: {}", e))?;
+    let schema_version = detect_schema_version(&root);
+    let migrated = migrate_workspace(stored, schema_version)?;
+    let envelope: StoredEnvelope = serde_json::from_str(&migrated)
+        .map_err(|e| format!("Failed to parse workspace JSON: {}", e))?;
+    let data = envelope
+        .data
+        .ok_or_else(|| "Workspace envelope has no data".to_string())?
+        .get()
+        .to_string();
+    Ok(VersionedWorkspace { schema_version, data })
+}
+
+/// Newest history snapshot that decompresses to valid JSON, as (filename, data)
+fn recover_from_history(history_dir: &Path) -> Option<(String, String)> {
+    let mut filenames: Vec<String> = fs::read_dir(history_dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|name| validate_history_filename(name).is_ok())
+        .collect();
+    // Timestamped names sort chronologically
+    filenames.sort_by(|a, b| b.cmp(a));
+
+    filenames.into_iter().find_map(|filename| {
+        let data = read_gz(&history_dir.join(&filename)).ok()?;
+        serde_json::from_str::<serde_json::Value>(&data).ok()?;
+        Some((filename, data))
+    })
+}
+
+/// Path of the uncompressed workspace file next to a `.json.gz` config path
+fn get_plaintext_path(config_path: &Path) -> PathBuf {
+    config_path.with_extension("").with_extension("json")
+}
+
+/// Save workspace data uncompressed to `workspaces.json` (development/debugging only).
+/// The compressed file is rotated to history and removed so loads pick up the plaintext file.
+pub fn save_workspace_plaintext(app_handle: &tauri::AppHandle, data: String) -> Result<bool, String> {
+    eprintln!("Warning: saving workspace uncompressed (debug mode)");
+
+    let config_path = get_config_path(app_handle)?;
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+
+    check_disk_space(&config_path, data.len() as u64)?;
+    rotate_to_history(app_handle, DEFAULT_WORKSPACE_NAME)?;
+
+    platform::write_file_atomic(&get_plaintext_path(&config_path), data.as_bytes())?;
+
+    if config_path.exists() {
+        fs::remove_file(&config_path)
+            .map_err(|e| format!("Failed to remove compressed config: {}", e))?;
+    }
+
+    Ok(true)
+}
+
+/// Remove the uncompressed workspace file after switching back to compressed saves
+pub fn remove_plaintext_workspace(app_handle: &tauri::AppHandle) -> Result<(), String> {
+    let plaintext_path = get_plaintext_path(&get_config_path(app_handle)?);
+    if plaintext_path.exists() {
+        fs::remove_file(&plaintext_path)
+            .map_err(|e| format!("Failed to remove uncompressed config: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Outcome of `optimize_workspace`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OptimizeResult {
+    pub original_bytes: u64,
+    pub optimized_bytes: u64,
+    /// optimized / original (1.0 when the file was left unchanged)
+    pub ratio: f64,
+}
+
+/// Recompress the workspace file at maximum compression.
+/// The original is rotated to history first and kept if recompression doesn't help.
+pub fn optimize_workspace(app_handle: &tauri::AppHandle) -> Result<OptimizeResult, String> {
+    let config_path = get_config_path(app_handle)?;
+    if !config_path.exists() {
+        return Err("No workspace file to optimize".to_string());
+    }
+
+    let original_bytes = fs::metadata(&config_path)
+        .map_err(|e| format!("Failed to read config metadata: {}", e))?
+        .len();
+    let data = read_gz(&config_path)?;
+    let comment = read_gz_comment(&config_path);
+
+    let compressed = compress_workspace(&data, Compression::best(), comment.as_deref())?;
+    let optimized_bytes = compressed.len() as u64;
+
+    if optimized_bytes >= original_bytes {
+        // Not smaller - leave the original in place
+        return Ok(OptimizeResult {
+            original_bytes,
+            optimized_bytes: original_bytes,
+            ratio: 1.0,
+        });
+    }
+
+    rotate_to_history(app_handle, DEFAULT_WORKSPACE_NAME)?;
+    platform::write_file_atomic(&config_path, &compressed)?;
+
+    Ok(OptimizeResult {
+        original_bytes,
+        optimized_bytes,
+        ratio: optimized_bytes as f64 / original_bytes as f64,
+    })
+}
+
+/// Read and decompress a gzip workspace file, verifying its checksum if it has one
+fn read_gz(path: &Path) -> Result<String, String> {
+    let bytes = fs::read(path)
         .map_err(|e| format!("Failed to open config file: {}", e))?;
-    let mut decoder = GzDecoder::new(file);
+    decode_gz(&bytes)
+}
+
+/// Decompress gzip workspace bytes and verify their checksum
+fn decode_gz(bytes: &[u8]) -> Result<String, String> {
+    let mut decoder = GzDecoder::new(bytes);
     let mut data = String::new();
     decoder.read_to_string(&mut data)
         .map_err(|e| format!("Failed to decompress data: {}", e))?;
+    verify_checksum(&data, decoder.header().and_then(|header| header.extra()))?;
 
-    Ok(Some(data))
+    Ok(data)
+}
+
+/// Compress and atomically write a gzip workspace file
+fn write_gz(path: &Path, data: &str) -> Result<(), String> {
+    let compressed = compress_workspace(data, Compression::default(), None)?;
+    platform::write_file_atomic(path, &compressed)
+}
+
+/// Copy a workspace file to `dest`, renaming its top-level `name` field if present
+fn clone_workspace_file(src: &Path, dest: &Path, dest_name: &str) -> Result<(), String> {
+    if dest.exists() {
+        return Err(format!("Workspace '{}' already exists", dest_name));
+    }
+
+    let data = read_gz(src)?;
+    let mut root: serde_json::Value =
+        serde_json::from_str(&data).map_err(|e| format!("Failed to parse workspace JSON: {}", e))?;
+    let body = if detect_schema_version(&root) > 0 { &mut root["data"] } else { &mut root };
+    if let Some(name) = body.get_mut("name") {
+        *name = serde_json::Value::String(dest_name.to_string());
+    }
+
+    let data = serde_json::to_string(&root)
+        .map_err(|e| format!("Failed to serialize workspace JSON: {}", e))?;
+    write_gz(dest, &data)
+}
+
+/// Copy a workspace (default if `src_name` is None) under a new name
+pub fn clone_workspace(
+    app_handle: &tauri::AppHandle,
+    src_name: Option<String>,
+    dest_name: String,
+) -> Result<(), String> {
+    let src_name = src_name.unwrap_or_else(|| DEFAULT_WORKSPACE_NAME.to_string());
+    let src = get_named_config_path(app_handle, &src_name)?;
+    if !src.exists() {
+        return Err(format!("Workspace '{}' does not exist", src_name));
+    }
+
+    let dest = get_named_config_path(app_handle, &dest_name)?;
+    clone_workspace_file(&src, &dest, &dest_name)
+}
+
+
+/// Render a JSON field as a plain cell value (strings unquoted, missing as empty)
+fn json_cell(value: Option<&serde_json::Value>) -> String {
+    match value {
+        None | Some(serde_json::Value::Null) => String::new(),
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// Get the terminal list of a parsed workspace JSON.
+/// Current format stores `terminals`, older exports used `tabs`.
+fn sessions_array(root: &serde_json::Value) -> Result<&Vec<serde_json::Value>, String> {
+    root.get("terminals")
+        .or_else(|| root.get("tabs"))
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| "Workspace JSON has no terminals array".to_string())
+}
+
+/// Get the terminal IDs stored in a workspace JSON
+pub fn terminal_ids(json: &str) -> Result<Vec<String>, String> {
+    let root: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse workspace JSON: {}", e))?;
+
+    Ok(sessions_array(&root)?
+        .iter()
+        .filter_map(|session| session.get("id").and_then(|id| id.as_str()))
+        .map(|id| id.to_string())
+        .collect())
+}
+
+/// Remove terminals whose `id` already appeared earlier in the list.
+/// Returns the number of entries removed.
+fn dedupe_sessions(root: &mut serde_json::Value) -> Result<usize, String> {
+    let key = if root.get("terminals").is_some() { "terminals" } else { "tabs" };
+    let sessions = root
+        .get_mut(key)
+        .and_then(|v| v.as_array_mut())
+        .ok_or_else(|| "Workspace JSON has no terminals array".to_string())?;
+
+    let mut seen = std::collections::HashSet::new();
+    let before = sessions.len();
+    sessions.retain(|session| match session.get("id").and_then(|id| id.as_str()) {
+        Some(id) => seen.insert(id.to_string()),
+        // Entries without an id can't be duplicates of anything
+        None => true,
+    });
+
+    Ok(before - sessions.len())
+}
+
+/// Remove duplicate terminal entries (by `id`, keeping the first) from a workspace JSON
+pub fn deduplicate_tabs(json: &str) -> Result<String, String> {
+    let mut root: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse workspace JSON: {}", e))?;
+    dedupe_sessions(&mut root)?;
+    serde_json::to_string(&root).map_err(|e| format!("Failed to serialize workspace JSON: {}", e))
+}
+
+/// Count duplicate terminal entries in a workspace JSON
+pub fn count_duplicate_tabs(json: &str) -> Result<usize, String> {
+    let mut root: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse workspace JSON: {}", e))?;
+    dedupe_sessions(&mut root)
+}
+
+/// Re-serialize workspace JSON without whitespace
+pub fn compact_json(json: &str) -> Result<String, String> {
+    let root: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse workspace JSON: {}", e))?;
+    serde_json::to_string(&root).map_err(|e| format!("Failed to serialize workspace JSON: {}", e))
+}
+
+/// Re-serialize workspace JSON with indentation
+pub fn prettify_json(json: &str) -> Result<String, String> {
+    let root: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse workspace JSON: {}", e))?;
+    serde_json::to_string_pretty(&root)
+        .map_err(|e| format!("Failed to serialize workspace JSON: {}", e))
+}
+
+/// Serialize the terminal list of a workspace JSON with the given delimiter
+fn export_sessions_delimited(json: &str, delimiter: u8) -> Result<String, String> {
+    let root: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse workspace JSON: {}", e))?;
+    let sessions = sessions_array(&root)?;
+
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(Vec::new());
+
+    writer
+        .write_record(["id", "cwd", "title", "created_at"])
+        .map_err(|e| format!("Failed to write header: {}", e))?;
+
+    for session in sessions {
+        let created_at = session.get("createdAt").or_else(|| session.get("created_at"));
+        writer
+            .write_record([
+                json_cell(session.get("id")),
+                json_cell(session.get("cwd")),
+                json_cell(session.get("title")),
+                json_cell(created_at),
+            ])
+            .map_err(|e| format!("Failed to write record: {}", e))?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| format!("Failed to flush CSV writer: {}", e))?;
+    String::from_utf8(bytes).map_err(|e| format!("UTF-8 encode error: {}", e))
+}
+
+/// Export the PTY session list of a workspace JSON as CSV
+pub fn export_sessions_csv(json: &str) -> Result<String, String> {
+    export_sessions_delimited(json, b',')
+}
+
+/// Export the PTY session list of a workspace JSON as TSV
+pub fn export_sessions_tsv(json: &str) -> Result<String, String> {
+    export_sessions_delimited(json, b'\t')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORKSPACE_JSON: &str = r#"{
+        "workspaces": [],
+        "terminals": [
+            {"id": "t1", "cwd": "/home/user", "title": "Terminal 1", "createdAt": 1700000000},
+            {"id": "t2", "cwd": "/tmp/a, b", "title": "Build \"prod\""},
+            {"id": "t3", "cwd": "/srv", "title": "Logs"}
+        ]
+    }"#;
+
+    #[test]
+    fn test_export_sessions_csv() {
+        let csv = export_sessions_csv(WORKSPACE_JSON).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "id,cwd,title,created_at");
+        assert_eq!(lines[1], "t1,/home/user,Terminal 1,1700000000");
+        assert_eq!(lines[2], r#"t2,"/tmp/a, b","Build ""prod""","#);
+        assert_eq!(lines[3], "t3,/srv,Logs,");
+    }
+
+    #[test]
+    fn test_export_sessions_tsv() {
+        let tsv = export_sessions_tsv(WORKSPACE_JSON).unwrap();
+        let lines: Vec<&str> = tsv.lines().collect();
+        assert_eq!(lines[0], "id\tcwd\ttitle\tcreated_at");
+        assert_eq!(lines[1], "t1\t/home/user\tTerminal 1\t1700000000");
+    }
+
+    #[test]
+    fn test_terminal_ids() {
+        assert_eq!(terminal_ids(WORKSPACE_JSON).unwrap(), vec!["t1", "t2", "t3"]);
+    }
+
+    #[test]
+    fn test_deduplicate_tabs() {
+        let json = r#"{"terminals": [
+            {"id": "a", "title": "first"},
+            {"id": "b", "title": "second"},
+            {"id": "a", "title": "duplicate"}
+        ]}"#;
+
+        assert_eq!(count_duplicate_tabs(json).unwrap(), 1);
+
+        let deduped = deduplicate_tabs(json).unwrap();
+        let root: serde_json::Value = serde_json::from_str(&deduped).unwrap();
+        let terminals = root["terminals"].as_array().unwrap();
+        assert_eq!(terminals.len(), 2);
+        assert_eq!(terminals[0]["title"], "first");
+        assert_eq!(terminals[1]["id"], "b");
+        assert_eq!(count_duplicate_tabs(&deduped).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_clone_workspace_file_is_independent() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("workspaces.json.gz");
+        let dest = dir.path().join("workspaces_copy.json.gz");
+        write_gz(&src, r#"{"name": "original", "terminals": []}"#).unwrap();
+
+        clone_workspace_file(&src, &dest, "copy").unwrap();
+        let cloned: serde_json::Value = serde_json::from_str(&read_gz(&dest).unwrap()).unwrap();
+        assert_eq!(cloned["name"], "copy");
+
+        // Changing the original must not affect the clone
+        write_gz(&src, r#"{"name": "changed", "terminals": [{"id": "x"}]}"#).unwrap();
+        let cloned: serde_json::Value = serde_json::from_str(&read_gz(&dest).unwrap()).unwrap();
+        assert_eq!(cloned["name"], "copy");
+        assert_eq!(cloned["terminals"].as_array().unwrap().len(), 0);
+
+        // Refuse to overwrite an existing destination
+        assert!(clone_workspace_file(&src, &dest, "copy").is_err());
+    }
+
+    #[test]
+    fn test_validate_history_filename() {
+        assert!(validate_history_filename("workspaces_20250101_120000.json.gz").is_ok());
+        assert!(validate_history_filename("workspaces_2025010_120000.json.gz").is_err());
+        assert!(validate_history_filename("workspaces_20250101-120000.json.gz").is_err());
+        assert!(validate_history_filename("../workspaces_20250101_120000.json.gz").is_err());
+        assert!(validate_history_filename("workspaces_../../etc/passwd.json.gz").is_err());
+        assert!(validate_history_filename("workspaces.json.gz").is_err());
+    }
+
+    #[test]
+    fn test_workspace_name_from_file() {
+        assert_eq!(workspace_name_from_file("workspaces.json.gz"), Some("default".to_string()));
+        assert_eq!(workspace_name_from_file("workspaces_work.json.gz"), Some("work".to_string()));
+        assert_eq!(workspace_name_from_file("workspaces_.json.gz"), None);
+        assert_eq!(workspace_name_from_file("workspaces.json"), None);
+        assert_eq!(workspace_name_from_file("other.json.gz"), None);
+    }
+
+    #[test]
+    fn test_export_sessions_missing_terminals() {
+        assert!(export_sessions_csv(r#"{"workspaces": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_gz_comment_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("workspaces.json.gz");
+
+        let compressed =
+            compress_workspace(WORKSPACE_JSON, Compression::default(), Some("before refactor")).unwrap();
+        fs::write(&path, compressed).unwrap();
+        assert_eq!(read_gz_comment(&path), Some("before refactor".to_string()));
+        assert_eq!(read_gz(&path).unwrap(), WORKSPACE_JSON);
+
+        let compressed = compress_workspace(WORKSPACE_JSON, Compression::default(), None).unwrap();
+        fs::write(&path, compressed).unwrap();
+        assert_eq!(read_gz_comment(&path), None);
+
+        assert!(validate_comment(&"x".repeat(MAX_COMMENT_LEN)).is_ok());
+        assert!(validate_comment(&"x".repeat(MAX_COMMENT_LEN + 1)).is_err());
+        assert!(validate_comment("a\0b").is_err());
+    }
+
+    #[test]
+    fn test_rotate_envelopes() {
+        let secret = crypto::encrypt("s3cret", "old", Some("pet".to_string())).unwrap();
+        let mut root = serde_json::json!({
+            "terminals": [{"id": "t1", "env": {"TOKEN": secret}}],
+            "name": "work"
+        });
+
+        assert!(rotate_envelopes(&mut root.clone(), "wrong", "new").is_err());
+        assert_eq!(rotate_envelopes(&mut root, "old", "new").unwrap(), 1);
+
+        let rotated: EncryptedEnvelope =
+            serde_json::from_value(root["terminals"][0]["env"]["TOKEN"].clone()).unwrap();
+        assert_eq!(crypto::decrypt(&rotated, "new").unwrap(), "s3cret");
+        assert_eq!(rotated.hint, Some("pet".to_string()));
+        assert_eq!(root["name"], "work");
+    }
+
+    #[test]
+    fn test_compact_and_prettify_json() {
+        let compact = compact_json(WORKSPACE_JSON).unwrap();
+        assert_eq!(
+            compact,
+            concat!(
+                r#"{"terminals":[{"createdAt":1700000000,"cwd":"/home/user","id":"t1","title":"Terminal 1"},"#,
+                r#"{"cwd":"/tmp/a, b","id":"t2","title":"Build \"prod\""},"#,
+                r#"{"cwd":"/srv","id":"t3","title":"Logs"}],"workspaces":[]}"#
+            )
+        );
+
+        let pretty = prettify_json(&compact).unwrap();
+        assert!(pretty.contains("\n  "));
+        assert_eq!(compact_json(&pretty).unwrap(), compact);
+
+        assert!(compact_json("{not json").is_err());
+    }
+
+    #[test]
+    fn test_encrypted_workspace_roundtrip() {
+        let data = r#"{"terminals":[{"id":"t1","cwd":"/work"}]}"#;
+        let encrypted = encrypt_workspace(data, "hunter2").unwrap();
+        assert!(!encrypted.contains("/work"));
+
+        let envelope = parse_encrypted_workspace(&encrypted).unwrap();
+        assert_eq!(crypto::decrypt(&envelope, "hunter2").unwrap(), data);
+        assert!(crypto::decrypt(&envelope, "wrong").is_err());
+
+        // Plain workspaces aren't mistaken for encrypted ones
+        assert!(parse_encrypted_workspace(data).is_none());
+        assert!(encrypt_workspace(data, "").is_err());
+    }
+
+    #[test]
+    fn test_failed_write_keeps_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("workspaces.json.gz");
+        write_gz(&path, r#"{"name": "original"}"#).unwrap();
+
+        // A write that fails part way leaves the original and no temp file behind
+        let result = platform::write_file_atomic_with(&path, |file| {
+            file.write_all(b"partial").unwrap();
+            Err("disk full".to_string())
+        });
+        assert_eq!(result.unwrap_err(), "disk full");
+        assert_eq!(read_gz(&path).unwrap(), r#"{"name": "original"}"#);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        // Overlapping writes each get their own temp file
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                thread::spawn(move || write_gz(&path, &format!(r#"{{"name": "writer {}"}}"#, i)))
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap().unwrap();
+        }
+        assert!(read_gz(&path).unwrap().starts_with(r#"{"name": "writer "#));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_workspace_settings_validation() {
+        let defaults = WorkspaceSettings::default();
+        assert!(defaults.validate().is_ok());
+        assert_eq!(defaults.history_limit(), MAX_HISTORY_FILES);
+        assert_eq!(defaults.compression(), Compression::default());
+
+        let custom = WorkspaceSettings {
+            max_history_files: Some(50),
+            compression_level: Some(1),
+        };
+        assert!(custom.validate().is_ok());
+        assert_eq!(custom.history_limit(), 50);
+        assert_eq!(custom.compression(), Compression::fast());
+
+        let bad_level = WorkspaceSettings {
+            compression_level: Some(10),
+            ..Default::default()
+        };
+        assert!(bad_level.validate().is_err());
+        let bad_history = WorkspaceSettings {
+            max_history_files: Some(0),
+            ..Default::default()
+        };
+        assert!(bad_history.validate().is_err());
+    }
+
+    #[test]
+    fn test_recover_from_history() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(recover_from_history(dir.path()).is_none());
+
+        write_gz(&dir.path().join("workspaces_20250101_120000.json.gz"), r#"{"name": "old"}"#).unwrap();
+        write_gz(&dir.path().join("workspaces_20250102_120000.json.gz"), r#"{"name": "newer"}"#).unwrap();
+        // Newest snapshots are corrupt: truncated gzip and invalid JSON
+        fs::write(dir.path().join("workspaces_20250104_120000.json.gz"), b"\x1f\x8b\x08").unwrap();
+        write_gz(&dir.path().join("workspaces_20250103_120000.json.gz"), "{not json").unwrap();
+
+        let (filename, data) = recover_from_history(dir.path()).unwrap();
+        assert_eq!(filename, "workspaces_20250102_120000.json.gz");
+        assert_eq!(data, r#"{"name": "newer"}"#);
+    }
+
+    #[test]
+    fn test_workspace_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("workspaces.json.gz");
+
+        write_gz(&path, r#"{"name": "ok"}"#).unwrap();
+        assert_eq!(read_gz(&path).unwrap(), r#"{"name": "ok"}"#);
+
+        // Valid gzip whose checksum belongs to different content
+        let mut encoder = GzBuilder::new()
+            .extra(checksum_extra(r#"{"name": "other"}"#))
+            .write(Vec::new(), Compression::default());
+        encoder.write_all(br#"{"name": "ok"}"#).unwrap();
+        fs::write(&path, encoder.finish().unwrap()).unwrap();
+        assert!(read_gz(&path).unwrap_err().starts_with(CHECKSUM_MISMATCH_ERROR));
+
+        // With no snapshot to fall back on, the mismatch is reported instead of starting fresh
+        let history = dir.path().join("history");
+        fs::create_dir(&history).unwrap();
+        let error = read_or_recover(&path, &history, DEFAULT_WORKSPACE_NAME).unwrap_err();
+        assert!(error.starts_with(CHECKSUM_MISMATCH_ERROR));
+
+        // A snapshot recovers it, and the recovery says why
+        write_gz(&history.join("workspaces_20250101_120000.json.gz"), r#"{"name": "snap"}"#).unwrap();
+        let (data, recovery) = read_or_recover(&path, &history, DEFAULT_WORKSPACE_NAME).unwrap();
+        assert_eq!(data.as_deref(), Some(r#"{"name": "snap"}"#));
+        let recovery = recovery.unwrap();
+        assert!(recovery.checksum_mismatch);
+        assert_eq!(recovery.filename, "workspaces_20250101_120000.json.gz");
+
+        // Valid gzip holding broken JSON is corruption too
+        write_gz(&path, "{broken").unwrap();
+        let (data, recovery) = read_or_recover(&path, &history, DEFAULT_WORKSPACE_NAME).unwrap();
+        assert_eq!(data.as_deref(), Some(r#"{"name": "snap"}"#));
+        assert!(!recovery.unwrap().checksum_mismatch);
+
+        // A file that can't be read at all is an error, not a reason to recover
+        let unreadable = dir.path().join("unreadable.json.gz");
+        fs::create_dir(&unreadable).unwrap();
+        let error = read_or_recover(&unreadable, &history, DEFAULT_WORKSPACE_NAME).unwrap_err();
+        assert!(error.starts_with("Failed to open config file"), "{}", error);
+
+        // Ordinary corruption without a snapshot still starts fresh
+        fs::remove_file(history.join("workspaces_20250101_120000.json.gz")).unwrap();
+        fs::write(&path, b"\x1f\x8b\x08").unwrap();
+        assert!(matches!(read_or_recover(&path, &history, DEFAULT_WORKSPACE_NAME), Ok((None, None))));
+
+        // Legacy files without a checksum still load
+        let mut encoder = GzBuilder::new().write(Vec::new(), Compression::default());
+        encoder.write_all(br#"{"name": "legacy"}"#).unwrap();
+        fs::write(&path, encoder.finish().unwrap()).unwrap();
+        assert_eq!(read_gz(&path).unwrap(), r#"{"name": "legacy"}"#);
+    }
+
+    #[test]
+    fn test_read_and_validate_workspace_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let gz_path = dir.path().join("export.json.gz");
+        let json_path = dir.path().join("export.json");
+        write_gz(&gz_path, WORKSPACE_JSON).unwrap();
+        fs::write(&json_path, WORKSPACE_JSON).unwrap();
+
+        assert_eq!(read_workspace_export(&gz_path).unwrap(), WORKSPACE_JSON);
+        assert_eq!(read_workspace_export(&json_path).unwrap(), WORKSPACE_JSON);
+        assert!(validate_workspace_json(WORKSPACE_JSON).is_ok());
+
+        assert!(validate_workspace_json("[1, 2]").is_err());
+        assert!(validate_workspace_json(r#"{"name": "no terminals"}"#).is_err());
+        assert!(validate_workspace_json("{broken").is_err());
+    }
+
+    #[test]
+    fn test_workspace_schema_migration() {
+        let legacy = r#"{"name":"old","terminals":[]}"#;
+        let root: serde_json::Value = serde_json::from_str(legacy).unwrap();
+        assert_eq!(detect_schema_version(&root), 0);
+
+        let migrated = migrate_workspace(legacy, 0).unwrap();
+        let root: serde_json::Value = serde_json::from_str(&migrated).unwrap();
+        assert_eq!(detect_schema_version(&root), WORKSPACE_SCHEMA_VERSION);
+        assert_eq!(root["data"]["name"], "old");
+
+        // Already current data is left as is
+        assert_eq!(migrate_workspace(&migrated, 1).unwrap(), migrated);
+        assert!(migrate_workspace(&migrated, WORKSPACE_SCHEMA_VERSION + 1).is_err());
+
+        // Both formats unwrap to the same app data
+        let old = unwrap_workspace(legacy).unwrap();
+        let new = unwrap_workspace(&migrated).unwrap();
+        assert_eq!((old.schema_version, new.schema_version), (0, 1));
+        assert_eq!(old.data, new.data);
+
+        // A legacy workspace that merely has a schema_version field stays v0
+        let root: serde_json::Value = serde_json::from_str(r#"{"schema_version":3}"#).unwrap();
+        assert_eq!(detect_schema_version(&root), 0);
+    }
+
+    #[test]
+    fn test_unwrap_workspace_returns_data_as_saved() {
+        // Unsorted keys and the frontend's own formatting must survive a load
+        let data = "{\"zeta\": 1,\n  \"alpha\": [ 2, 3 ]}";
+        let stored = wrap_workspace(data).unwrap();
+        assert_eq!(unwrap_workspace(&stored).unwrap().data, data);
+        assert_eq!(unwrap_workspace(data).unwrap().data, data);
+    }
+
+    #[test]
+    fn test_purge_plaintext_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let plain = dir.path().join("workspaces_20250101_120000.json.gz");
+        let encrypted = dir.path().join("workspaces_20250102_120000.json.gz");
+        let unrelated = dir.path().join("notes.txt");
+        let data = r#"{"terminals":[{"id":"t1","cwd":"/secret"}]}"#;
+        write_gz(&plain, &wrap_workspace(data).unwrap()).unwrap();
+        let sealed = encrypt_workspace(data, "hunter2").unwrap();
+        write_gz(&encrypted, &wrap_workspace(&sealed).unwrap()).unwrap();
+        fs::write(&unrelated, "keep").unwrap();
+
+        assert_eq!(purge_plaintext_history(dir.path()).unwrap(), 1);
+        assert!(!plain.exists());
+        assert!(encrypted.exists());
+        assert!(unrelated.exists());
+
+        assert_eq!(purge_plaintext_history(&dir.path().join("missing")).unwrap(), 0);
+    }
 }
@@ -1,6 +1,6 @@
 import { invoke } from '@tauri-apps/api/core'
 import { listen, UnlistenFn } from '@tauri-apps/api/event'
-import type { CreatePtyOptions } from '../types'
+import type { CreatePtyOptions, PtyExitEvent } from '../types'
 
 export interface TauriAPI {
   pty: {
@@ -11,7 +11,7 @@ export interface TauriAPI {
     restart: (id: string, cwd: string) => Promise<boolean>
     getCwd: (id: string) => Promise<string | null>
     onOutput: (callback: (id: string, data: string) => void) => Promise<UnlistenFn>
-    onExit: (callback: (id: string, exitCode: number) => void) => Promise<UnlistenFn>
+    onExit: (callback: (id: string, exitCode: number, signal: string | null) => void) => Promise<UnlistenFn>
   }
   workspace: {
     save: (data: string) => Promise<boolean>
@@ -62,10 +62,11 @@ export const tauriAPI: TauriAPI = {
       })
     },
 
-    onExit: async (callback: (id: string, exitCode: number) => void): Promise<UnlistenFn> => {
-      return listen<[string, number]>('pty:exit', (event) => {
-        const [id, exitCode] = event.payload
-        callback(id, exitCode)
+    onExit: async (callback: (id: string, exitCode: number, signal: string | null) => void): Promise<UnlistenFn> => {
+      return listen<PtyExitEvent>('pty:exit', (event) => {
+        const { id, exit_code, signal } = event.payload
+        // Signal-terminated processes have no exit code
+        callback(id, exit_code ?? 1, signal)
       })
     },
   },
@@ -85,6 +85,17 @@ export interface PtyExit {
   exitCode: number;
 }
 
+// Payload of the pty:exit event
+export interface PtyExitEvent {
+  id: string;
+  exit_code: number | null;     // null when terminated by a signal
+  signal: string | null;        // e.g. "SIGKILL"
+  signal_number: number | null; // e.g. 9
+  core_dumped: boolean;
+  reason: string | null;        // e.g. "idle_timeout" when moonterm ended the session
+  success: boolean;
+}
+
 // Command bookmark for quick access to frequently used commands
 export interface CommandBookmark {
   id: string;